@@ -2,14 +2,115 @@
 
 #[ink::contract]
 mod karera_platform {
-    use ink::storage::Mapping;
+    use ink::env::call::{build_call, ExecutionInput, Selector};
+    use ink::env::hash::Blake2x256;
+    use ink::prelude::string::String;
+    use ink::storage::{Lazy, Mapping};
     use ink::prelude::vec::Vec;
 
     // Race duration: 10 minutes = 600 seconds / 6 seconds per block = 100 blocks
     const RACE_DURATION_BLOCKS: u32 = 100;
-    const HORSES_PER_RACE: u8 = 6;
+    const MIN_FIELD_SIZE: u8 = 2;
+    const MAX_FIELD_SIZE: u8 = 20;
     const TOTAL_RACES: u8 = 5;
     const FINISH_LINE: u32 = 1000; // Distance units to finish
+    const STORAGE_VERSION: u32 = 2;
+    const UPGRADE_DELAY_BLOCKS: u32 = 14_400; // 24 hours of 6 second blocks
+    const MAX_HOUSE_FEE_BPS: u16 = 2_000; // House fee is capped at 20%
+    const BPS_DENOMINATOR: u128 = 10_000;
+    const MAX_PAGE_SIZE: u32 = 50;
+    const DEFAULT_DIVERGENCE_THRESHOLD_BPS: u16 = 1_000;
+    const ARCHIVE_GRACE_BLOCKS: u32 = 100_800; // 7 days of 6 second blocks
+    const MAX_ARCHIVE_BATCH: u32 = 50;
+    const MAX_CLAIM_CODE_BLOCKS: u32 = 100_800; // 7 days of 6 second blocks
+    // Bumped whenever the movement algorithm changes; races keep the version they started with
+    const SIM_ALGORITHM_VERSION: u16 = 6;
+    const MAX_REFERRAL_BPS: u16 = 5_000; // Referrers get at most half of the house fee
+    const DEFAULT_RACE_LEAD_BLOCKS: u32 = 600; // Races are scheduled an hour after creation
+    const DEFAULT_BETTING_CUTOFF_BLOCKS: u32 = 10; // Betting closes a minute before the scheduled start
+    const STREAK_COOLING_OFF_BLOCKS: u32 = 600; // One hour between acknowledging a streak and betting again
+    const MAX_CATCHUP_BLOCKS: u32 = 20; // Simulated blocks per update_race call
+    const MAX_PROMOTIONS: usize = 10;
+    const MAX_SLIP_LEGS: usize = 10;
+    const MAX_PARLAY_LEGS: usize = TOTAL_RACES as usize;
+    const DEFAULT_PARLAY_MAX_MULTIPLE: u32 = 50; // Parlays pay at most 50 times the stake
+    const LIVE_BETTING_CUTOFF_BPS: u128 = 8_000; // Live betting closes once any horse passes 80% of the distance
+    const DEFAULT_PURSE_VESTING_BLOCKS: u32 = 100_800; // Purses vest linearly over 7 days
+    const MAX_FREEZE_BLOCKS: u32 = 201_600; // Freezes expire within 14 days
+    const MAX_HORSE_NAME_LEN: usize = 32;
+    const MAX_METADATA_URI_LEN: usize = 128;
+    const DEFAULT_STEAM_THRESHOLD_BPS: u16 = 1_000;
+    const DEFAULT_STEAM_WINDOW_BLOCKS: u32 = 100;
+    const PICK_POINTS: u32 = 10; // Points for a correct free pick
+    const LEADERBOARD_SIZE: usize = 10;
+    const MAX_MIRRORS: usize = 8;
+    const EMERGENCY_DELAY_BLOCKS: u32 = 100_800; // 7 days of 6 second blocks
+    const MAX_CONSOLATION_BPS: u16 = 5_000; // At most half the net pool goes to the runner-up
+    const LOYALTY_MAX_STREAK_BONUS: u32 = 5; // Bonus points per race stop growing after a 5 race streak
+    const LOYALTY_WIN_POINTS: u32 = 1;
+    const MAX_TOURNAMENT_RACES: usize = 16;
+    const MAX_RACE_DURATION_BLOCKS: u32 = 1_200; // Races run for at most two hours
+    const MAX_BOX_HORSES: usize = 5; // A five horse box is 60 permutations
+    const XP_PER_LEVEL: u32 = 10; // Career experience needed per simulation bonus level
+    const MAX_EXPERIENCE_LEVEL: u32 = 2;
+    const FORM_LENGTH: usize = 5; // Finishing positions kept in a career's form line
+    const REWARD_PRECISION: u128 = 1_000_000_000_000;
+    const DEFAULT_MAX_BETS_PER_RACE: u32 = 100; // Keeps the per-bettor loops in claims bounded
+    const MAX_SIGNERS: usize = 10;
+    const MAX_MIGRATION_BATCH: u8 = 10; // Races moved per migrate call
+    const FORCE_SETTLE_TIMEOUT_BLOCKS: u32 = 14_400; // A race left unsettled 24 hours past its end can be forced
+    const HANDICAP_PER_WIN: u8 = 2; // Weight penalty per win in a registered horse's form line
+    const MAX_HANDICAP: u8 = 6;
+    const MAX_HELD_TICKETS: usize = 100; // Tickets received by transfer per holder and race, bounding claims
+    const MAX_RACE_ASSETS: usize = 4; // PSP22 pools per race alongside the native pool
+    const MAX_ASSET_BETS: usize = 20; // Asset bets per bettor and race, bounding claims
+
+    /// Full 256-bit product of two u128 values as (high, low) halves
+    fn widening_mul(a: u128, b: u128) -> (u128, u128) {
+        const MASK: u128 = u64::MAX as u128;
+        let (a_hi, a_lo) = (a >> 64, a & MASK);
+        let (b_hi, b_lo) = (b >> 64, b & MASK);
+
+        let lo_lo = a_lo * b_lo;
+        let hi_lo = a_hi * b_lo;
+        let lo_hi = a_lo * b_hi;
+        let hi_hi = a_hi * b_hi;
+
+        let cross = (lo_lo >> 64) + (hi_lo & MASK) + lo_hi;
+        let high = hi_hi + (hi_lo >> 64) + (cross >> 64);
+        let low = (cross << 64) | (lo_lo & MASK);
+        (high, low)
+    }
+
+    /// `a * b / c` rounded down, with a 256-bit intermediate product.
+    /// Fails if `c` is zero or the quotient does not fit in a u128.
+    fn mul_div(a: u128, b: u128, c: u128) -> Result<u128, Error> {
+        if c == 0 {
+            return Err(Error::MathOverflow);
+        }
+
+        let (high, low) = widening_mul(a, b);
+        if high == 0 {
+            return Ok(low / c);
+        }
+        if high >= c {
+            return Err(Error::MathOverflow);
+        }
+
+        // Long division of the 256-bit product, one bit of the low half at a time
+        let mut remainder = high;
+        let mut quotient = 0u128;
+        for bit in (0..128).rev() {
+            let carry = remainder >> 127;
+            remainder = (remainder << 1) | ((low >> bit) & 1);
+            quotient <<= 1;
+            if carry == 1 || remainder >= c {
+                remainder = remainder.wrapping_sub(c);
+                quotient |= 1;
+            }
+        }
+        Ok(quotient)
+    }
 
     #[derive(Debug, PartialEq, Eq, Clone, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
@@ -17,6 +118,8 @@ mod karera_platform {
         Pending,
         Active,
         Finished,
+        Cancelled,
+        Archived,
     }
 
     #[derive(Debug, Clone, scale::Encode, scale::Decode)]
@@ -26,6 +129,25 @@ mod karera_platform {
         pub position: u32,
         pub finished: bool,
         pub finish_time: Option<u32>,
+        pub scratched: bool,
+        // Distance covered in the latest update, for client-side interpolation
+        pub last_velocity: u32,
+        pub last_update_block: u32,
+        // Liking for soft going from 0 to 100; helps on muddy and sloppy tracks
+        pub affinity: u8,
+        // Experience level of the registered horse running here, pinned at entry
+        pub experience: u8,
+        // Weight penalty of the registered horse running here for its recent wins, pinned at entry
+        pub handicap: u8,
+        // Filled in from `horse_metadata` by the horse views, never kept in horse storage
+        pub metadata: Option<HorseMetadata>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct HorseMetadata {
+        pub name: String,
+        pub metadata_uri: String,
     }
 
     #[derive(Debug, Clone, scale::Encode, scale::Decode)]
@@ -36,31 +158,974 @@ mod karera_platform {
         pub start_block: u32,
         pub current_block: u32,
         pub winner: Option<u8>,
+        // All horses sharing first place; more than one on a dead heat
+        pub winners: Vec<u8>,
         pub rankings: Vec<u8>,
+        pub season: u32,
+        pub organizer: Option<AccountId>,
+        // Block at which the race finished or was cancelled
+        pub settled_at: u32,
+        // Movement algorithm version pinned when the race started
+        pub sim_version: u16,
+        pub scheduled_start: u32,
+        pub grade: RaceGrade,
+        // Number of horses entered; horse ids run from 0 to field_size - 1
+        pub field_size: u8,
+        // Chosen by the operator before the start, otherwise drawn at `start_race`
+        pub track_condition: Option<TrackCondition>,
+        pub bet_mode: BetMode,
+        // Race length, pinned again when the race starts
+        pub duration_blocks: u32,
+        // When set, the result is reported by this oracle instead of simulated
+        pub oracle: Option<AccountId>,
     }
 
     #[derive(Debug, Clone, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
     pub struct Bet {
+        // Global sequential id, see `get_bet_by_id`
+        pub id: u64,
+        pub bettor: AccountId,
+        pub race_id: u8,
+        pub horse_id: u8,
+        pub amount: Balance,
+    }
+
+    /// A bet in an approved PSP22 asset, pooled with the race's other bets in that asset
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct AssetBet {
+        pub asset: AccountId,
+        pub horse_id: u8,
+        pub amount: Balance,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum HoldReason {
+        SuspiciousBetting,
+        VeterinaryCheck,
+        StewardsInquiry,
+        Other,
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct DayReport {
+        pub day: u32,
+        pub closed_at: u32,
+        pub races_settled: u32,
+        pub handle: Balance,
+        pub payouts: Balance,
+        pub refunds: Balance,
+        pub fees: Balance,
+        pub unclaimed_balance: Balance,
+    }
+
+    #[derive(Debug, Default, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum BetMode {
+        #[default]
+        Parimutuel,
+        // Bettors lock the operator's posted odds, paid out of stakes and an operator reserve
+        FixedOdds,
+    }
+
+    #[derive(Debug, Default, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum TrackCondition {
+        #[default]
+        Fast,
+        Good,
+        Muddy,
+        Sloppy,
+    }
+
+    #[derive(Debug, Default, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum RaceGrade {
+        #[default]
+        Ungraded,
+        Grade3,
+        Grade2,
+        Grade1,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum Role {
+        Owner,
+        RaceManager,
+        Treasurer,
+        Integrity,
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct SeasonPnl {
+        pub staked: Balance,
+        pub returned: Balance,
+    }
+
+    /// A pending race and the blocks left until its scheduled start
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct UpcomingRace {
+        pub race_id: u8,
+        pub scheduled_start: u32,
+        pub blocks_until_start: u32,
+        // Anyone may start the race once its scheduled start is reached
+        pub auto_start: bool,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct OddsDivergence {
+        pub horse_id: u8,
+        pub published_bps: u16,
+        pub pool_bps: u16,
+        pub divergence_bps: u16,
+        pub flagged: bool,
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Organizer {
+        pub active: bool,
+        pub bond: Balance,
+        pub fee_share_bps: u16,
+        pub races_created: u32,
+        pub open_races: u32,
+        pub fees_earned: Balance,
+        pub fees_withdrawn: Balance,
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct OrganizerGuardrails {
+        pub min_bond: Balance,
+        // 0 leaves organizer pools uncapped
+        pub max_pool: Balance,
+        pub max_fee_share_bps: u16,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct ClaimCode {
+        pub bettor: AccountId,
+        pub race_id: u8,
+        pub expires_at: u32,
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct NewAccountLimits {
+        // Accounts are limited until they have entered this many races...
+        pub ramp_races: u32,
+        // ...and been betting for this many blocks
+        pub ramp_blocks: u32,
+        pub base_max_bet: Balance,
+        pub increment_per_race: Balance,
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct AccountHistory {
+        pub first_bet_block: u32,
+        pub races_entered: u32,
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct RaceHighlights {
+        pub lead_changes: u32,
+        // Horse that gained the most places from its worst running position
+        pub comeback_horse: Option<u8>,
+        pub comeback_places: u8,
+        // Distance between winner and runner-up when the winner crossed the line
+        pub winning_margin: u32,
+        // Largest distance covered by one horse in a single update
+        pub fastest_horse: Option<u8>,
+        pub fastest_distance: u32,
+        pub fastest_block: u32,
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct RaceTracking {
+        pub leader: Option<u8>,
+        pub margin_recorded: bool,
+        pub highlights: RaceHighlights,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum CardStrategy {
+        // Split evenly, backing the given horse in each race of the card
+        EqualSplit(Vec<u8>),
+        // Split evenly, backing the pool favorite in each race
+        FavoritesOnly,
+        // Split by weight, backing the given (horse, weight) in each race
+        Weighted(Vec<(u8, u32)>),
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct StreakState {
+        pub consecutive_losses: u32,
+        // Losing streak length that requires `acknowledge_streak`; zero disables the check
+        pub threshold: u32,
+        pub cooling_off_until: u32,
+    }
+
+    /// Reduced takeout for races of the given grades starting within a block window
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct TakeoutPromotion {
+        pub id: u32,
+        pub start_block: u32,
+        pub end_block: u32,
+        pub fee_bps: u16,
+        pub grades: Vec<RaceGrade>,
+    }
+
+    /// Bet authorized off-chain by `bettor` and submitted by a relayer
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct BetOrder {
+        pub bettor: AccountId,
+        pub race_id: u8,
+        pub horse_id: u8,
+        pub amount: Balance,
+        pub nonce: u64,
+        // Last block at which the order may be submitted
+        pub deadline: u32,
+    }
+
+    /// One bet of a slip
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct SlipLeg {
+        pub race_id: u8,
+        pub horse_id: u8,
+        pub amount: Balance,
+    }
+
+    /// What earlier legs of the same slip add ahead of a leg
+    #[derive(Debug, Default, Clone, Copy)]
+    struct PriorLegs {
+        // Staked in the leg's season
+        stake: Balance,
+        // Added to the leg's race, and to its horse
+        pool: Balance,
+        horse_pool: Balance,
+        // Bets on the leg's race, and in all
+        race_bets: u32,
+        bets: u32,
+    }
+
+    /// Result of one simulation step
+    #[derive(Debug, Default)]
+    struct StepOutcome {
+        // Positions and velocities of the horses that moved
+        positions: HorseReadings,
+        velocities: HorseReadings,
+        all_finished: bool,
+    }
+
+    /// Multi-race bet that pays only if every selection wins
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Parlay {
         pub bettor: AccountId,
+        // (race_id, horse_id) selections, one per race
+        pub legs: Vec<(u8, u8)>,
+        pub amount: Balance,
+        // House fees reserved for the winnings; the payout is capped at amount + reserve
+        pub reserve: Balance,
+        pub settled: bool,
+    }
+
+    /// Everything needed to audit one race, assembled by `get_race_proof`
+    #[derive(Debug, Clone, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct RaceProofBundle {
+        pub race: Race,
+        // Randomness: post position draw seed and result, revealed simulation entropy
+        pub draw_seed: Option<[u8; 32]>,
+        pub post_positions: Vec<u8>,
+        pub entropy: Option<[u8; 32]>,
+        pub bet_entropy: Option<[u8; 32]>,
+        // Recorded simulation inputs: (block, timestamp) per step
+        pub steps: Vec<(u32, u64)>,
+        // Published odds and the closing pools per horse
+        pub published_probabilities: Vec<u16>,
+        pub horse_pools: Vec<(u8, Balance)>,
+        pub total_pool: Balance,
+        pub pool_boost: Balance,
+        pub jackpot_seed: Balance,
+        // Winner odds as (net pool, winning pool), if anyone backed the winner
+        pub winner_odds: Option<(Balance, Balance)>,
+        // blake2_256 of the SCALE-encoded (rankings, winners)
+        pub results_digest: Hash,
+        pub fee: Balance,
+        pub referral_commission: Balance,
+    }
+
+    /// Bet placed while a race is running, weighted by the horse's remaining distance
+    #[derive(Debug, Clone, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct LiveBet {
+        pub horse_id: u8,
+        pub amount: Balance,
+        pub weight: Balance,
+    }
+
+    /// Vesting applied to purses: nothing is released before the cliff, then linearly
+    /// until `duration_blocks` after the race finished
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct PurseVesting {
+        pub cliff_blocks: u32,
+        pub duration_blocks: u32,
+    }
+
+    /// Purse owed to a horse owner for one race, released by `claim_purse`
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct PurseEscrow {
+        pub total: Balance,
+        pub claimed: Balance,
+        pub start_block: u32,
+        pub vesting: PurseVesting,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum FreezeReason {
+        SuspectedCollusion,
+        SuspectedMarketManipulation,
+        PaymentDispute,
+        IdentityCheck,
+        Other,
+    }
+
+    /// Temporary hold on an account's new bets and withdrawals while it is investigated
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct AccountFreeze {
+        pub reason: FreezeReason,
+        pub frozen_at: u32,
+        pub expires_at: u32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct HorseResult {
+        pub horse_id: u8,
+        pub finish_time: Option<u32>,
+        pub position: u32,
+        pub pool: Balance,
+        // Return per unit staked had this horse won alone, in basis points
+        pub odds_bps: Option<u128>,
+    }
+
+    /// Finished race in finishing order, as returned by `get_race_results`
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct RaceResults {
+        pub race_id: u8,
+        pub winners: Vec<u8>,
+        pub rankings: Vec<HorseResult>,
+        pub total_pool: Balance,
+        pub net_pool: Balance,
+        // Actual return per unit staked on the winners, in basis points
+        pub winning_odds_bps: Option<u128>,
+    }
+
+    /// Kind of market within a race and its parameters
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Market {
+        // Pre-race parimutuel win pool
+        Win { horse_id: u8 },
+        // In-running pool with progress-discounted stakes
+        Live { horse_id: u8 },
+        // Trifecta pool, boxing every ordered permutation of three of the horses
+        TrifectaBox { horses: Vec<u8> },
+    }
+
+    /// Identifies one market: the race it belongs to and the market within it
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct MarketId {
         pub race_id: u8,
+        pub market: Market,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct MarketInfo {
+        pub market_id: MarketId,
+        pub open: bool,
+        // Stake on the selection; weighted stake for live markets
+        pub pool: Balance,
+        pub total_pool: Balance,
+        // Indicative return per unit staked at the current house fee, in basis points
+        pub odds_bps: Option<u128>,
+    }
+
+    /// Free prediction on a race, no stake involved
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Pick {
         pub horse_id: u8,
+        pub scored: bool,
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Loyalty {
+        pub points: u32,
+        // Consecutive races, by id, in which the account placed a bet
+        pub streak: u32,
+        pub last_race: Option<u8>,
+        // Stake still eligible for a house-paid fee discount, from redeemed points
+        pub fee_free_stake: Balance,
+    }
+
+    /// Series of races scored on championship points, with its own pool on the overall champion
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Tournament {
+        pub id: u32,
+        pub races: Vec<u8>,
+        // Championship points by finishing position; positions past the end score nothing
+        pub points: Vec<u32>,
+        // Horse ids run from 0 to the largest field of the series
+        pub field_size: u8,
+        pub total_pool: Balance,
+        pub settled: bool,
+        // Settled with none of its races finished: every stake is refunded without a fee
+        pub refunded: bool,
+        // Horses tied on the most points once settled
+        pub champions: Vec<u8>,
+        // Champion backers are paid stake * net_pool / winning_pool
+        pub net_pool: Balance,
+        pub winning_pool: Balance,
+    }
+
+    /// Platform parameter that governance can change
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum ParameterChange {
+        HouseFee(u16),
+        RaceDuration(u32),
+        NewAccountLimits(NewAccountLimits),
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct GovernanceConfig {
+        // PSP22 token whose balances weigh proposals and votes
+        pub token: AccountId,
+        pub voting_period_blocks: u32,
+        // Votes in favour a proposal needs to pass, besides outweighing those against
+        pub quorum: Balance,
+        // Token balance needed to open a proposal
+        pub proposal_threshold: Balance,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Proposal {
+        pub id: u32,
+        pub proposer: AccountId,
+        pub change: ParameterChange,
+        pub end_block: u32,
+        pub votes_for: Balance,
+        pub votes_against: Balance,
+        pub executed: bool,
+    }
+
+    /// Pool caps of a race; `None` leaves that pool uncapped
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct PoolCaps {
+        pub per_horse: Option<Balance>,
+        pub per_race: Option<Balance>,
+    }
+
+    /// Trifecta box: every ordered permutation of `horses` for the first three places,
+    /// each carrying `unit_stake`
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct TrifectaTicket {
+        pub bettor: AccountId,
+        pub race_id: u8,
+        pub horses: Vec<u8>,
         pub amount: Balance,
+        pub unit_stake: Balance,
+        pub claimed: bool,
+    }
+
+    /// A registered horse's record across every race it has run
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct HorseCareer {
+        pub id: u32,
+        pub name: String,
+        pub starts: u32,
+        pub wins: u32,
+        // Finishes second or third
+        pub places: u32,
+        pub experience: u32,
+        // Latest finishing positions, oldest first, 1 for a win
+        pub form: Vec<u8>,
+    }
+
+    /// Error returned by PSP22 token messages, as laid out by the PSP22 standard
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum PSP22Error {
+        Custom(String),
+        InsufficientBalance,
+        InsufficientAllowance,
+        ZeroRecipientAddress,
+        ZeroSenderAddress,
+        SafeTransferCheckFailed(String),
+    }
+
+    /// Token id as laid out by the PSP34 standard. Bet tickets are `U64` of the bet id.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Id {
+        U8(u8),
+        U16(u16),
+        U32(u32),
+        U64(u64),
+        U128(u128),
+        Bytes(Vec<u8>),
+    }
+
+    /// Error returned by PSP34 token messages, as laid out by the PSP34 standard
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum PSP34Error {
+        Custom(String),
+        SelfApprove,
+        NotApproved,
+        TokenExists,
+        TokenNotExists,
+        SafeTransferCheckFailed(String),
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct StakingConfig {
+        // PSP22 token locked in the vault
+        pub token: AccountId,
+        // Share of the house fee on each settled race paid to stakers
+        pub fee_share_bps: u16,
+        pub epoch_blocks: u32,
+    }
+
+    /// A staker's position in the revenue-sharing vault
+    #[derive(Debug, Default, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct StakeInfo {
+        // Stake earning a share of every epoch closed since `reward_per_token_paid`
+        pub active: Balance,
+        // Stake that starts earning with `pending_epoch`
+        pub pending: Balance,
+        pub pending_epoch: u32,
+        pub reward_per_token_paid: u128,
+        pub rewards: Balance,
+    }
+
+    /// Limits on how often one account may bet; 0 disables a limit
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct BetRateLimits {
+        pub max_bets_per_race: u32,
+        pub max_bets_per_window: u32,
+        pub window_blocks: u32,
+    }
+
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum SweepDestination {
+        #[default]
+        Treasury,
+        Jackpot,
+    }
+
+    /// Sensitive owner operation that needs signer approvals once multisig is enabled
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum OwnerAction {
+        SetHouseFee(u16),
+        ScheduleUpgrade(Hash),
+        CancelUpgrade,
+        Upgrade(Hash),
+        InitiateEmergencyWithdraw,
+        CancelEmergencyWithdraw,
+        FinalizeEmergencyWithdraw(AccountId),
+        SetSigners(Vec<AccountId>, u32),
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct PendingAction {
+        pub action: OwnerAction,
+        pub proposer: AccountId,
+        pub confirmations: Vec<AccountId>,
+        pub executed: bool,
     }
 
+    /// Betting statistics of a race's win pool
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct RaceStats {
+        pub unique_bettors: u32,
+        pub bet_count: u32,
+        pub largest_bet: Balance,
+    }
+
+    // Composite storage keys and values
+    /// (bettor, race_id, bet index)
+    type BetKey = (AccountId, u8, u32);
+    /// (bettor, race_id)
+    type BettorRace = (AccountId, u8);
+    /// (race_id, deployment id)
+    type MirrorKey = (u8, u32);
+    /// (tournament_id, bettor, horse_id)
+    type TournamentBetKey = (u32, AccountId, u8);
+    /// (race_id, first, second, third)
+    type TrifectaKey = (u8, u8, u8, u8);
+    /// (race_id, horse_id, asset)
+    type AssetPoolKey = (u8, u8, AccountId);
+    /// (race_id, asset)
+    type RaceAsset = (u8, AccountId);
+    /// (bettor, season, asset)
+    type AssetSeasonKey = (AccountId, u32, AccountId);
+    /// (net pool, winning pool) winning stakes are priced at
+    type PoolOdds = (Balance, Balance);
+    /// (cap, season it takes effect)
+    type PendingLossCap = (Option<Balance>, u32);
+    /// (block, timestamp)
+    type StepInputs = (u32, u64);
+    /// (window start block, share in bps)
+    type SteamReference = (u32, u16);
+    /// (runner-up horse, tranche, runner-up pool)
+    type RunnerUpOdds = (u8, Balance, Balance);
+    /// (career id, experience level)
+    type Entrant = (u32, u8);
+    /// (horse_id, position or velocity) of each horse moved
+    type HorseReadings = Vec<(u8, u32)>;
+    /// (account, points), best first
+    type Standings = Vec<(AccountId, u32)>;
+    /// (bettor, bet index) of each ticket
+    type TicketRefs = Vec<(AccountId, u32)>;
+
     #[ink(storage)]
     pub struct KareraPlatform {
         owner: AccountId,
         races: Mapping<u8, Race>,
         current_race_id: u8,
-        // Store horses separately: (race_id, horse_id) -> Horse
+        // Layout v1 horse storage: (race_id, horse_id) -> Horse. Only read for races
+        // not yet moved to `race_horses` by `migrate`.
         horses: Mapping<(u8, u8), Horse>,
+        // Every horse of a race under one key, indexed by horse id
+        race_horses: Mapping<u8, Vec<Horse>>,
         // Store bets: (bettor, race_id, bet_index) -> Bet
         bet_count: Mapping<(AccountId, u8), u32>,
-        bets: Mapping<(AccountId, u8, u32), Bet>,
+        bets: Mapping<BetKey, Bet>,
         total_pool: Mapping<u8, Balance>,
         horse_pools: Mapping<(u8, u8), Balance>,
         payouts_claimed: Mapping<(AccountId, u8), bool>,
+        // Non-betting contributions added to a race's pool by fans or sponsors
+        pool_boosts: Mapping<u8, Balance>,
+        // Layout version of this storage, bumped by `migrate`
+        storage_version: u32,
+        // Scheduled code upgrade: (code_hash, earliest block it may be applied)
+        pending_upgrade: Option<(Hash, u32)>,
+        // Integrity holds blocking new bets on a horse: (race_id, horse_id) -> reason
+        horse_holds: Mapping<(u8, u8), HoldReason>,
+        scratch_refunds_claimed: Mapping<(AccountId, u8), bool>,
+        // Stakes on scratched horses not yet refunded, per race
+        scratch_unrefunded: Mapping<u8, Balance>,
+        // Live and trifecta stakes on a cancelled race not yet refunded
+        cancel_unrefunded: Mapping<u8, Balance>,
+        // House fee taken from each race's pool at settlement, in basis points
+        house_fee_bps: u16,
+        accrued_fees: Balance,
+        race_fees: Mapping<u8, Balance>,
+        // Winnings owed to bettors of settled races but not yet claimed
+        unclaimed_winnings: Balance,
+        current_day: u32,
+        // Running totals for the current day, stored by day index on `close_day`
+        day_totals: DayReport,
+        day_reports: Mapping<u32, DayReport>,
+        // Operator roles; the Owner role passes every role check
+        roles: Mapping<AccountId, Role>,
+        current_season: u32,
+        // Opt-in per-season loss caps; raising or removing a cap waits for the next season
+        loss_caps: Mapping<AccountId, Balance>,
+        pending_loss_caps: Mapping<AccountId, PendingLossCap>,
+        // (bettor, season) -> amounts staked and returned in races of that season
+        season_pnl: Mapping<(AccountId, u32), SeasonPnl>,
+        // Calibrated win probabilities per race, in basis points indexed by horse id
+        published_probabilities: Mapping<u8, Vec<u16>>,
+        divergence_threshold_bps: u16,
+        // Community organizers running their own races within platform guardrails
+        organizers: Mapping<AccountId, Organizer>,
+        organizer_guardrails: OrganizerGuardrails,
+        // Bonds posted and fees earned but not withdrawn, over all organizers
+        organizer_bonds: Balance,
+        organizer_fees_owed: Balance,
+        // Index of distinct bettors per race: (race_id, index) -> bettor
+        race_bettors: Mapping<(u8, u32), AccountId>,
+        race_bettor_count: Mapping<u8, u32>,
+        // Stake paid back out of a race through winning claims and refunds
+        settled_stake: Mapping<u8, Balance>,
+        // Bettors already cleaned up by an in-progress `archive_race`
+        archive_cursor: Mapping<u8, u32>,
+        // Commit-reveal entropy from bettors: (race_id, account) -> hash(salt ++ account)
+        entropy_commitments: Mapping<(u8, AccountId), Hash>,
+        // Revealed salts folded together, mixed into the race's acceleration rolls
+        race_entropy: Mapping<u8, [u8; 32]>,
+        // Rolling hash of every bet placed on a race, mixed in alongside `race_entropy`
+        bet_entropy: Mapping<u8, [u8; 32]>,
+        // Hash-locked claim codes: blake2_256(preimage) -> code
+        claim_codes: Mapping<Hash, ClaimCode>,
+        bettor_claim_codes: Mapping<(AccountId, u8), Hash>,
+        // Inputs of every simulation step, for replay: (race_id, step) -> (block, timestamp)
+        race_steps: Mapping<(u8, u32), StepInputs>,
+        race_step_count: Mapping<u8, u32>,
+        // Pools nobody could win, carried into the next platform race
+        jackpot: Balance,
+        race_jackpot_seed: Mapping<u8, Balance>,
+        // Bet size ramp for new accounts, disabled while both thresholds are zero
+        new_account_limits: NewAccountLimits,
+        account_history: Mapping<AccountId, AccountHistory>,
+        // Referral program: referee -> referrer, paid a share of the house fee on referred bets
+        referrers: Mapping<AccountId, AccountId>,
+        referral_bps: u16,
+        referred_handle: Mapping<(AccountId, u8), Balance>,
+        race_referred_handle: Mapping<u8, Balance>,
+        // Referral share in force when each race settled
+        race_referral_rate: Mapping<u8, u16>,
+        // Commissions set aside when each race settled, and the (commission, referred handle)
+        // still unclaimed on it
+        race_referral_commissions: Mapping<u8, Balance>,
+        race_referral_unclaimed: Mapping<u8, (Balance, Balance)>,
+        // Commissions set aside but not yet claimed, over all races
+        referral_owed: Balance,
+        referral_claimed: Mapping<(AccountId, u8), bool>,
+        // Blocks between race creation and its scheduled start
+        race_lead_blocks: u32,
+        // Betting closes this many blocks before the scheduled start
+        betting_cutoff_blocks: u32,
+        // Running statistics while a race is active, summarized into highlights at finish
+        race_tracking: Mapping<u8, RaceTracking>,
+        worst_ranks: Mapping<(u8, u8), u8>,
+        race_highlights: Mapping<u8, RaceHighlights>,
+        // Race cards grouping a day's races: card_id -> race ids
+        cards: Mapping<u32, Vec<u8>>,
+        next_card_id: u32,
+        // Losing streak tracking for the cooling-off check
+        streaks: Mapping<AccountId, StreakState>,
+        streak_counted: Mapping<(AccountId, u8), bool>,
+        // Scheduled takeout promotions, pruned once expired
+        promotions: Vec<TakeoutPromotion>,
+        next_promotion_id: u32,
+        // Prepaid balances funding signed bets
+        deposits: Mapping<AccountId, Balance>,
+        total_deposits: Balance,
+        bet_nonces: Mapping<AccountId, u64>,
+        // Post position draw: seed and horse ids in post order, drawn at race creation
+        draw_seeds: Mapping<u8, [u8; 32]>,
+        post_positions: Mapping<u8, Vec<u8>>,
+        // House-banked parlays and the winner odds they settle at
+        parlays: Mapping<u32, Parlay>,
+        next_parlay_id: u32,
+        // Stakes of parlays not yet settled
+        open_parlay_stakes: Balance,
+        // House fees reserved for the winnings of parlays not yet settled
+        parlay_reserves: Balance,
+        // Most a parlay can pay, as a multiple of its stake
+        parlay_max_multiple: u32,
+        race_odds: Mapping<u8, (Balance, Balance)>,
+        // In-running bets: weighted pools per horse, staked totals and fees per race
+        live_bets: Mapping<BetKey, LiveBet>,
+        live_bet_count: Mapping<(AccountId, u8), u32>,
+        live_pools: Mapping<(u8, u8), Balance>,
+        live_total: Mapping<u8, Balance>,
+        live_fees: Mapping<u8, Balance>,
+        live_claimed: Mapping<(AccountId, u8), bool>,
+        // Race purses paid to the owners of the winning horses through vesting escrows
+        horse_owners: Mapping<(u8, u8), AccountId>,
+        race_purses: Mapping<u8, Balance>,
+        purse_escrows: Mapping<(AccountId, u8), PurseEscrow>,
+        purse_vesting: PurseVesting,
+        frozen_accounts: Mapping<AccountId, AccountFreeze>,
+        horse_metadata: Mapping<(u8, u8), HorseMetadata>,
+        // Steam detection: pool share reference per horse as (window start block, share in bps)
+        steam_references: Mapping<(u8, u8), SteamReference>,
+        steam_threshold_bps: u16,
+        steam_window_blocks: u32,
+        // Free-to-play predictions and season points
+        picks: Mapping<(AccountId, u8), Pick>,
+        season_points: Mapping<(AccountId, u32), u32>,
+        leaderboards: Mapping<u32, Standings>,
+        // Staked pool a race needs at its start, otherwise it is cancelled and refunded
+        min_pool: Balance,
+        // Mirrored deployments of the same races, netted into one settlement price.
+        // `mirror_pools` holds gross per-horse pools reported by the bridge for each deployment;
+        // `mirror_settlements` what this deployment owes (+) or is owed by (-) each mirror.
+        bridge: Option<AccountId>,
+        mirror_pools: Mapping<MirrorKey, Vec<Balance>>,
+        mirror_deployments: Mapping<u8, Vec<u32>>,
+        mirror_settlements: Mapping<(u8, u32), i128>,
+        // Block from which a started emergency withdrawal may be finalized
+        emergency_withdraw_at: Option<u32>,
+        // Consolation mode: share of the net pool paid to backers of the runner-up,
+        // settled as (runner-up horse, tranche, runner-up pool)
+        race_consolation: Mapping<u8, u16>,
+        runner_up_odds: Mapping<u8, RunnerUpOdds>,
+        loyalty: Mapping<AccountId, Loyalty>,
+        // Fee-free stake granted per redeemed loyalty point; zero disables redemption
+        loyalty_point_value: Balance,
+        // Decimal odds in basis points posted per (race, horse) on fixed-odds races
+        fixed_odds: Mapping<(u8, u8), u32>,
+        // Operator reserve backing a fixed-odds race: (funder, amount)
+        odds_reserves: Mapping<u8, (AccountId, Balance)>,
+        // Payout owed on each horse of a fixed-odds race should it win
+        fixed_liabilities: Mapping<(u8, u8), Balance>,
+        // Odds locked by each fixed-odds bet, keyed like `bets`
+        bet_odds: Mapping<BetKey, u32>,
+        tournaments: Mapping<u32, Tournament>,
+        next_tournament_id: u32,
+        race_tournament: Mapping<u8, u32>,
+        // Championship points per (tournament, horse)
+        championship_points: Mapping<(u32, u8), u32>,
+        // Champion pool stake per (tournament, horse), and per (tournament, bettor, horse)
+        tournament_pools: Mapping<(u32, u8), Balance>,
+        tournament_bets: Mapping<TournamentBetKey, Balance>,
+        tournament_claimed: Mapping<(u32, AccountId), bool>,
+        // Champion pools of tournaments not yet settled
+        open_tournament_pools: Balance,
+        race_duration_blocks: u32,
+        // Once set, governance token holders control the house fee, race duration and bet limits
+        governance: Option<GovernanceConfig>,
+        proposals: Mapping<u32, Proposal>,
+        next_proposal_id: u32,
+        proposal_votes: Mapping<(u32, AccountId), bool>,
+        // Governance tokens locked by proposers and voters until each proposal's voting ends
+        governance_locks: Mapping<(u32, AccountId), Balance>,
+        pool_caps: Mapping<u8, PoolCaps>,
+        // Most a winning ticket may return, as a multiple of its stake in basis points
+        payout_caps: Mapping<u8, u32>,
+        // Pro-rata factor in basis points applied to every payout of a race that could not cover its winners
+        payout_factors: Mapping<u8, u32>,
+        trifecta_tickets: Mapping<u32, TrifectaTicket>,
+        next_trifecta_ticket_id: u32,
+        // Trifecta tickets of each (bettor, race)
+        trifecta_ticket_ids: Mapping<BettorRace, Vec<u32>>,
+        // Stake on each ordered (race, first, second, third) combination
+        trifecta_pools: Mapping<TrifectaKey, Balance>,
+        trifecta_totals: Mapping<u8, Balance>,
+        trifecta_fees: Mapping<u8, Balance>,
+        // Every bet by its global id, in placement order
+        bets_by_id: Mapping<u64, Bet>,
+        next_bet_id: u64,
+        horse_careers: Mapping<u32, HorseCareer>,
+        horse_career_count: u32,
+        // (career id, experience level) of the registered horse in each (race, horse) slot
+        race_entrants: Mapping<(u8, u8), Entrant>,
+        staking: Option<StakingConfig>,
+        stakes: Mapping<AccountId, StakeInfo>,
+        total_staked: Balance,
+        // Stake waiting to become active at the start of each epoch
+        pending_stake: Mapping<u32, Balance>,
+        staking_epoch: u32,
+        staking_epoch_start: u32,
+        // Fees collected for stakers in the open epoch
+        epoch_rewards: Balance,
+        // Rewards per staked token, scaled by REWARD_PRECISION, over all closed epochs
+        reward_per_token: u128,
+        // Value of `reward_per_token` when each epoch opened
+        epoch_reward_per_token: Mapping<u32, u128>,
+        // Rewards of closed epochs not yet claimed
+        staking_rewards_owed: Balance,
+        bet_rate_limits: BetRateLimits,
+        // (window index, bets placed in it) per account
+        bet_windows: Mapping<AccountId, (u32, u32)>,
+        // Blocks after a race finishes during which winnings can be claimed; 0 never expires
+        claim_window_blocks: u32,
+        sweep_destination: SweepDestination,
+        // Winnings still owed on each finished race
+        race_unclaimed: Mapping<u8, Balance>,
+        // Amount swept from each race whose claim window expired
+        race_swept: Mapping<u8, Balance>,
+        // Winnings owed on each finished race's main pool, winners and consolation, not yet paid
+        pool_winnings_unpaid: Mapping<u8, Balance>,
+        // Left over from each race's main pool once every winning ticket is paid, lost to rounding
+        rounding_dust: Mapping<u8, Balance>,
+        // Positions of the horses moved at each (race, block offset from the start)
+        replay_snapshots: Mapping<(u8, u32), HorseReadings>,
+        // Multisig over sensitive owner actions; a threshold of 0 leaves them to the owner
+        signers: Vec<AccountId>,
+        approval_threshold: u32,
+        pending_actions: Mapping<u32, PendingAction>,
+        next_action_id: u32,
+        // Next race to move to the v2 horse layout. Kept out of the root cell, which code at
+        // a new layout could not decode with the field added.
+        migration_cursor: Lazy<u8>,
+        // Betting statistics per race
+        race_stats: Mapping<u8, RaceStats>,
+        // Accounts excluded from placing new bets, e.g. self-excluded players
+        restricted_accounts: Mapping<AccountId, ()>,
+        // Handicap weight of each horse of a race drawn from the registry, by horse id
+        race_handicaps: Mapping<u8, Vec<u8>>,
+        // Bets and claims advance the running race when set
+        lazy_progression: bool,
+        // Bet tickets: PSP34 tokens keyed by bet id, held by the bettor until transferred.
+        // `ticket_holders` is only set once a ticket changes hands.
+        ticket_holders: Mapping<u64, AccountId>,
+        burned_tickets: Mapping<u64, ()>,
+        ticket_balances: Mapping<AccountId, u32>,
+        ticket_supply: u64,
+        // Tickets received by transfer: (holder, race_id) -> (bettor, bet index)
+        acquired_tickets: Mapping<BettorRace, TicketRefs>,
+        ticket_approvals: Mapping<u64, AccountId>,
+        // Operators approved for every ticket of an owner: (owner, operator)
+        ticket_operators: Mapping<(AccountId, AccountId), ()>,
+        // Races created by `create_race_at`, startable by anyone from their scheduled start
+        auto_start_races: Mapping<u8, ()>,
+        // PSP22 tokens bettors may back horses in, each pooled apart from the native pool
+        approved_assets: Mapping<AccountId, ()>,
+        // Assets bet on each race, in order of their first bet
+        race_assets: Mapping<u8, Vec<AccountId>>,
+        asset_pools: Mapping<AssetPoolKey, Balance>,
+        asset_totals: Mapping<(u8, AccountId), Balance>,
+        asset_bets: Mapping<BettorRace, Vec<AssetBet>>,
+        // (net pool, winning pool) of each asset pool on a finished race
+        asset_odds: Mapping<RaceAsset, PoolOdds>,
+        // Stake paid back out of each asset pool of a settled race, as winnings, scratch or refund
+        asset_settled_stake: Mapping<(u8, AccountId), Balance>,
+        // (bettor, season, asset) -> asset stakes and returns, held to the loss cap apart from native value
+        asset_season_pnl: Mapping<AssetSeasonKey, SeasonPnl>,
+        // House fees and unbacked pools taken in each asset, withdrawable by the treasurer
+        asset_fees: Mapping<AccountId, Balance>,
+    }
+
+    #[ink(event)]
+    pub struct RaceScheduled {
+        #[ink(topic)]
+        race_id: u8,
+        start_block: u32,
     }
 
     #[ink(event)]
@@ -68,6 +1133,7 @@ mod karera_platform {
         #[ink(topic)]
         race_id: u8,
         start_block: u32,
+        track_condition: TrackCondition,
     }
 
     #[ink(event)]
@@ -76,6 +1142,7 @@ mod karera_platform {
         race_id: u8,
         block_number: u32,
         positions: Vec<(u8, u32)>,
+        velocities: Vec<(u8, u32)>,
     }
 
     #[ink(event)]
@@ -83,11 +1150,14 @@ mod karera_platform {
         #[ink(topic)]
         race_id: u8,
         winner: u8,
+        winners: Vec<u8>,
         rankings: Vec<u8>,
     }
 
     #[ink(event)]
     pub struct BetPlaced {
+        #[ink(topic)]
+        bet_id: u64,
         #[ink(topic)]
         bettor: AccountId,
         race_id: u8,
@@ -95,365 +1165,9271 @@ mod karera_platform {
         amount: Balance,
     }
 
-    impl KareraPlatform {
-        #[ink(constructor)]
-        pub fn new() -> Self {
-            Self {
-                owner: Self::env().caller(),
-                races: Mapping::new(),
-                current_race_id: 0,
-                horses: Mapping::new(),
-                bet_count: Mapping::new(),
-                bets: Mapping::new(),
-                total_pool: Mapping::new(),
-                horse_pools: Mapping::new(),
-                payouts_claimed: Mapping::new(),
-            }
-        }
+    #[ink(event)]
+    pub struct PoolBoosted {
+        #[ink(topic)]
+        sponsor: AccountId,
+        race_id: u8,
+        amount: Balance,
+    }
 
-        /// Create a new race
-        #[ink(message)]
-        pub fn create_race(&mut self) -> Result<u8, Error> {
-            if self.current_race_id >= TOTAL_RACES {
-                return Err(Error::MaxRacesReached);
-            }
+    #[ink(event)]
+    pub struct WinningsClaimed {
+        #[ink(topic)]
+        bettor: AccountId,
+        race_id: u8,
+        payout: Balance,
+        recipient: AccountId,
+        // Basis points of the computed payout actually paid, below 10_000 after a haircut
+        payout_factor: u32,
+    }
 
-            let race_id = self.current_race_id;
-            
-            // Initialize horses
-            for i in 0..HORSES_PER_RACE {
-                let horse = Horse {
-                    id: i,
-                    position: 0,
-                    finished: false,
-                    finish_time: None,
-                };
-                self.horses.insert((race_id, i), &horse);
-            }
+    #[ink(event)]
+    pub struct AssetApprovalSet {
+        #[ink(topic)]
+        asset: AccountId,
+        approved: bool,
+    }
 
-            let race = Race {
+    #[ink(event)]
+    pub struct AssetBetPlaced {
+        #[ink(topic)]
+        bettor: AccountId,
+        race_id: u8,
+        horse_id: u8,
+        #[ink(topic)]
+        asset: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct AssetWinningsClaimed {
+        #[ink(topic)]
+        bettor: AccountId,
+        race_id: u8,
+        #[ink(topic)]
+        asset: AccountId,
+        payout: Balance,
+        recipient: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct AssetBetRefunded {
+        #[ink(topic)]
+        bettor: AccountId,
+        race_id: u8,
+        #[ink(topic)]
+        asset: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct AssetFeesWithdrawn {
+        #[ink(topic)]
+        asset: AccountId,
+        to: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct BetRefunded {
+        #[ink(topic)]
+        bettor: AccountId,
+        race_id: u8,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct RaceCancelled {
+        #[ink(topic)]
+        race_id: u8,
+        block_number: u32,
+    }
+
+    #[ink(event)]
+    pub struct UpgradeScheduled {
+        #[ink(topic)]
+        code_hash: Hash,
+        eta_block: u32,
+    }
+
+    #[ink(event)]
+    pub struct UpgradeCancelled {
+        #[ink(topic)]
+        code_hash: Hash,
+    }
+
+    #[ink(event)]
+    pub struct Upgraded {
+        #[ink(topic)]
+        code_hash: Hash,
+    }
+
+    #[ink(event)]
+    pub struct StorageMigrated {
+        from_version: u32,
+        to_version: u32,
+    }
+
+    #[ink(event)]
+    pub struct HorseHeld {
+        #[ink(topic)]
+        race_id: u8,
+        horse_id: u8,
+        reason: HoldReason,
+    }
+
+    #[ink(event)]
+    pub struct HorseReleased {
+        #[ink(topic)]
+        race_id: u8,
+        horse_id: u8,
+    }
+
+    #[ink(event)]
+    pub struct HorseScratched {
+        #[ink(topic)]
+        race_id: u8,
+        horse_id: u8,
+        refundable_pool: Balance,
+    }
+
+    #[ink(event)]
+    pub struct HouseFeeUpdated {
+        fee_bps: u16,
+    }
+
+    #[ink(event)]
+    pub struct FeesWithdrawn {
+        #[ink(topic)]
+        to: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct DayClosed {
+        #[ink(topic)]
+        day: u32,
+        report: DayReport,
+    }
+
+    #[ink(event)]
+    pub struct RoleGranted {
+        #[ink(topic)]
+        account: AccountId,
+        role: Role,
+    }
+
+    #[ink(event)]
+    pub struct RoleRevoked {
+        #[ink(topic)]
+        account: AccountId,
+        role: Role,
+    }
+
+    #[ink(event)]
+    pub struct SeasonStarted {
+        #[ink(topic)]
+        season: u32,
+    }
+
+    #[ink(event)]
+    pub struct LossCapUpdated {
+        #[ink(topic)]
+        account: AccountId,
+        cap: Option<Balance>,
+        effective_season: u32,
+    }
+
+    #[ink(event)]
+    pub struct OddsDivergenceFlagged {
+        #[ink(topic)]
+        race_id: u8,
+        horse_id: u8,
+        published_bps: u16,
+        pool_bps: u16,
+    }
+
+    #[ink(event)]
+    pub struct OrganizerApproved {
+        #[ink(topic)]
+        organizer: AccountId,
+        fee_share_bps: u16,
+    }
+
+    #[ink(event)]
+    pub struct OrganizerBonded {
+        #[ink(topic)]
+        organizer: AccountId,
+        bond: Balance,
+    }
+
+    #[ink(event)]
+    pub struct OrganizerRevoked {
+        #[ink(topic)]
+        organizer: AccountId,
+        slashed: Balance,
+    }
+
+    #[ink(event)]
+    pub struct OrganizerFeesWithdrawn {
+        #[ink(topic)]
+        organizer: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct RaceArchived {
+        #[ink(topic)]
+        race_id: u8,
+        bettors_cleared: u32,
+    }
+
+    #[ink(event)]
+    pub struct EntropyCommitted {
+        #[ink(topic)]
+        race_id: u8,
+        account: AccountId,
+        commitment: Hash,
+    }
+
+    #[ink(event)]
+    pub struct EntropyRevealed {
+        #[ink(topic)]
+        race_id: u8,
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct ClaimCodeCreated {
+        #[ink(topic)]
+        bettor: AccountId,
+        race_id: u8,
+        commitment: Hash,
+        expires_at: u32,
+    }
+
+    #[ink(event)]
+    pub struct ClaimCodeRedeemed {
+        #[ink(topic)]
+        commitment: Hash,
+        recipient: AccountId,
+        payout: Balance,
+    }
+
+    #[ink(event)]
+    pub struct JackpotRolledOver {
+        #[ink(topic)]
+        from_race: u8,
+        amount: Balance,
+        jackpot: Balance,
+    }
+
+    #[ink(event)]
+    pub struct JackpotSeeded {
+        #[ink(topic)]
+        race_id: u8,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ReferralRecorded {
+        #[ink(topic)]
+        referee: AccountId,
+        #[ink(topic)]
+        referrer: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct ReferralEarningsClaimed {
+        #[ink(topic)]
+        referrer: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct CardCreated {
+        #[ink(topic)]
+        card_id: u32,
+        races: Vec<u8>,
+    }
+
+    #[ink(event)]
+    pub struct CardBetPlaced {
+        #[ink(topic)]
+        bettor: AccountId,
+        card_id: u32,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct StreakAcknowledged {
+        #[ink(topic)]
+        account: AccountId,
+        losses: u32,
+        cooling_off_until: u32,
+    }
+
+    #[ink(event)]
+    pub struct RaceGraded {
+        #[ink(topic)]
+        race_id: u8,
+        grade: RaceGrade,
+    }
+
+    #[ink(event)]
+    pub struct PromotionScheduled {
+        #[ink(topic)]
+        promotion_id: u32,
+        start_block: u32,
+        end_block: u32,
+        fee_bps: u16,
+        grades: Vec<RaceGrade>,
+    }
+
+    #[ink(event)]
+    pub struct PromotionCancelled {
+        #[ink(topic)]
+        promotion_id: u32,
+    }
+
+    #[ink(event)]
+    pub struct PromotionApplied {
+        #[ink(topic)]
+        race_id: u8,
+        promotion_id: u32,
+        fee_bps: u16,
+    }
+
+    #[ink(event)]
+    pub struct Deposited {
+        #[ink(topic)]
+        account: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct Withdrawn {
+        #[ink(topic)]
+        account: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct SignedBetRelayed {
+        #[ink(topic)]
+        bettor: AccountId,
+        relayer: AccountId,
+        nonce: u64,
+    }
+
+    #[ink(event)]
+    pub struct PostPositionsDrawn {
+        #[ink(topic)]
+        race_id: u8,
+        seed: [u8; 32],
+        positions: Vec<u8>,
+    }
+
+    #[ink(event)]
+    pub struct SlipPlaced {
+        #[ink(topic)]
+        bettor: AccountId,
+        legs: u32,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ParlayPlaced {
+        #[ink(topic)]
+        parlay_id: u32,
+        #[ink(topic)]
+        bettor: AccountId,
+        legs: Vec<(u8, u8)>,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ParlaySettled {
+        #[ink(topic)]
+        parlay_id: u32,
+        #[ink(topic)]
+        bettor: AccountId,
+        payout: Balance,
+    }
+
+    #[ink(event)]
+    pub struct LiveBetPlaced {
+        #[ink(topic)]
+        bettor: AccountId,
+        #[ink(topic)]
+        race_id: u8,
+        horse_id: u8,
+        amount: Balance,
+        weight: Balance,
+    }
+
+    #[ink(event)]
+    pub struct LiveBetsSettled {
+        #[ink(topic)]
+        bettor: AccountId,
+        #[ink(topic)]
+        race_id: u8,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct PurseFunded {
+        #[ink(topic)]
+        race_id: u8,
+        amount: Balance,
+        purse: Balance,
+    }
+
+    #[ink(event)]
+    pub struct PurseEscrowed {
+        #[ink(topic)]
+        race_id: u8,
+        #[ink(topic)]
+        owner: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct PurseClaimed {
+        #[ink(topic)]
+        owner: AccountId,
+        race_id: u8,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct AccountFrozen {
+        #[ink(topic)]
+        account: AccountId,
+        reason: FreezeReason,
+        expires_at: u32,
+    }
+
+    #[ink(event)]
+    pub struct AccountUnfrozen {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct HorseMetadataUpdated {
+        #[ink(topic)]
+        race_id: u8,
+        horse_id: u8,
+        name: String,
+        metadata_uri: String,
+    }
+
+    #[ink(event)]
+    pub struct SteamMove {
+        #[ink(topic)]
+        race_id: u8,
+        #[ink(topic)]
+        horse_id: u8,
+        before_bps: u16,
+        after_bps: u16,
+        since_block: u32,
+    }
+
+    #[ink(event)]
+    pub struct PickSubmitted {
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        race_id: u8,
+        horse_id: u8,
+    }
+
+    #[ink(event)]
+    pub struct PickScored {
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        race_id: u8,
+        points: u32,
+        season_points: u32,
+    }
+
+    #[ink(event)]
+    pub struct MinimumPoolNotMet {
+        #[ink(topic)]
+        race_id: u8,
+        staked: Balance,
+        min_pool: Balance,
+    }
+
+    #[ink(event)]
+    pub struct MirrorPoolsReported {
+        #[ink(topic)]
+        race_id: u8,
+        deployment: u32,
+        total: Balance,
+    }
+
+    #[ink(event)]
+    pub struct MirrorSettled {
+        #[ink(topic)]
+        race_id: u8,
+        deployment: u32,
+        amount: i128,
+    }
+
+    #[ink(event)]
+    pub struct MirrorReconciled {
+        #[ink(topic)]
+        race_id: u8,
+        deployment: u32,
+        amount: i128,
+    }
+
+    #[ink(event)]
+    pub struct EmergencyWithdrawInitiated {
+        executable_at: u32,
+    }
+
+    #[ink(event)]
+    pub struct EmergencyWithdrawCancelled {}
+
+    #[ink(event)]
+    pub struct EmergencyWithdrawFinalized {
+        #[ink(topic)]
+        to: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ConsolationSet {
+        #[ink(topic)]
+        race_id: u8,
+        share_bps: u16,
+    }
+
+    #[ink(event)]
+    pub struct LoyaltyPointsEarned {
+        #[ink(topic)]
+        account: AccountId,
+        points: u32,
+        streak: u32,
+    }
+
+    #[ink(event)]
+    pub struct LoyaltyPointsRedeemed {
+        #[ink(topic)]
+        account: AccountId,
+        points: u32,
+        fee_free_stake: Balance,
+    }
+
+    #[ink(event)]
+    pub struct LoyaltyDiscountApplied {
+        #[ink(topic)]
+        account: AccountId,
+        race_id: u8,
+        discount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct BetModeSet {
+        #[ink(topic)]
+        race_id: u8,
+        mode: BetMode,
+    }
+
+    #[ink(event)]
+    pub struct FixedOddsPosted {
+        #[ink(topic)]
+        race_id: u8,
+        odds: Vec<u32>,
+    }
+
+    #[ink(event)]
+    pub struct OddsReserveFunded {
+        #[ink(topic)]
+        race_id: u8,
+        funder: AccountId,
+        amount: Balance,
+        reserve: Balance,
+    }
+
+    #[ink(event)]
+    pub struct BookSettled {
+        #[ink(topic)]
+        race_id: u8,
+        funder: Option<AccountId>,
+        owed: Balance,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct TournamentCreated {
+        #[ink(topic)]
+        tournament_id: u32,
+        races: Vec<u8>,
+        points: Vec<u32>,
+    }
+
+    #[ink(event)]
+    pub struct ChampionshipPointsAwarded {
+        #[ink(topic)]
+        tournament_id: u32,
+        race_id: u8,
+        standings: Vec<u32>,
+    }
+
+    #[ink(event)]
+    pub struct TournamentBetPlaced {
+        #[ink(topic)]
+        tournament_id: u32,
+        bettor: AccountId,
+        horse_id: u8,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct TournamentSettled {
+        #[ink(topic)]
+        tournament_id: u32,
+        champions: Vec<u8>,
+        net_pool: Balance,
+        winning_pool: Balance,
+    }
+
+    #[ink(event)]
+    pub struct TournamentWinningsClaimed {
+        #[ink(topic)]
+        tournament_id: u32,
+        bettor: AccountId,
+        payout: Balance,
+    }
+
+    #[ink(event)]
+    pub struct RaceDurationUpdated {
+        duration_blocks: u32,
+    }
+
+    #[ink(event)]
+    pub struct GovernanceEnabled {
+        #[ink(topic)]
+        token: AccountId,
+        voting_period_blocks: u32,
+        quorum: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ProposalCreated {
+        #[ink(topic)]
+        proposal_id: u32,
+        proposer: AccountId,
+        change: ParameterChange,
+        end_block: u32,
+    }
+
+    #[ink(event)]
+    pub struct VoteCast {
+        #[ink(topic)]
+        proposal_id: u32,
+        voter: AccountId,
+        support: bool,
+        weight: Balance,
+    }
+
+    #[ink(event)]
+    pub struct GovernanceTokensWithdrawn {
+        #[ink(topic)]
+        proposal_id: u32,
+        #[ink(topic)]
+        account: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ProposalExecuted {
+        #[ink(topic)]
+        proposal_id: u32,
+        passed: bool,
+    }
+
+    #[ink(event)]
+    pub struct PoolCapsSet {
+        #[ink(topic)]
+        race_id: u8,
+        caps: PoolCaps,
+    }
+
+    #[ink(event)]
+    pub struct PayoutCapSet {
+        #[ink(topic)]
+        race_id: u8,
+        multiple_bps: Option<u32>,
+    }
+
+    #[ink(event)]
+    pub struct ExcessRefunded {
+        #[ink(topic)]
+        bettor: AccountId,
+        race_id: u8,
+        horse_id: u8,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct TrifectaBoxPlaced {
+        #[ink(topic)]
+        ticket_id: u32,
+        bettor: AccountId,
+        race_id: u8,
+        horses: Vec<u8>,
+        permutations: u32,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct TrifectaClaimed {
+        #[ink(topic)]
+        ticket_id: u32,
+        bettor: AccountId,
+        payout: Balance,
+    }
+
+    #[ink(event)]
+    pub struct HorseRegistered {
+        #[ink(topic)]
+        horse_id: u32,
+        name: String,
+    }
+
+    #[ink(event)]
+    pub struct Staked {
+        #[ink(topic)]
+        staker: AccountId,
+        amount: Balance,
+        active_from_epoch: u32,
+    }
+
+    #[ink(event)]
+    pub struct Unstaked {
+        #[ink(topic)]
+        staker: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct StakingRewardsClaimed {
+        #[ink(topic)]
+        staker: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct StakingEpochClosed {
+        #[ink(topic)]
+        epoch: u32,
+        rewards: Balance,
+        total_staked: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ExpiredWinningsSwept {
+        #[ink(topic)]
+        race_id: u8,
+        amount: Balance,
+        destination: SweepDestination,
+    }
+
+    #[ink(event)]
+    pub struct RoundingDustSwept {
+        #[ink(topic)]
+        race_id: u8,
+        amount: Balance,
+        destination: SweepDestination,
+    }
+
+    #[ink(event)]
+    pub struct SignersUpdated {
+        signers: Vec<AccountId>,
+        threshold: u32,
+    }
+
+    #[ink(event)]
+    pub struct ActionSubmitted {
+        #[ink(topic)]
+        action_id: u32,
+        proposer: AccountId,
+        action: OwnerAction,
+    }
+
+    #[ink(event)]
+    pub struct ActionConfirmed {
+        #[ink(topic)]
+        action_id: u32,
+        signer: AccountId,
+        confirmations: u32,
+    }
+
+    #[ink(event)]
+    pub struct ActionExecuted {
+        #[ink(topic)]
+        action_id: u32,
+    }
+
+    #[ink(event)]
+    pub struct RaceOracleSet {
+        #[ink(topic)]
+        race_id: u8,
+        oracle: Option<AccountId>,
+    }
+
+    #[ink(event)]
+    pub struct ResultSubmitted {
+        #[ink(topic)]
+        race_id: u8,
+        oracle: AccountId,
+        rankings: Vec<u8>,
+    }
+
+    #[ink(event)]
+    pub struct RaceForceSettled {
+        #[ink(topic)]
+        race_id: u8,
+        cancelled: bool,
+    }
+
+    #[ink(event)]
+    pub struct AccountRestricted {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct AccountUnrestricted {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    /// PSP34 transfer of a bet ticket; `from` is None on mint and `to` is None on burn
+    #[ink(event)]
+    pub struct Transfer {
+        #[ink(topic)]
+        from: Option<AccountId>,
+        #[ink(topic)]
+        to: Option<AccountId>,
+        #[ink(topic)]
+        id: Id,
+    }
+
+    #[ink(event)]
+    pub struct Approval {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        operator: AccountId,
+        #[ink(topic)]
+        id: Option<Id>,
+        approved: bool,
+    }
+
+    impl KareraPlatform {
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            let mut roles = Mapping::new();
+            roles.insert(Self::env().caller(), &Role::Owner);
+
+            Self {
+                owner: Self::env().caller(),
+                races: Mapping::new(),
+                current_race_id: 0,
+                horses: Mapping::new(),
+                race_horses: Mapping::new(),
+                bet_count: Mapping::new(),
+                bets: Mapping::new(),
+                total_pool: Mapping::new(),
+                horse_pools: Mapping::new(),
+                payouts_claimed: Mapping::new(),
+                pool_boosts: Mapping::new(),
+                storage_version: STORAGE_VERSION,
+                pending_upgrade: None,
+                horse_holds: Mapping::new(),
+                scratch_refunds_claimed: Mapping::new(),
+                scratch_unrefunded: Mapping::new(),
+                cancel_unrefunded: Mapping::new(),
+                house_fee_bps: 0,
+                accrued_fees: 0,
+                race_fees: Mapping::new(),
+                unclaimed_winnings: 0,
+                current_day: 0,
+                day_totals: DayReport::default(),
+                day_reports: Mapping::new(),
+                roles,
+                current_season: 0,
+                loss_caps: Mapping::new(),
+                pending_loss_caps: Mapping::new(),
+                season_pnl: Mapping::new(),
+                published_probabilities: Mapping::new(),
+                divergence_threshold_bps: DEFAULT_DIVERGENCE_THRESHOLD_BPS,
+                organizers: Mapping::new(),
+                organizer_guardrails: OrganizerGuardrails::default(),
+                organizer_bonds: 0,
+                organizer_fees_owed: 0,
+                race_bettors: Mapping::new(),
+                race_bettor_count: Mapping::new(),
+                settled_stake: Mapping::new(),
+                archive_cursor: Mapping::new(),
+                entropy_commitments: Mapping::new(),
+                race_entropy: Mapping::new(),
+                bet_entropy: Mapping::new(),
+                claim_codes: Mapping::new(),
+                bettor_claim_codes: Mapping::new(),
+                race_steps: Mapping::new(),
+                race_step_count: Mapping::new(),
+                jackpot: 0,
+                race_jackpot_seed: Mapping::new(),
+                new_account_limits: NewAccountLimits::default(),
+                account_history: Mapping::new(),
+                referrers: Mapping::new(),
+                referral_bps: 0,
+                referred_handle: Mapping::new(),
+                race_referred_handle: Mapping::new(),
+                race_referral_rate: Mapping::new(),
+                race_referral_commissions: Mapping::new(),
+                race_referral_unclaimed: Mapping::new(),
+                referral_owed: 0,
+                referral_claimed: Mapping::new(),
+                race_lead_blocks: DEFAULT_RACE_LEAD_BLOCKS,
+                betting_cutoff_blocks: DEFAULT_BETTING_CUTOFF_BLOCKS,
+                race_tracking: Mapping::new(),
+                worst_ranks: Mapping::new(),
+                race_highlights: Mapping::new(),
+                cards: Mapping::new(),
+                next_card_id: 0,
+                streaks: Mapping::new(),
+                streak_counted: Mapping::new(),
+                promotions: Vec::new(),
+                next_promotion_id: 0,
+                deposits: Mapping::new(),
+                total_deposits: 0,
+                bet_nonces: Mapping::new(),
+                draw_seeds: Mapping::new(),
+                post_positions: Mapping::new(),
+                parlays: Mapping::new(),
+                next_parlay_id: 0,
+                open_parlay_stakes: 0,
+                parlay_reserves: 0,
+                parlay_max_multiple: DEFAULT_PARLAY_MAX_MULTIPLE,
+                race_odds: Mapping::new(),
+                live_bets: Mapping::new(),
+                live_bet_count: Mapping::new(),
+                live_pools: Mapping::new(),
+                live_total: Mapping::new(),
+                live_fees: Mapping::new(),
+                live_claimed: Mapping::new(),
+                horse_owners: Mapping::new(),
+                race_purses: Mapping::new(),
+                purse_escrows: Mapping::new(),
+                purse_vesting: PurseVesting {
+                    cliff_blocks: 0,
+                    duration_blocks: DEFAULT_PURSE_VESTING_BLOCKS,
+                },
+                frozen_accounts: Mapping::new(),
+                horse_metadata: Mapping::new(),
+                steam_references: Mapping::new(),
+                steam_threshold_bps: DEFAULT_STEAM_THRESHOLD_BPS,
+                steam_window_blocks: DEFAULT_STEAM_WINDOW_BLOCKS,
+                picks: Mapping::new(),
+                season_points: Mapping::new(),
+                leaderboards: Mapping::new(),
+                min_pool: 0,
+                bridge: None,
+                mirror_pools: Mapping::new(),
+                mirror_deployments: Mapping::new(),
+                mirror_settlements: Mapping::new(),
+                emergency_withdraw_at: None,
+                race_consolation: Mapping::new(),
+                runner_up_odds: Mapping::new(),
+                loyalty: Mapping::new(),
+                loyalty_point_value: 0,
+                fixed_odds: Mapping::new(),
+                odds_reserves: Mapping::new(),
+                fixed_liabilities: Mapping::new(),
+                bet_odds: Mapping::new(),
+                tournaments: Mapping::new(),
+                next_tournament_id: 0,
+                race_tournament: Mapping::new(),
+                championship_points: Mapping::new(),
+                tournament_pools: Mapping::new(),
+                tournament_bets: Mapping::new(),
+                tournament_claimed: Mapping::new(),
+                open_tournament_pools: 0,
+                race_duration_blocks: RACE_DURATION_BLOCKS,
+                governance: None,
+                proposals: Mapping::new(),
+                next_proposal_id: 0,
+                proposal_votes: Mapping::new(),
+                governance_locks: Mapping::new(),
+                pool_caps: Mapping::new(),
+                payout_caps: Mapping::new(),
+                payout_factors: Mapping::new(),
+                trifecta_tickets: Mapping::new(),
+                next_trifecta_ticket_id: 0,
+                trifecta_ticket_ids: Mapping::new(),
+                trifecta_pools: Mapping::new(),
+                trifecta_totals: Mapping::new(),
+                trifecta_fees: Mapping::new(),
+                bets_by_id: Mapping::new(),
+                next_bet_id: 0,
+                horse_careers: Mapping::new(),
+                horse_career_count: 0,
+                race_entrants: Mapping::new(),
+                staking: None,
+                stakes: Mapping::new(),
+                total_staked: 0,
+                pending_stake: Mapping::new(),
+                staking_epoch: 0,
+                staking_epoch_start: 0,
+                epoch_rewards: 0,
+                reward_per_token: 0,
+                epoch_reward_per_token: Mapping::new(),
+                staking_rewards_owed: 0,
+                bet_rate_limits: BetRateLimits {
+                    max_bets_per_race: DEFAULT_MAX_BETS_PER_RACE,
+                    max_bets_per_window: 0,
+                    window_blocks: 0,
+                },
+                bet_windows: Mapping::new(),
+                claim_window_blocks: 0,
+                sweep_destination: SweepDestination::Treasury,
+                race_unclaimed: Mapping::new(),
+                race_swept: Mapping::new(),
+                pool_winnings_unpaid: Mapping::new(),
+                rounding_dust: Mapping::new(),
+                replay_snapshots: Mapping::new(),
+                signers: Vec::new(),
+                approval_threshold: 0,
+                pending_actions: Mapping::new(),
+                next_action_id: 0,
+                migration_cursor: Lazy::new(),
+                race_stats: Mapping::new(),
+                restricted_accounts: Mapping::new(),
+                race_handicaps: Mapping::new(),
+                lazy_progression: false,
+                ticket_holders: Mapping::new(),
+                burned_tickets: Mapping::new(),
+                ticket_balances: Mapping::new(),
+                ticket_supply: 0,
+                acquired_tickets: Mapping::new(),
+                ticket_approvals: Mapping::new(),
+                ticket_operators: Mapping::new(),
+                auto_start_races: Mapping::new(),
+                approved_assets: Mapping::new(),
+                race_assets: Mapping::new(),
+                asset_pools: Mapping::new(),
+                asset_totals: Mapping::new(),
+                asset_bets: Mapping::new(),
+                asset_odds: Mapping::new(),
+                asset_settled_stake: Mapping::new(),
+                asset_season_pnl: Mapping::new(),
+                asset_fees: Mapping::new(),
+            }
+        }
+
+        /// Create a new race with `field_size` horses.
+        /// Races created by an approved organizer are run by that organizer.
+        #[ink(message)]
+        pub fn create_race(&mut self, field_size: u8) -> Result<u8, Error> {
+            let scheduled_start = self.env().block_number() + self.race_lead_blocks;
+            self.open_race(field_size, scheduled_start)
+        }
+
+        /// Create a new race with `field_size` horses scheduled to start at `start_block`.
+        /// From that block on `start_race` is open to anyone, so the race does not wait
+        /// on its operator.
+        #[ink(message)]
+        pub fn create_race_at(&mut self, start_block: u32, field_size: u8) -> Result<u8, Error> {
+            // Betting must be open for at least one block before the cutoff
+            if start_block <= self.env().block_number() + self.betting_cutoff_blocks {
+                return Err(Error::InvalidSchedule);
+            }
+
+            let race_id = self.open_race(field_size, start_block)?;
+            self.auto_start_races.insert(race_id, &());
+
+            self.env().emit_event(RaceScheduled { race_id, start_block });
+
+            Ok(race_id)
+        }
+
+        fn open_race(&mut self, field_size: u8, scheduled_start: u32) -> Result<u8, Error> {
+            let caller = self.env().caller();
+
+            if !(MIN_FIELD_SIZE..=MAX_FIELD_SIZE).contains(&field_size) {
+                return Err(Error::InvalidFieldSize {
+                    min: MIN_FIELD_SIZE,
+                    max: MAX_FIELD_SIZE,
+                });
+            }
+
+            let organizer = if self.ensure_role(Role::RaceManager).is_ok() {
+                None
+            } else {
+                let mut account = self.organizers.get(caller).ok_or(Error::Unauthorized)?;
+                if !account.active {
+                    return Err(Error::Unauthorized);
+                }
+                if account.bond < self.organizer_guardrails.min_bond {
+                    return Err(Error::InsufficientBond);
+                }
+                account.races_created += 1;
+                account.open_races += 1;
+                self.organizers.insert(caller, &account);
+                Some(caller)
+            };
+
+            if self.current_race_id >= TOTAL_RACES {
+                return Err(Error::MaxRacesReached);
+            }
+
+            let race_id = self.current_race_id;
+            
+            let race = Race {
                 id: race_id,
                 status: RaceStatus::Pending,
                 start_block: 0,
                 current_block: 0,
                 winner: None,
+                winners: Vec::new(),
                 rankings: Vec::new(),
+                season: self.current_season,
+                organizer,
+                settled_at: 0,
+                sim_version: 0,
+                scheduled_start,
+                grade: RaceGrade::Ungraded,
+                field_size,
+                track_condition: None,
+                bet_mode: BetMode::Parimutuel,
+                duration_blocks: self.race_duration_blocks,
+                oracle: None,
+            };
+
+            self.races.insert(race_id, &race);
+            self.current_race_id += 1;
+
+            // Draw post positions before the race is open to bets
+            let seed_input = scale::Encode::encode(&(
+                race_id,
+                self.env().block_number(),
+                self.env().block_timestamp(),
+                caller,
+            ));
+            let seed = self.env().hash_bytes::<Blake2x256>(&seed_input);
+            let positions = self.draw_post_positions(&seed, field_size);
+            self.draw_seeds.insert(race_id, &seed);
+            self.post_positions.insert(race_id, &positions);
+
+            // Initialize horses, with track affinities taken from the draw seed. Once the
+            // registry can fill the field, each slot is run by a registered horse.
+            let entrants = self.draw_entrants(&seed, field_size);
+            let mut horses = Vec::new();
+            for i in 0..field_size {
+                let mut horse = Self::fresh_horse(i);
+                horse.affinity = Self::affinity(&seed, i);
+                if let Some(career) = entrants.get(i as usize).and_then(|id| self.horse_careers.get(id)) {
+                    horse.experience = (career.experience / XP_PER_LEVEL).min(MAX_EXPERIENCE_LEVEL) as u8;
+                    horse.handicap = Self::handicap(&career);
+                    self.race_entrants.insert((race_id, i), &(career.id, horse.experience));
+                }
+                horses.push(horse);
+            }
+            self.race_horses.insert(race_id, &horses);
+            if !entrants.is_empty() {
+                let handicaps: Vec<u8> = horses.iter().map(|horse| horse.handicap).collect();
+                self.race_handicaps.insert(race_id, &handicaps);
+            }
+
+            self.env().emit_event(PostPositionsDrawn {
+                race_id,
+                seed,
+                positions,
+            });
+
+            // Carry the jackpot into platform races; organizer races are left out
+            if race.organizer.is_none() && self.jackpot > 0 {
+                let amount = core::mem::take(&mut self.jackpot);
+                self.total_pool.insert(race_id, &amount);
+                self.race_jackpot_seed.insert(race_id, &amount);
+
+                self.env().emit_event(JackpotSeeded { race_id, amount });
+            }
+
+            Ok(race_id)
+        }
+
+        /// Start a race. Races created with `create_race_at` may be started by anyone
+        /// once their scheduled start block is reached.
+        #[ink(message)]
+        pub fn start_race(&mut self, race_id: u8) -> Result<(), Error> {
+            let mut race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            let due = self.auto_start_races.contains(race_id) && self.env().block_number() >= race.scheduled_start;
+            if !due {
+                self.ensure_race_operator(&race)?;
+            }
+            
+            if race.status != RaceStatus::Pending {
+                return Err(Error::RaceAlreadyStarted);
+            }
+
+            // Holds still standing at the start scratch the horse and void its market
+            for i in 0..race.field_size {
+                if self.horse_holds.contains((race_id, i)) {
+                    self.scratch(race_id, i)?;
+                }
+            }
+
+            let horses = self.load_horses(race_id);
+            let runners = (0..race.field_size).filter(|i| !Self::scratched_in(&horses, *i)).count();
+            if runners < 2 {
+                return Err(Error::NotEnoughRunners);
+            }
+
+            // Too little liquidity: cancel instead of running so bettors can claim refunds
+            let staked: Balance = (0..race.field_size)
+                .filter(|i| !Self::scratched_in(&horses, *i))
+                .map(|i| self.horse_pools.get((race_id, i)).unwrap_or(0))
+                .sum();
+            if staked < self.min_pool {
+                self.env().emit_event(MinimumPoolNotMet {
+                    race_id,
+                    staked,
+                    min_pool: self.min_pool,
+                });
+                self.cancel(race);
+                return Ok(());
+            }
+
+            // Report markets whose closing pool disagrees with the published odds
+            for divergence in self.get_odds_divergence(race_id) {
+                if divergence.flagged {
+                    self.env().emit_event(OddsDivergenceFlagged {
+                        race_id,
+                        horse_id: divergence.horse_id,
+                        published_bps: divergence.published_bps,
+                        pool_bps: divergence.pool_bps,
+                    });
+                }
+            }
+
+            let current_block = self.env().block_number();
+            let track_condition = match race.track_condition {
+                Some(condition) => condition,
+                None => {
+                    let timestamp = self.env().block_timestamp();
+                    let draw = Self::pseudo_random(race_id, current_block, timestamp, self.entropy_seed(race_id));
+                    match draw % 4 {
+                        0 => TrackCondition::Fast,
+                        1 => TrackCondition::Good,
+                        2 => TrackCondition::Muddy,
+                        _ => TrackCondition::Sloppy,
+                    }
+                }
+            };
+            race.track_condition = Some(track_condition);
+            race.status = RaceStatus::Active;
+            race.sim_version = SIM_ALGORITHM_VERSION;
+            race.duration_blocks = self.race_duration_blocks;
+            race.start_block = current_block;
+            race.current_block = current_block;
+
+            self.races.insert(race_id, &race);
+
+            self.env().emit_event(RaceStarted {
+                race_id,
+                start_block: current_block,
+                track_condition,
+            });
+
+            Ok(())
+        }
+
+        /// Update race progress. From simulation v2 one step is simulated per block
+        /// elapsed since the last update, up to `MAX_CATCHUP_BLOCKS` per call.
+        #[ink(message)]
+        pub fn update_race(&mut self, race_id: u8) -> Result<(), Error> {
+            let mut race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            
+            if race.status != RaceStatus::Active {
+                return Err(Error::RaceNotActive);
+            }
+
+            if race.oracle.is_some() {
+                return Err(Error::OracleSettled);
+            }
+
+            let current_block = self.env().block_number();
+            let blocks_elapsed = current_block - race.start_block;
+            let catch_up = race.sim_version >= 2;
+
+            // Last block the simulation may reach before the race is called
+            let last_block = if catch_up {
+                current_block.min(race.start_block + race.duration_blocks - 1)
+            } else {
+                current_block
+            };
+
+            // Check if race should end, once every block up to the limit has been simulated
+            if blocks_elapsed >= race.duration_blocks && (!catch_up || race.current_block >= last_block) {
+                return self.finish_race(race_id);
+            }
+
+            let blocks: Vec<u32> = if catch_up {
+                (race.current_block + 1..=last_block).take(MAX_CATCHUP_BLOCKS as usize).collect()
+            } else {
+                Vec::from([current_block])
+            };
+            if blocks.is_empty() {
+                // Already simulated up to this block
+                return Ok(());
+            }
+
+            let entropy = self.entropy_seed(race_id);
+            let timestamp = self.env().block_timestamp();
+
+            let mut outcome = StepOutcome::default();
+            for block in blocks {
+                outcome = self.simulate_step(&mut race, block, timestamp, entropy)?;
+                self.replay_snapshots.insert((race_id, block - race.start_block), &outcome.positions);
+                race.current_block = block;
+                if outcome.all_finished {
+                    break;
+                }
+            }
+
+            self.races.insert(race_id, &race);
+
+            self.env().emit_event(RaceUpdate {
+                race_id,
+                block_number: race.current_block,
+                positions: outcome.positions,
+                velocities: outcome.velocities,
+            });
+
+            // If all horses finished, end race
+            if outcome.all_finished {
+                self.finish_race(race_id)?;
+            }
+
+            Ok(())
+        }
+
+        /// Advance every running horse by one simulation step at `block`, returning the
+        /// moved horses' positions and velocities and whether the whole field has finished
+        fn simulate_step(
+            &mut self,
+            race: &mut Race,
+            block: u32,
+            timestamp: u64,
+            entropy: u32,
+        ) -> Result<StepOutcome, Error> {
+            let race_id = race.id;
+            let blocks_elapsed = block - race.start_block;
+
+            // Record the step inputs so the race can be replayed by `verify_race`
+            let step = self.race_step_count.get(race_id).unwrap_or(0);
+            self.race_steps.insert((race_id, step), &(block, timestamp));
+            self.race_step_count.insert(race_id, &(step + 1));
+
+            // Update horse positions
+            let mut positions = Vec::new();
+            let mut velocities = Vec::new();
+            let mut field = Vec::new();
+            let mut all_finished = true;
+
+            let mut horses = self.load_horses(race_id);
+            for horse in horses.iter_mut() {
+                if !horse.finished && !horse.scratched {
+                    let acceleration = Self::acceleration(
+                        race.sim_version,
+                        horse,
+                        block,
+                        timestamp,
+                        entropy,
+                        race.track_condition.unwrap_or_default(),
+                    )
+                    .ok_or(Error::UnsupportedSimVersion)?;
+                    let previous_position = horse.position;
+                    horse.position += acceleration;
+
+                    // Check if horse finished
+                    if horse.position >= FINISH_LINE {
+                        horse.position = FINISH_LINE;
+                        horse.finished = true;
+                        horse.finish_time = Some(blocks_elapsed);
+                        race.rankings.push(horse.id);
+                    } else {
+                        all_finished = false;
+                    }
+
+                    horse.last_velocity = horse.position - previous_position;
+                    horse.last_update_block = block;
+
+                    positions.push((horse.id, horse.position));
+                    velocities.push((horse.id, horse.last_velocity));
+                }
+
+                if !horse.scratched {
+                    field.push(horse.clone());
+                }
+            }
+            self.race_horses.insert(race_id, &horses);
+
+            self.track_highlights(race, &mut field, block);
+
+            Ok(StepOutcome {
+                positions,
+                velocities,
+                all_finished,
+            })
+        }
+
+        /// Update lead changes, worst running positions, winning margin and fastest segment
+        fn track_highlights(&mut self, race: &Race, field: &mut [Horse], block: u32) {
+            if field.is_empty() {
+                return;
+            }
+
+            let race_id = race.id;
+            let mut tracking = self.race_tracking.get(race_id).unwrap_or_default();
+
+            for horse in field.iter() {
+                if horse.last_update_block == block && horse.last_velocity > tracking.highlights.fastest_distance {
+                    tracking.highlights.fastest_horse = Some(horse.id);
+                    tracking.highlights.fastest_distance = horse.last_velocity;
+                    tracking.highlights.fastest_block = block;
+                }
+            }
+
+            Self::rank_horses(field, race, block);
+
+            let leader = field[0].id;
+            if tracking.leader.is_some_and(|previous| previous != leader) {
+                tracking.highlights.lead_changes += 1;
+            }
+            tracking.leader = Some(leader);
+
+            if field[0].finished && !tracking.margin_recorded {
+                tracking.highlights.winning_margin = match field.get(1) {
+                    Some(runner_up) if !runner_up.finished => FINISH_LINE - runner_up.position,
+                    _ => 0,
+                };
+                tracking.margin_recorded = true;
+            }
+
+            for (rank, horse) in field.iter().enumerate() {
+                let worst = self.worst_ranks.get((race_id, horse.id)).unwrap_or(0);
+                if rank as u8 > worst {
+                    self.worst_ranks.insert((race_id, horse.id), &(rank as u8));
+                }
+            }
+
+            self.race_tracking.insert(race_id, &tracking);
+        }
+
+        /// Finish a race
+        fn finish_race(&mut self, race_id: u8) -> Result<(), Error> {
+            let mut race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            
+            if race.status != RaceStatus::Active {
+                return Err(Error::RaceNotActive);
+            }
+
+            // Collect and sort horses
+            let mut horses_vec: Vec<Horse> = self
+                .load_horses(race_id)
+                .into_iter()
+                .filter(|horse| !horse.scratched)
+                .collect();
+
+            Self::rank_horses(&mut horses_vec, &race, race.current_block);
+
+            race.rankings.clear();
+            for horse in horses_vec.iter() {
+                race.rankings.push(horse.id);
+            }
+
+            race.winners = Self::dead_heat_winners(&horses_vec);
+
+            // Summarize the tracked statistics, finding the largest comeback
+            let mut highlights = self.race_tracking.take(race_id).unwrap_or_default().highlights;
+            for (rank, horse) in horses_vec.iter().enumerate() {
+                let worst = self.worst_ranks.take((race_id, horse.id)).unwrap_or(0);
+                let gained = worst.saturating_sub(rank as u8);
+                if gained > highlights.comeback_places {
+                    highlights.comeback_horse = Some(horse.id);
+                    highlights.comeback_places = gained;
+                }
+            }
+            self.race_highlights.insert(race_id, &highlights);
+
+            self.settle_race(race)
+        }
+
+        /// Mark a ranked race finished and settle every market on it
+        fn settle_race(&mut self, mut race: Race) -> Result<(), Error> {
+            let race_id = race.id;
+            race.winner = Some(race.rankings[0]);
+            race.status = RaceStatus::Finished;
+            race.settled_at = self.env().block_number();
+
+            self.races.insert(race_id, &race);
+
+            let fee = match race.bet_mode {
+                BetMode::Parimutuel => self.settle_pool(&race)?,
+                BetMode::FixedOdds => {
+                    self.settle_book(&race)?;
+                    0
+                }
+            };
+            self.close_organizer_race(&race);
+            self.escrow_purse(&race);
+            self.award_championship_points(&race);
+            self.record_careers(&race);
+            self.advance_staking_epoch();
+
+            self.day_totals.races_settled += 1;
+            self.day_totals.fees += fee;
+
+            self.env().emit_event(RaceFinished {
+                race_id,
+                winner: race.rankings[0],
+                winners: race.winners.clone(),
+                rankings: race.rankings.clone(),
+            });
+
+            Ok(())
+        }
+
+        /// Place a bet on a horse. Only the part of the stake that fits under the race's
+        /// pool caps is accepted; the excess is refunded.
+        #[ink(message, payable)]
+        pub fn place_bet(&mut self, race_id: u8, horse_id: u8) -> Result<(), Error> {
+            self.progress_active_race()?;
+
+            let bettor = self.env().caller();
+            let amount = self.env().transferred_value();
+            self.accept_bet(bettor, race_id, horse_id, amount)
+        }
+
+        /// Record the part of a received stake that fits under the race's pool caps and
+        /// refund the excess to the bettor
+        fn accept_bet(&mut self, bettor: AccountId, race_id: u8, horse_id: u8, amount: Balance) -> Result<(), Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            let accepted = amount.min(self.pool_cap_room(&race, horse_id, 0, 0));
+            if accepted == 0 && amount > 0 {
+                return Err(Error::PoolCapReached);
+            }
+
+            self.record_bet(bettor, race_id, horse_id, accepted)?;
+
+            let excess = amount - accepted;
+            if excess > 0 {
+                if self.env().transfer(bettor, excess).is_err() {
+                    return Err(Error::TransferFailed);
+                }
+                self.ensure_solvent()?;
+
+                self.env().emit_event(ExcessRefunded {
+                    bettor,
+                    race_id,
+                    horse_id,
+                    amount: excess,
+                });
+            }
+
+            Ok(())
+        }
+
+        /// Place a bet, recording `referrer` as the caller's referrer if they have none yet.
+        /// Stakes over the pool caps are refunded as by `place_bet`.
+        #[ink(message, payable)]
+        pub fn place_bet_with_referrer(&mut self, race_id: u8, horse_id: u8, referrer: AccountId) -> Result<(), Error> {
+            let bettor = self.env().caller();
+            let amount = self.env().transferred_value();
+
+            if referrer == bettor {
+                return Err(Error::InvalidReferrer);
+            }
+
+            self.progress_active_race()?;
+
+            if !self.referrers.contains(bettor) {
+                self.referrers.insert(bettor, &referrer);
+
+                self.env().emit_event(ReferralRecorded {
+                    referee: bettor,
+                    referrer,
+                });
+            }
+
+            self.accept_bet(bettor, race_id, horse_id, amount)
+        }
+
+        /// Place a bet signed off-chain by the bettor, funded from their deposit.
+        /// The signature is an ECDSA signature over blake2_256(SCALE(contract, order)),
+        /// and the bettor's account is the blake2_256 hash of the compressed public key.
+        #[ink(message)]
+        pub fn place_bet_signed(&mut self, order: BetOrder, signature: [u8; 65]) -> Result<(), Error> {
+            if self.env().block_number() > order.deadline {
+                return Err(Error::OrderExpired);
+            }
+
+            let payload = scale::Encode::encode(&(self.env().account_id(), &order));
+            let message_hash = self.env().hash_bytes::<Blake2x256>(&payload);
+            let public_key = self
+                .env()
+                .ecdsa_recover(&signature, &message_hash)
+                .map_err(|_| Error::InvalidSignature)?;
+            let signer = AccountId::from(self.env().hash_bytes::<Blake2x256>(&public_key));
+            if signer != order.bettor {
+                return Err(Error::InvalidSignature);
+            }
+
+            let nonce = self.bet_nonces.get(order.bettor).unwrap_or(0);
+            if order.nonce != nonce {
+                return Err(Error::InvalidNonce);
+            }
+            self.bet_nonces.insert(order.bettor, &(nonce + 1));
+
+            let deposit = self.deposits.get(order.bettor).unwrap_or(0);
+            if order.amount > deposit {
+                return Err(Error::InsufficientDeposit);
+            }
+            self.deposits.insert(order.bettor, &(deposit - order.amount));
+            self.total_deposits -= order.amount;
+
+            self.record_bet(order.bettor, order.race_id, order.horse_id, order.amount)?;
+
+            self.env().emit_event(SignedBetRelayed {
+                bettor: order.bettor,
+                relayer: self.env().caller(),
+                nonce,
+            });
+
+            Ok(())
+        }
+
+        /// Add to the caller's prepaid balance for signed bets
+        #[ink(message, payable)]
+        pub fn deposit(&mut self) -> Result<(), Error> {
+            let account = self.env().caller();
+            let amount = self.env().transferred_value();
+
+            if amount == 0 {
+                return Err(Error::InvalidBetAmount);
+            }
+
+            self.credit_deposit(account, amount);
+
+            self.env().emit_event(Deposited { account, amount });
+
+            Ok(())
+        }
+
+        /// Withdraw from the caller's prepaid balance
+        #[ink(message)]
+        pub fn withdraw(&mut self, amount: Balance) -> Result<(), Error> {
+            let account = self.env().caller();
+            self.ensure_not_frozen(account)?;
+
+            let balance = self.deposits.get(account).unwrap_or(0);
+
+            if amount > balance {
+                return Err(Error::InsufficientDeposit);
+            }
+
+            self.deposits.insert(account, &(balance - amount));
+            self.total_deposits -= amount;
+
+            if self.env().transfer(account, amount).is_err() {
+                return Err(Error::TransferFailed);
+            }
+            self.ensure_solvent()?;
+
+            self.env().emit_event(Withdrawn { account, amount });
+
+            Ok(())
+        }
+
+        /// Place several bets at once; the transferred value must equal the total stake
+        #[ink(message, payable)]
+        pub fn place_slip(&mut self, legs: Vec<SlipLeg>) -> Result<(), Error> {
+            let bettor = self.env().caller();
+            let amount = self.env().transferred_value();
+
+            if legs.is_empty() || legs.len() > MAX_SLIP_LEGS {
+                return Err(Error::InvalidSlip);
+            }
+
+            if Self::slip_total(&legs)? != amount {
+                return Err(Error::InvalidBetAmount);
+            }
+
+            for leg in legs.iter() {
+                self.record_bet(bettor, leg.race_id, leg.horse_id, leg.amount)?;
+            }
+
+            self.env().emit_event(SlipPlaced {
+                bettor,
+                legs: legs.len() as u32,
+                amount,
+            });
+
+            Ok(())
+        }
+
+        /// Place a parlay across several races. Parlays are banked by the house, which reserves
+        /// the most the parlay can win out of its fees; losing stakes go to accrued fees.
+        #[ink(message, payable)]
+        pub fn place_parlay(&mut self, legs: Vec<(u8, u8)>) -> Result<u32, Error> {
+            let bettor = self.env().caller();
+            let amount = self.env().transferred_value();
+
+            if amount == 0 {
+                return Err(Error::InvalidBetAmount);
+            }
+
+            if legs.len() < 2 || legs.len() > MAX_PARLAY_LEGS {
+                return Err(Error::InvalidParlay);
+            }
+
+            let mut races = Vec::new();
+            for (index, (race_id, horse_id)) in legs.iter().enumerate() {
+                if legs[..index].iter().any(|(other, _)| other == race_id) {
+                    return Err(Error::InvalidParlay);
+                }
+                let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+                if race.bet_mode != BetMode::Parimutuel {
+                    return Err(Error::WrongBetMode);
+                }
+                self.check_market(&race, *horse_id)?;
+                races.push(race);
+            }
+
+            // The parlay counts against the account in its first leg's season
+            self.refresh_streak(bettor);
+            self.check_account(bettor, &races[0], amount, 0)?;
+
+            let reserve = amount
+                .checked_mul(self.parlay_max_multiple.saturating_sub(1) as u128)
+                .ok_or(Error::InvalidBetAmount)?;
+            if reserve > self.accrued_fees {
+                return Err(Error::InsufficientFees);
+            }
+            self.accrued_fees -= reserve;
+            self.parlay_reserves += reserve;
+
+            let parlay_id = self.next_parlay_id;
+            self.next_parlay_id += 1;
+
+            self.parlays.insert(parlay_id, &Parlay {
+                bettor,
+                legs: legs.clone(),
+                amount,
+                reserve,
+                settled: false,
+            });
+            self.open_parlay_stakes += amount;
+
+            let season = races[0].season;
+            let mut pnl = self.season_pnl.get((bettor, season)).unwrap_or_default();
+            pnl.staked += amount;
+            self.season_pnl.insert((bettor, season), &pnl);
+            self.day_totals.handle += amount;
+
+            self.env().emit_event(ParlayPlaced {
+                parlay_id,
+                bettor,
+                legs,
+                amount,
+            });
+
+            Ok(parlay_id)
+        }
+
+        /// Settle a decided parlay, paying any winnings to its bettor.
+        /// Payout is the stake multiplied by each winning leg's odds at finish;
+        /// legs on cancelled races or scratched horses are void.
+        #[ink(message)]
+        pub fn settle_parlay(&mut self, parlay_id: u32) -> Result<Balance, Error> {
+            let mut parlay = self.parlays.get(parlay_id).ok_or(Error::ParlayNotFound)?;
+
+            if parlay.settled {
+                return Err(Error::AlreadyClaimed);
+            }
+
+            // A single losing leg decides the parlay; otherwise every race must be settled
+            let mut payout = Some(parlay.amount);
+            let mut undecided = false;
+            let mut season = None;
+            for (race_id, horse_id) in parlay.legs.iter() {
+                let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+                season = season.or(Some(race.season));
+                match race.status {
+                    RaceStatus::Finished | RaceStatus::Archived => {
+                        if race.winners.contains(horse_id) {
+                            if let (Some(stake), Some((net_pool, winning_pool))) = (payout, self.race_odds.get(race_id)) {
+                                payout = Some(mul_div(stake, net_pool, winning_pool)?);
+                            }
+                        } else if race.rankings.contains(horse_id) {
+                            payout = None;
+                        }
+                    }
+                    RaceStatus::Cancelled => {}
+                    _ => undecided = true,
+                }
+            }
+
+            if undecided && payout.is_some() {
+                return Err(Error::ParlayUndecided);
+            }
+
+            parlay.settled = true;
+            self.parlays.insert(parlay_id, &parlay);
+            self.open_parlay_stakes -= parlay.amount;
+            self.parlay_reserves -= parlay.reserve;
+
+            // The stake and reserve back the payout, the rest goes back to the house
+            let backing = parlay.amount + parlay.reserve;
+            let payout = payout.unwrap_or(0).min(backing);
+            self.accrued_fees += backing - payout;
+
+            if payout > 0 {
+                self.record_returned(parlay.bettor, season.unwrap_or_default(), payout);
+                self.day_totals.payouts += payout;
+
+                if self.env().transfer(parlay.bettor, payout).is_err() {
+                    return Err(Error::TransferFailed);
+                }
+                self.ensure_solvent()?;
+            }
+
+            self.env().emit_event(ParlaySettled {
+                parlay_id,
+                bettor: parlay.bettor,
+                payout,
+            });
+
+            Ok(payout)
+        }
+
+        /// Bet on a running horse in the race's live pool. The stake's share of the live pool
+        /// is discounted by the distance the horse has already covered.
+        #[ink(message, payable)]
+        pub fn place_live_bet(&mut self, race_id: u8, horse_id: u8) -> Result<Balance, Error> {
+            let bettor = self.env().caller();
+            let amount = self.env().transferred_value();
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            let horse = self.check_live_market(&race, horse_id)?;
+
+            self.refresh_streak(bettor);
+            self.check_account(bettor, &race, amount, 0)?;
+
+            let mut pnl = self.season_pnl.get((bettor, race.season)).unwrap_or_default();
+            pnl.staked += amount;
+            self.season_pnl.insert((bettor, race.season), &pnl);
+
+            let weight = mul_div(amount, (FINISH_LINE - horse.position) as u128, FINISH_LINE as u128)?;
+
+            let count = self.live_bet_count.get((bettor, race_id)).unwrap_or(0);
+            self.live_bets.insert((bettor, race_id, count), &LiveBet { horse_id, amount, weight });
+            self.live_bet_count.insert((bettor, race_id), &(count + 1));
+
+            let pool = self.live_pools.get((race_id, horse_id)).unwrap_or(0);
+            self.live_pools.insert((race_id, horse_id), &(pool + weight));
+
+            let total = self.live_total.get(race_id).unwrap_or(0);
+            self.live_total.insert(race_id, &(total + amount));
+
+            self.day_totals.handle += amount;
+
+            self.env().emit_event(LiveBetPlaced {
+                bettor,
+                race_id,
+                horse_id,
+                amount,
+                weight,
+            });
+
+            Ok(weight)
+        }
+
+        /// Claim live pool winnings on a finished race, or refunds on a cancelled one
+        #[ink(message)]
+        pub fn claim_live_winnings(&mut self, race_id: u8) -> Result<Balance, Error> {
+            let bettor = self.env().caller();
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            if self.live_claimed.get((bettor, race_id)).unwrap_or(false) {
+                return Err(Error::AlreadyClaimed);
+            }
+
+            let count = self.live_bet_count.get((bettor, race_id)).unwrap_or(0);
+            let bets: Vec<LiveBet> = (0..count).filter_map(|i| self.live_bets.get((bettor, race_id, i))).collect();
+
+            let amount = match race.status {
+                RaceStatus::Cancelled => {
+                    let refund: Balance = bets.iter().map(|bet| bet.amount).sum();
+                    self.day_totals.refunds += refund;
+                    self.release_cancel_refund(race_id, refund);
+                    refund
+                }
+                RaceStatus::Finished | RaceStatus::Archived => {
+                    self.ensure_claim_open(&race)?;
+                    let weight: Balance = bets
+                        .iter()
+                        .filter(|bet| race.winners.contains(&bet.horse_id))
+                        .map(|bet| bet.weight)
+                        .sum();
+                    let winning_weight = self.live_winning_weight(&race);
+                    if weight == 0 || winning_weight == 0 {
+                        return Err(Error::NoWinningBets);
+                    }
+
+                    let net_pool = self.live_total.get(race_id).unwrap_or(0) - self.live_fees.get(race_id).unwrap_or(0);
+                    let payout = mul_div(weight, net_pool, winning_weight)?;
+                    self.release_winnings(race.id, payout);
+                    self.day_totals.payouts += payout;
+                    payout
+                }
+                _ => return Err(Error::RaceNotFinished),
+            };
+
+            if amount == 0 {
+                return Err(Error::NothingToClaim);
+            }
+
+            self.live_claimed.insert((bettor, race_id), &true);
+            self.record_returned(bettor, race.season, amount);
+
+            if self.env().transfer(bettor, amount).is_err() {
+                return Err(Error::TransferFailed);
+            }
+            self.ensure_solvent()?;
+
+            self.env().emit_event(LiveBetsSettled {
+                bettor,
+                race_id,
+                amount,
+            });
+
+            Ok(amount)
+        }
+
+        /// Bet the transferred value on any market
+        #[ink(message, payable)]
+        pub fn bet(&mut self, market_id: MarketId) -> Result<(), Error> {
+            match market_id.market {
+                Market::Win { horse_id } => self.place_bet(market_id.race_id, horse_id),
+                Market::Live { horse_id } => self.place_live_bet(market_id.race_id, horse_id).map(|_| ()),
+                Market::TrifectaBox { horses } => self.place_trifecta_box(market_id.race_id, horses).map(|_| ()),
+            }
+        }
+
+        /// Claim the caller's winnings or refunds from a market type of a race.
+        /// Claims cover every selection the caller backed in that market type.
+        #[ink(message)]
+        pub fn claim(&mut self, market_id: MarketId) -> Result<Balance, Error> {
+            let race = self.races.get(market_id.race_id).ok_or(Error::RaceNotFound)?;
+            match market_id.market {
+                Market::Win { .. } if race.status == RaceStatus::Cancelled => self.claim_refund(race.id),
+                Market::Win { .. } => self.claim_winnings(race.id),
+                Market::Live { .. } => self.claim_live_winnings(race.id),
+                Market::TrifectaBox { .. } => self.claim_trifecta_tickets(race.id),
+            }
+        }
+
+        /// Back a horse to be the overall champion of a tournament, until its first race starts
+        #[ink(message, payable)]
+        pub fn bet_on_champion(&mut self, tournament_id: u32, horse_id: u8) -> Result<(), Error> {
+            let bettor = self.env().caller();
+            let amount = self.env().transferred_value();
+            let mut tournament = self.tournaments.get(tournament_id).ok_or(Error::TournamentNotFound)?;
+
+            // The bet counts against the account in its first race's season
+            let first_race = self.races.get(tournament.races[0]).ok_or(Error::RaceNotFound)?;
+            self.refresh_streak(bettor);
+            self.check_account(bettor, &first_race, amount, 0)?;
+
+            if horse_id >= tournament.field_size {
+                return Err(Error::InvalidHorse { max: tournament.field_size - 1 });
+            }
+
+            // Betting closed when the first of the tournament's races started
+            let started = tournament
+                .races
+                .iter()
+                .filter_map(|race_id| self.races.get(race_id))
+                .find(|race| race.status != RaceStatus::Pending);
+            if let Some(race) = started {
+                return Err(Error::BettingClosed { closes_at: race.start_block });
+            }
+
+            let stake = self.tournament_bets.get((tournament_id, bettor, horse_id)).unwrap_or(0);
+            self.tournament_bets.insert((tournament_id, bettor, horse_id), &(stake + amount));
+
+            let pool = self.tournament_pools.get((tournament_id, horse_id)).unwrap_or(0);
+            self.tournament_pools.insert((tournament_id, horse_id), &(pool + amount));
+
+            tournament.total_pool += amount;
+            self.tournaments.insert(tournament_id, &tournament);
+            self.open_tournament_pools += amount;
+
+            let mut pnl = self.season_pnl.get((bettor, first_race.season)).unwrap_or_default();
+            pnl.staked += amount;
+            self.season_pnl.insert((bettor, first_race.season), &pnl);
+            self.day_totals.handle += amount;
+
+            self.env().emit_event(TournamentBetPlaced {
+                tournament_id,
+                bettor,
+                horse_id,
+                amount,
+            });
+
+            Ok(())
+        }
+
+        /// Crown the horses with the most championship points once every race of the
+        /// tournament is decided, taking the house fee from the champion pool.
+        /// Without a backed champion the pool rolls into the jackpot; if no race finished
+        /// every stake is refunded instead.
+        #[ink(message)]
+        pub fn settle_tournament(&mut self, tournament_id: u32) -> Result<(), Error> {
+            let mut tournament = self.tournaments.get(tournament_id).ok_or(Error::TournamentNotFound)?;
+
+            if tournament.settled {
+                return Err(Error::InvalidTournament);
+            }
+
+            let mut any_finished = false;
+            for race_id in tournament.races.iter() {
+                let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+                if matches!(race.status, RaceStatus::Pending | RaceStatus::Active) {
+                    return Err(Error::TournamentUndecided);
+                }
+                any_finished |= matches!(race.status, RaceStatus::Finished | RaceStatus::Archived);
+            }
+
+            if !any_finished {
+                self.open_tournament_pools -= tournament.total_pool;
+                self.unclaimed_winnings += tournament.total_pool;
+                tournament.refunded = true;
+                tournament.settled = true;
+                self.tournaments.insert(tournament_id, &tournament);
+
+                self.env().emit_event(TournamentSettled {
+                    tournament_id,
+                    champions: Vec::new(),
+                    net_pool: 0,
+                    winning_pool: 0,
+                });
+
+                return Ok(());
+            }
+
+            let standings = self.tournament_standings(&tournament);
+            let best = standings.iter().copied().max().unwrap_or(0);
+            if best > 0 {
+                tournament.champions = (0..tournament.field_size).filter(|i| standings[*i as usize] == best).collect();
+            }
+
+            let fee = mul_div(tournament.total_pool, self.house_fee_bps as u128, BPS_DENOMINATOR)?;
+            let net_pool = tournament.total_pool - fee;
+            let winning_pool: Balance = tournament
+                .champions
+                .iter()
+                .map(|horse_id| self.tournament_pools.get((tournament_id, *horse_id)).unwrap_or(0))
+                .sum();
+
+            self.accrued_fees += fee;
+            self.open_tournament_pools -= tournament.total_pool;
+            if winning_pool > 0 {
+                tournament.net_pool = net_pool;
+                tournament.winning_pool = winning_pool;
+                self.unclaimed_winnings += net_pool;
+            } else if net_pool > 0 {
+                self.jackpot += net_pool;
+            }
+
+            tournament.settled = true;
+            self.tournaments.insert(tournament_id, &tournament);
+
+            self.env().emit_event(TournamentSettled {
+                tournament_id,
+                champions: tournament.champions,
+                net_pool: tournament.net_pool,
+                winning_pool: tournament.winning_pool,
+            });
+
+            Ok(())
+        }
+
+        /// Claim the caller's share of a settled tournament's champion pool, or their stakes
+        /// back on a refunded tournament
+        #[ink(message)]
+        pub fn claim_tournament_winnings(&mut self, tournament_id: u32) -> Result<Balance, Error> {
+            let bettor = self.env().caller();
+            let tournament = self.tournaments.get(tournament_id).ok_or(Error::TournamentNotFound)?;
+
+            if !tournament.settled {
+                return Err(Error::TournamentUndecided);
+            }
+
+            if self.tournament_claimed.get((tournament_id, bettor)).unwrap_or(false) {
+                return Err(Error::AlreadyClaimed);
+            }
+
+            let payout = if tournament.refunded {
+                let refund: Balance = (0..tournament.field_size)
+                    .map(|horse_id| self.tournament_bets.get((tournament_id, bettor, horse_id)).unwrap_or(0))
+                    .sum();
+                if refund == 0 {
+                    return Err(Error::NothingToRefund);
+                }
+                self.day_totals.refunds += refund;
+                refund
+            } else {
+                let stake: Balance = tournament
+                    .champions
+                    .iter()
+                    .map(|horse_id| self.tournament_bets.get((tournament_id, bettor, *horse_id)).unwrap_or(0))
+                    .sum();
+                if stake == 0 || tournament.winning_pool == 0 {
+                    return Err(Error::NoWinningBets);
+                }
+                let payout = mul_div(stake, tournament.net_pool, tournament.winning_pool)?;
+                self.day_totals.payouts += payout;
+                payout
+            };
+
+            self.tournament_claimed.insert((tournament_id, bettor), &true);
+            self.unclaimed_winnings = self.unclaimed_winnings.saturating_sub(payout);
+            if let Some(race) = self.races.get(tournament.races[0]) {
+                self.record_returned(bettor, race.season, payout);
+            }
+
+            if self.env().transfer(bettor, payout).is_err() {
+                return Err(Error::TransferFailed);
+            }
+            self.ensure_solvent()?;
+
+            self.env().emit_event(TournamentWinningsClaimed {
+                tournament_id,
+                bettor,
+                payout,
+            });
+
+            Ok(payout)
+        }
+
+        /// Box the selected horses for the trifecta: one ticket covering every ordered
+        /// permutation of three of them, with the stake split evenly across permutations
+        #[ink(message, payable)]
+        pub fn place_trifecta_box(&mut self, race_id: u8, horses: Vec<u8>) -> Result<u32, Error> {
+            let bettor = self.env().caller();
+            let amount = self.env().transferred_value();
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            self.check_trifecta_box(&race, &horses)?;
+
+            self.refresh_streak(bettor);
+            self.check_account(bettor, &race, amount, 0)?;
+
+            let permutations = Self::box_permutations(&horses);
+            let unit_stake = amount / permutations.len() as u128;
+            if unit_stake == 0 {
+                return Err(Error::InvalidBetAmount);
+            }
+
+            for (first, second, third) in permutations.iter() {
+                let key = (race_id, *first, *second, *third);
+                let pool = self.trifecta_pools.get(key).unwrap_or(0);
+                self.trifecta_pools.insert(key, &(pool + unit_stake));
+            }
+
+            // Any remainder of the split stays in the pool for the winners
+            let total = self.trifecta_totals.get(race_id).unwrap_or(0);
+            self.trifecta_totals.insert(race_id, &(total + amount));
+
+            let mut pnl = self.season_pnl.get((bettor, race.season)).unwrap_or_default();
+            pnl.staked += amount;
+            self.season_pnl.insert((bettor, race.season), &pnl);
+            self.day_totals.handle += amount;
+
+            let ticket_id = self.next_trifecta_ticket_id;
+            self.next_trifecta_ticket_id += 1;
+            self.trifecta_tickets.insert(ticket_id, &TrifectaTicket {
+                bettor,
+                race_id,
+                horses: horses.clone(),
+                amount,
+                unit_stake,
+                claimed: false,
+            });
+            let mut ticket_ids = self.trifecta_ticket_ids.get((bettor, race_id)).unwrap_or_default();
+            ticket_ids.push(ticket_id);
+            self.trifecta_ticket_ids.insert((bettor, race_id), &ticket_ids);
+
+            self.env().emit_event(TrifectaBoxPlaced {
+                ticket_id,
+                bettor,
+                race_id,
+                horses,
+                permutations: permutations.len() as u32,
+                amount,
+            });
+
+            Ok(ticket_id)
+        }
+
+        /// Claim a trifecta ticket: the winning permutation's share of the net trifecta pool,
+        /// or the full stake back if the race was cancelled or fewer than three horses finished
+        #[ink(message)]
+        pub fn claim_trifecta(&mut self, ticket_id: u32) -> Result<Balance, Error> {
+            let mut ticket = self.trifecta_tickets.get(ticket_id).ok_or(Error::TicketNotFound)?;
+
+            if ticket.bettor != self.env().caller() {
+                return Err(Error::Unauthorized);
+            }
+
+            if ticket.claimed {
+                return Err(Error::AlreadyClaimed);
+            }
+
+            let race = self.races.get(ticket.race_id).ok_or(Error::RaceNotFound)?;
+            if race.status != RaceStatus::Cancelled {
+                self.ensure_claim_open(&race)?;
+            }
+
+            let amount = match race.status {
+                RaceStatus::Cancelled => {
+                    self.day_totals.refunds += ticket.amount;
+                    self.release_cancel_refund(race.id, ticket.amount);
+                    ticket.amount
+                }
+                RaceStatus::Finished | RaceStatus::Archived if race.rankings.len() < 3 => {
+                    self.release_winnings(race.id, ticket.amount);
+                    self.day_totals.refunds += ticket.amount;
+                    ticket.amount
+                }
+                RaceStatus::Finished | RaceStatus::Archived => {
+                    // A box holds at most one permutation of the first three home
+                    if !race.rankings[..3].iter().all(|horse_id| ticket.horses.contains(horse_id)) {
+                        return Err(Error::NoWinningBets);
+                    }
+                    let winning_stake = self.trifecta_winning_stake(&race);
+                    let net_pool = self.trifecta_totals.get(race.id).unwrap_or(0)
+                        - self.trifecta_fees.get(race.id).unwrap_or(0);
+                    let payout = mul_div(ticket.unit_stake, net_pool, winning_stake)?;
+                    self.release_winnings(race.id, payout);
+                    self.day_totals.payouts += payout;
+                    payout
+                }
+                _ => return Err(Error::RaceNotFinished),
+            };
+
+            ticket.claimed = true;
+            self.trifecta_tickets.insert(ticket_id, &ticket);
+            self.record_returned(ticket.bettor, race.season, amount);
+
+            if self.env().transfer(ticket.bettor, amount).is_err() {
+                return Err(Error::TransferFailed);
+            }
+            self.ensure_solvent()?;
+
+            self.env().emit_event(TrifectaClaimed {
+                ticket_id,
+                bettor: ticket.bettor,
+                payout: amount,
+            });
+
+            Ok(amount)
+        }
+
+        /// Spread one stake across every race of a card according to `strategy`.
+        /// Each allocation is recorded as an individual bet; rounding remainders go to the last race.
+        #[ink(message, payable)]
+        pub fn bet_card(&mut self, card_id: u32, strategy: CardStrategy) -> Result<(), Error> {
+            let races = self.cards.get(card_id).ok_or(Error::CardNotFound)?;
+            let bettor = self.env().caller();
+            let amount = self.env().transferred_value();
+
+            if amount == 0 {
+                return Err(Error::InvalidBetAmount);
+            }
+
+            // (race_id, horse_id, weight) for every leg
+            let mut legs = Vec::new();
+            match strategy {
+                CardStrategy::EqualSplit(horses) => {
+                    if horses.len() != races.len() {
+                        return Err(Error::InvalidCardStrategy);
+                    }
+                    for (race_id, horse_id) in races.iter().zip(horses) {
+                        legs.push((*race_id, horse_id, 1u128));
+                    }
+                }
+                CardStrategy::FavoritesOnly => {
+                    for race_id in races.iter() {
+                        let favorite = self.favorite(*race_id).ok_or(Error::InvalidCardStrategy)?;
+                        legs.push((*race_id, favorite, 1u128));
+                    }
+                }
+                CardStrategy::Weighted(picks) => {
+                    if picks.len() != races.len() {
+                        return Err(Error::InvalidCardStrategy);
+                    }
+                    for (race_id, (horse_id, weight)) in races.iter().zip(picks) {
+                        if weight > 0 {
+                            legs.push((*race_id, horse_id, weight as u128));
+                        }
+                    }
+                }
+            }
+
+            let total_weight: u128 = legs.iter().map(|(_, _, weight)| weight).sum();
+            if total_weight == 0 {
+                return Err(Error::InvalidCardStrategy);
+            }
+
+            let mut remaining = amount;
+            let last = legs.len() - 1;
+            for (index, (race_id, horse_id, weight)) in legs.into_iter().enumerate() {
+                let stake = if index == last {
+                    remaining
+                } else {
+                    mul_div(amount, weight, total_weight)?
+                };
+                remaining -= stake;
+
+                if stake > 0 {
+                    self.record_bet(bettor, race_id, horse_id, stake)?;
+                }
+            }
+
+            self.env().emit_event(CardBetPlaced {
+                bettor,
+                card_id,
+                amount,
+            });
+
+            Ok(())
+        }
+
+        /// Ensure the race is open for bets on the horse
+        fn check_market(&self, race: &Race, horse_id: u8) -> Result<(), Error> {
+            let closes_at = self.betting_deadline(race);
+            if race.status != RaceStatus::Pending || self.env().block_number() >= closes_at {
+                return Err(Error::BettingClosed { closes_at });
+            }
+
+            if horse_id >= race.field_size {
+                return Err(Error::InvalidHorse { max: race.field_size - 1 });
+            }
+
+            if self.horse_holds.contains((race.id, horse_id)) {
+                return Err(Error::HorseOnHold);
+            }
+
+            if self.is_scratched(race.id, horse_id) {
+                return Err(Error::HorseNotRunning);
+            }
+
+            Ok(())
+        }
+
+        /// Ensure the race's live pool is open for bets on the horse, returning the horse
+        fn check_live_market(&self, race: &Race, horse_id: u8) -> Result<Horse, Error> {
+            if race.status != RaceStatus::Active {
+                return Err(Error::RaceNotActive);
+            }
+
+            if race.bet_mode != BetMode::Parimutuel {
+                return Err(Error::WrongBetMode);
+            }
+
+            // Oracle races have no running positions to price live bets on
+            if race.oracle.is_some() {
+                return Err(Error::OracleSettled);
+            }
+
+            let horses = self.load_horses(race.id);
+            let leader = horses.iter().map(|horse| horse.position).max().unwrap_or(0);
+            if leader as u128 * BPS_DENOMINATOR >= FINISH_LINE as u128 * LIVE_BETTING_CUTOFF_BPS {
+                return Err(Error::BettingClosed {
+                    closes_at: race.current_block,
+                });
+            }
+
+            let horse = horses
+                .get(horse_id as usize)
+                .cloned()
+                .ok_or(Error::InvalidHorse { max: race.field_size - 1 })?;
+            if horse.scratched || horse.finished {
+                return Err(Error::HorseNotRunning);
+            }
+
+            Ok(horse)
+        }
+
+        /// Combined stake of a slip's legs, rejecting totals that overflow
+        fn slip_total(legs: &[SlipLeg]) -> Result<Balance, Error> {
+            legs.iter()
+                .try_fold(0u128, |total, leg| total.checked_add(leg.amount))
+                .ok_or(Error::InvalidBetAmount)
+        }
+
+        /// Every check a bet must pass, given the stake and bets of earlier legs of the same slip
+        fn check_bet(
+            &self,
+            bettor: AccountId,
+            race: &Race,
+            horse_id: u8,
+            amount: Balance,
+            prior: &PriorLegs,
+        ) -> Result<(), Error> {
+            self.check_market(race, horse_id)?;
+            self.ensure_within_rate_limits(bettor, race, prior.race_bets, prior.bets)?;
+            self.ensure_within_pool_limit(race, self.total_pool.get(race.id).unwrap_or(0), prior.pool + amount)?;
+            if amount > self.pool_cap_room(race, horse_id, prior.pool, prior.horse_pool) {
+                return Err(Error::PoolCapReached);
+            }
+            self.check_account(bettor, race, amount, prior.stake)
+        }
+
+        /// Responsible gambling checks on the bettor: streak, new account limit and loss cap
+        fn check_account(&self, bettor: AccountId, race: &Race, amount: Balance, prior_stake: Balance) -> Result<(), Error> {
+            self.ensure_not_frozen(bettor)?;
+            self.ensure_not_restricted(bettor)?;
+            self.ensure_streak_clear(bettor)?;
+
+            if amount == 0 {
+                return Err(Error::InvalidBetAmount);
+            }
+
+            let history = self.account_history.get(bettor).unwrap_or(AccountHistory {
+                first_bet_block: self.env().block_number(),
+                races_entered: 0,
+            });
+            if let Some(max_bet) = self.bet_limit_for(&history) {
+                if amount > max_bet {
+                    return Err(Error::BetAboveLimit { max_bet });
+                }
+            }
+
+            let pnl = self.season_pnl.get((bettor, race.season)).unwrap_or_default();
+            self.ensure_within_loss_cap(bettor, &pnl, prior_stake + amount)
+        }
+
+        /// Ensure the bettor is neither cooling off nor owes a losing streak acknowledgement
+        fn ensure_streak_clear(&self, bettor: AccountId) -> Result<(), Error> {
+            let (streak, _) = self.evaluate_streak(bettor);
+            if self.env().block_number() < streak.cooling_off_until {
+                return Err(Error::CoolingOff {
+                    until: streak.cooling_off_until,
+                });
+            }
+            if streak.threshold > 0 && streak.consecutive_losses >= streak.threshold {
+                return Err(Error::StreakAcknowledgementRequired);
+            }
+            Ok(())
+        }
+
+        /// Ensure staking `amount` more keeps the season's net losses in `pnl` within the bettor's cap
+        fn ensure_within_loss_cap(&self, bettor: AccountId, pnl: &SeasonPnl, amount: Balance) -> Result<(), Error> {
+            if let Some(cap) = self.get_loss_cap(bettor) {
+                if (pnl.staked + amount).saturating_sub(pnl.returned) > cap {
+                    return Err(Error::LossCapReached { cap });
+                }
+            }
+            Ok(())
+        }
+
+        /// Lock the posted odds for a fixed-odds bet, provided the stakes and
+        /// reserve still cover the horse's payouts should it win
+        fn lock_odds(&mut self, bettor: AccountId, race: &Race, horse_id: u8, index: u32, amount: Balance) -> Result<(), Error> {
+            let mut odds = self.fixed_odds.get((race.id, horse_id)).ok_or(Error::OddsNotPosted)?;
+            if let Some(cap) = self.payout_caps.get(race.id) {
+                odds = odds.min(cap);
+            }
+            let payout = mul_div(amount, odds as u128, BPS_DENOMINATOR)?;
+
+            let liability = self.fixed_liabilities.get((race.id, horse_id)).unwrap_or(0) + payout;
+            let reserve = self.odds_reserves.get(race.id).map(|(_, reserve)| reserve).unwrap_or(0);
+            let stakes = self.total_pool.get(race.id).unwrap_or(0) + amount;
+            if liability > reserve + stakes {
+                return Err(Error::ReserveExceeded);
+            }
+
+            self.fixed_liabilities.insert((race.id, horse_id), &liability);
+            self.bet_odds.insert((bettor, race.id, index), &odds);
+
+            Ok(())
+        }
+
+        /// Ensure one more bet keeps the bettor within the per-race and per-window bet counts,
+        /// with `prior_race_bets` and `prior_bets` not yet recorded from earlier legs of a slip
+        fn ensure_within_rate_limits(
+            &self,
+            bettor: AccountId,
+            race: &Race,
+            prior_race_bets: u32,
+            prior_bets: u32,
+        ) -> Result<(), Error> {
+            let limits = self.bet_rate_limits;
+
+            let race_bets = self.bet_count.get((bettor, race.id)).unwrap_or(0) + prior_race_bets;
+            if limits.max_bets_per_race > 0 && race_bets >= limits.max_bets_per_race {
+                return Err(Error::TooManyBets {
+                    max_bets: limits.max_bets_per_race,
+                });
+            }
+
+            let window_bets = self.bets_in_window(bettor) + prior_bets;
+            if limits.max_bets_per_window > 0 && window_bets >= limits.max_bets_per_window {
+                let window = self.env().block_number() / limits.window_blocks;
+                return Err(Error::BetRateLimited {
+                    retry_at: (window + 1) * limits.window_blocks,
+                });
+            }
+
+            Ok(())
+        }
+
+        /// Bets placed by the account in the current block window
+        fn bets_in_window(&self, bettor: AccountId) -> u32 {
+            let window_blocks = self.bet_rate_limits.window_blocks;
+            if window_blocks == 0 {
+                return 0;
+            }
+            match self.bet_windows.get(bettor) {
+                Some((window, count)) if window == self.env().block_number() / window_blocks => count,
+                _ => 0,
+            }
+        }
+
+        fn count_in_window(&mut self, bettor: AccountId) {
+            if let Some(window) = self.env().block_number().checked_div(self.bet_rate_limits.window_blocks) {
+                self.bet_windows.insert(bettor, &(window, self.bets_in_window(bettor) + 1));
+            }
+        }
+
+        /// Validate and store a bet of `amount` already received from `bettor`
+        fn record_bet(&mut self, bettor: AccountId, race_id: u8, horse_id: u8, amount: Balance) -> Result<(), Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            self.refresh_streak(bettor);
+            self.check_bet(bettor, &race, horse_id, amount, &PriorLegs::default())?;
+
+            let mut history = self.account_history.get(bettor).unwrap_or(AccountHistory {
+                first_bet_block: self.env().block_number(),
+                races_entered: 0,
+            });
+
+            // Stakes count as losses until something is returned from the race
+            let mut pnl = self.season_pnl.get((bettor, race.season)).unwrap_or_default();
+            pnl.staked += amount;
+            self.season_pnl.insert((bettor, race.season), &pnl);
+
+            // Redeemed loyalty points top the stake up by the house fee it will bear
+            let amount = amount + self.loyalty_discount(bettor, race_id, amount);
+
+            let bet_id = self.next_bet_id;
+            self.next_bet_id += 1;
+            let bet = Bet {
+                id: bet_id,
+                bettor,
+                race_id,
+                horse_id,
+                amount,
+            };
+
+            let mut stats = self.race_stats.get(race_id).unwrap_or_default();
+            stats.bet_count += 1;
+            stats.largest_bet = stats.largest_bet.max(amount);
+
+            // Store bet
+            let count = self.bet_count.get((bettor, race_id)).unwrap_or(0);
+            if count == 0 {
+                stats.unique_bettors += 1;
+                let bettors = self.race_bettor_count.get(race_id).unwrap_or(0);
+                self.race_bettors.insert((race_id, bettors), &bettor);
+                self.race_bettor_count.insert(race_id, &(bettors + 1));
+                history.races_entered += 1;
+                self.accrue_loyalty(bettor, race_id);
+            }
+            self.account_history.insert(bettor, &history);
+            self.race_stats.insert(race_id, &stats);
+            if race.bet_mode == BetMode::FixedOdds {
+                self.lock_odds(bettor, &race, horse_id, count, amount)?;
+            }
+            self.bets.insert((bettor, race_id, count), &bet);
+            self.bets_by_id.insert(bet_id, &bet);
+            self.bet_count.insert((bettor, race_id), &(count + 1));
+            self.mint_ticket(bettor, bet_id);
+            self.mix_bet_entropy(race_id, bettor, amount);
+            self.count_in_window(bettor);
+
+            // Update pools
+            let shares_before = self.pool_shares(&race);
+            let total = self.total_pool.get(race_id).unwrap_or(0);
+            self.total_pool.insert(race_id, &(total + amount));
+
+            let horse_total = self.horse_pools.get((race_id, horse_id)).unwrap_or(0);
+            self.horse_pools.insert((race_id, horse_id), &(horse_total + amount));
+            self.detect_steam(&race, shares_before);
+
+            self.day_totals.handle += amount;
+
+            if let Some(referrer) = self.referrers.get(bettor) {
+                self.add_referred_handle(referrer, race_id, amount as i128);
+            }
+
+            self.env().emit_event(BetPlaced {
+                bet_id,
+                bettor,
+                race_id,
+                horse_id,
+                amount,
+            });
+
+            Ok(())
+        }
+
+        /// Boost a race's pool without taking a position.
+        /// Boosted funds are paid out to winners and are never refunded.
+        #[ink(message, payable)]
+        pub fn boost_pool(&mut self, race_id: u8) -> Result<(), Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            if race.status != RaceStatus::Pending && race.status != RaceStatus::Active {
+                return Err(Error::BettingClosed {
+                    closes_at: race.settled_at,
+                });
+            }
+
+            if race.bet_mode != BetMode::Parimutuel {
+                return Err(Error::WrongBetMode);
+            }
+
+            let sponsor = self.env().caller();
+            let amount = self.env().transferred_value();
+
+            if amount == 0 {
+                return Err(Error::InvalidBetAmount);
+            }
+
+            self.ensure_within_pool_limit(&race, self.total_pool.get(race_id).unwrap_or(0), amount)?;
+
+            let boosted = self.pool_boosts.get(race_id).unwrap_or(0);
+            self.pool_boosts.insert(race_id, &(boosted + amount));
+
+            // Boosts only enter the total pool, so they are shared by winners
+            let total = self.total_pool.get(race_id).unwrap_or(0);
+            self.total_pool.insert(race_id, &(total + amount));
+
+            self.env().emit_event(PoolBoosted {
+                sponsor,
+                race_id,
+                amount,
+            });
+
+            Ok(())
+        }
+
+        /// Bet `amount` of an approved PSP22 asset on a horse, pulled with `PSP22::transfer_from`.
+        /// Each asset forms its own parimutuel pool on the race, settled against the same result.
+        #[ink(message)]
+        pub fn place_asset_bet(&mut self, race_id: u8, horse_id: u8, asset: AccountId, amount: Balance) -> Result<(), Error> {
+            self.progress_active_race()?;
+
+            let bettor = self.env().caller();
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            self.ensure_not_frozen(bettor)?;
+            self.ensure_not_restricted(bettor)?;
+
+            if !self.approved_assets.contains(asset) {
+                return Err(Error::AssetNotApproved);
+            }
+
+            if amount == 0 {
+                return Err(Error::InvalidBetAmount);
+            }
+
+            if race.bet_mode != BetMode::Parimutuel {
+                return Err(Error::WrongBetMode);
+            }
+
+            self.check_market(&race, horse_id)?;
+
+            let asset_bets = self.asset_bets.get((bettor, race_id)).unwrap_or_default().len();
+            if asset_bets >= MAX_ASSET_BETS {
+                return Err(Error::TooManyBets {
+                    max_bets: MAX_ASSET_BETS as u32,
+                });
+            }
+
+            // Native bet checks, with the loss cap and pool limit applied within the asset
+            self.refresh_streak(bettor);
+            self.ensure_streak_clear(bettor)?;
+            self.ensure_within_rate_limits(bettor, &race, asset_bets as u32, 0)?;
+            let pool = self.asset_totals.get((race_id, asset)).unwrap_or(0);
+            self.ensure_within_pool_limit(&race, pool, amount)?;
+            let pnl = self.asset_season_pnl.get((bettor, race.season, asset)).unwrap_or_default();
+            self.ensure_within_loss_cap(bettor, &pnl, amount)?;
+
+            let assets = self.race_assets.get(race_id).unwrap_or_default();
+            if !assets.contains(&asset) && assets.len() >= MAX_RACE_ASSETS {
+                return Err(Error::TooManyAssets);
+            }
+
+            self.token_transfer_from(asset, bettor, amount)?;
+            self.record_asset_bet(bettor, &race, horse_id, asset, amount);
+
+            self.env().emit_event(AssetBetPlaced {
+                bettor,
+                race_id,
+                horse_id,
+                asset,
+                amount,
+            });
+
+            Ok(())
+        }
+
+        /// Claim winnings on every bet ticket the caller holds on a race
+        #[ink(message)]
+        pub fn claim_winnings(&mut self, race_id: u8) -> Result<Balance, Error> {
+            let caller = self.env().caller();
+
+            if self.active_claim_code(caller, race_id).is_some() {
+                return Err(Error::ClaimCodeActive);
+            }
+
+            self.progress_active_race()?;
+            self.pay_winnings(caller, race_id, caller)
+        }
+
+        /// Claim winnings to another address. The claim is still recorded against the caller.
+        #[ink(message)]
+        pub fn claim_winnings_to(&mut self, race_id: u8, recipient: AccountId) -> Result<Balance, Error> {
+            let caller = self.env().caller();
+
+            if self.active_claim_code(caller, race_id).is_some() {
+                return Err(Error::ClaimCodeActive);
+            }
+
+            self.progress_active_race()?;
+            self.pay_winnings(caller, race_id, recipient)
+        }
+
+        /// Lock the caller's winnings on a finished race behind a claim code.
+        /// Anyone presenting the preimage of `commitment` before expiry can redeem
+        /// the payout to an address of their choice.
+        #[ink(message)]
+        pub fn create_claim_code(&mut self, race_id: u8, commitment: Hash, valid_for_blocks: u32) -> Result<u32, Error> {
+            let bettor = self.env().caller();
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            self.winnings_of(bettor, &race)?;
+
+            if self.active_claim_code(bettor, race_id).is_some() {
+                return Err(Error::ClaimCodeActive);
+            }
+
+            if valid_for_blocks == 0 || valid_for_blocks > MAX_CLAIM_CODE_BLOCKS {
+                return Err(Error::InvalidClaimCode);
+            }
+
+            if self.claim_codes.contains(commitment) {
+                return Err(Error::InvalidClaimCode);
+            }
+
+            let expires_at = self.env().block_number() + valid_for_blocks;
+            self.claim_codes.insert(commitment, &ClaimCode {
+                bettor,
+                race_id,
+                expires_at,
+            });
+            self.bettor_claim_codes.insert((bettor, race_id), &commitment);
+
+            self.env().emit_event(ClaimCodeCreated {
+                bettor,
+                race_id,
+                commitment,
+                expires_at,
+            });
+
+            Ok(expires_at)
+        }
+
+        /// Cancel the caller's claim code on a race, re-enabling direct claims
+        #[ink(message)]
+        pub fn cancel_claim_code(&mut self, race_id: u8) -> Result<(), Error> {
+            let bettor = self.env().caller();
+            let commitment = self.bettor_claim_codes.take((bettor, race_id)).ok_or(Error::InvalidClaimCode)?;
+            self.claim_codes.remove(commitment);
+
+            Ok(())
+        }
+
+        /// Redeem a claim code by presenting its preimage, paying the winnings to `recipient`.
+        /// The preimage is public once submitted, so codes should be redeemed promptly.
+        #[ink(message)]
+        pub fn redeem_claim_code(&mut self, preimage: [u8; 32], recipient: AccountId) -> Result<Balance, Error> {
+            let commitment = Hash::from(self.env().hash_bytes::<Blake2x256>(&preimage));
+            let code = self.claim_codes.take(commitment).ok_or(Error::InvalidClaimCode)?;
+            self.bettor_claim_codes.remove((code.bettor, code.race_id));
+
+            if self.env().block_number() > code.expires_at {
+                return Err(Error::ClaimCodeExpired {
+                    expired_at: code.expires_at,
+                });
+            }
+
+            let payout = self.pay_winnings(code.bettor, code.race_id, recipient)?;
+
+            self.env().emit_event(ClaimCodeRedeemed {
+                commitment,
+                recipient,
+                payout,
+            });
+
+            Ok(payout)
+        }
+
+        /// PSP34 collection id of the bet tickets: this contract's address
+        #[ink(message, selector = 0xffa27a5f)]
+        pub fn collection_id(&self) -> Id {
+            let contract = self.env().account_id();
+            let mut bytes = Vec::new();
+            bytes.extend_from_slice(contract.as_ref());
+            Id::Bytes(bytes)
+        }
+
+        /// Number of bet tickets held by `owner`
+        #[ink(message, selector = 0xcde7e55f)]
+        pub fn balance_of(&self, owner: AccountId) -> u32 {
+            self.ticket_balances.get(owner).unwrap_or(0)
+        }
+
+        /// Current holder of a bet ticket, entitled to its payout or refund
+        #[ink(message, selector = 0x1168624d)]
+        pub fn owner_of(&self, id: Id) -> Option<AccountId> {
+            self.ticket(&id).ok().map(|(_, holder)| holder)
+        }
+
+        /// Whether `operator` may transfer the ticket `id` of `owner`, or every ticket of theirs when None
+        #[ink(message, selector = 0x4790f55a)]
+        pub fn allowance(&self, owner: AccountId, operator: AccountId, id: Option<Id>) -> bool {
+            if self.ticket_operators.contains((owner, operator)) {
+                return true;
+            }
+            match id.map(|id| self.ticket(&id)) {
+                Some(Ok((bet, holder))) => holder == owner && self.ticket_approvals.get(bet.id) == Some(operator),
+                _ => false,
+            }
+        }
+
+        /// Approve or revoke `operator` for one of the caller's tickets, or all of them when `id` is None
+        #[ink(message, selector = 0x1932a8b0)]
+        pub fn approve(&mut self, operator: AccountId, id: Option<Id>, approved: bool) -> Result<(), PSP34Error> {
+            let owner = self.env().caller();
+
+            if operator == owner {
+                return Err(PSP34Error::SelfApprove);
+            }
+
+            match &id {
+                Some(id) => {
+                    let (bet, holder) = self.ticket(id)?;
+                    if holder != owner {
+                        return Err(PSP34Error::NotApproved);
+                    }
+                    if approved {
+                        self.ticket_approvals.insert(bet.id, &operator);
+                    } else {
+                        self.ticket_approvals.remove(bet.id);
+                    }
+                }
+                None if approved => {
+                    self.ticket_operators.insert((owner, operator), &());
+                }
+                None => self.ticket_operators.remove((owner, operator)),
+            }
+
+            self.env().emit_event(Approval {
+                owner,
+                operator,
+                id,
+                approved,
+            });
+
+            Ok(())
+        }
+
+        /// Transfer a bet ticket, and with it the payout or refund of its bet, to `to`
+        #[ink(message, selector = 0x3128d61b)]
+        pub fn transfer(&mut self, to: AccountId, id: Id, _data: Vec<u8>) -> Result<(), PSP34Error> {
+            let (bet, from) = self.ticket(&id)?;
+            let caller = self.env().caller();
+
+            if caller != from && !self.allowance(from, caller, Some(id.clone())) {
+                return Err(PSP34Error::NotApproved);
+            }
+
+            if to == from {
+                return Ok(());
+            }
+
+            let race_id = bet.race_id;
+            let index = (0..self.bet_count.get((bet.bettor, race_id)).unwrap_or(0))
+                .find(|i| self.bets.get((bet.bettor, race_id, *i)).is_some_and(|b| b.id == bet.id))
+                .ok_or(PSP34Error::TokenNotExists)?;
+            let entry = (bet.bettor, index);
+
+            let mut received = self.acquired_tickets.get((to, race_id)).unwrap_or_default();
+            if received.len() >= MAX_HELD_TICKETS {
+                return Err(PSP34Error::Custom(String::from("TooManyTickets")));
+            }
+            received.push(entry);
+            self.acquired_tickets.insert((to, race_id), &received);
+
+            let mut sent = self.acquired_tickets.get((from, race_id)).unwrap_or_default();
+            sent.retain(|acquired| *acquired != entry);
+            if sent.is_empty() {
+                self.acquired_tickets.remove((from, race_id));
+            } else {
+                self.acquired_tickets.insert((from, race_id), &sent);
+            }
+
+            self.ticket_holders.insert(bet.id, &to);
+            self.ticket_approvals.remove(bet.id);
+
+            let from_balance = self.ticket_balances.get(from).unwrap_or(0);
+            self.ticket_balances.insert(from, &from_balance.saturating_sub(1));
+            let to_balance = self.ticket_balances.get(to).unwrap_or(0);
+            self.ticket_balances.insert(to, &(to_balance + 1));
+
+            self.env().emit_event(Transfer {
+                from: Some(from),
+                to: Some(to),
+                id,
+            });
+
+            Ok(())
+        }
+
+        /// Number of bet tickets not yet burned
+        #[ink(message, selector = 0x628413fe)]
+        pub fn total_supply(&self) -> Balance {
+            self.ticket_supply as Balance
+        }
+
+        /// Commit to a secret salt for a pending race: `commitment = blake2_256(salt ++ caller)`
+        #[ink(message)]
+        pub fn commit_entropy(&mut self, race_id: u8, commitment: Hash) -> Result<(), Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            if race.status != RaceStatus::Pending {
+                return Err(Error::RaceAlreadyStarted);
+            }
+
+            let account = self.env().caller();
+            if self.entropy_commitments.contains((race_id, account)) {
+                return Err(Error::AlreadyCommitted);
+            }
+
+            self.entropy_commitments.insert((race_id, account), &commitment);
+
+            self.env().emit_event(EntropyCommitted {
+                race_id,
+                account,
+                commitment,
+            });
+
+            Ok(())
+        }
+
+        /// Reveal a committed salt before the race starts, mixing it into the race entropy.
+        /// Unrevealed commitments are simply ignored.
+        #[ink(message)]
+        pub fn reveal_entropy(&mut self, race_id: u8, salt: [u8; 32]) -> Result<(), Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            if race.status != RaceStatus::Pending {
+                return Err(Error::RaceAlreadyStarted);
+            }
+
+            let account = self.env().caller();
+            let commitment = self.entropy_commitments.get((race_id, account)).ok_or(Error::NoCommitment)?;
+
+            let mut preimage = Vec::with_capacity(64);
+            preimage.extend_from_slice(&salt);
+            preimage.extend_from_slice(account.as_ref());
+            if Hash::from(self.env().hash_bytes::<Blake2x256>(&preimage)) != commitment {
+                return Err(Error::InvalidReveal);
+            }
+
+            self.entropy_commitments.remove((race_id, account));
+
+            // entropy = blake2_256(entropy ++ salt)
+            let mut input = Vec::with_capacity(64);
+            input.extend_from_slice(&self.race_entropy.get(race_id).unwrap_or([0u8; 32]));
+            input.extend_from_slice(&salt);
+            let entropy = self.env().hash_bytes::<Blake2x256>(&input);
+            self.race_entropy.insert(race_id, &entropy);
+
+            self.env().emit_event(EntropyRevealed { race_id, account });
+
+            Ok(())
+        }
+
+        /// Claim referral commissions earned on every settled race
+        #[ink(message)]
+        pub fn claim_referral_earnings(&mut self) -> Result<Balance, Error> {
+            let referrer = self.env().caller();
+            let mut amount = 0u128;
+
+            for race_id in 0..self.current_race_id {
+                let handle = self.referred_handle.get((referrer, race_id)).unwrap_or(0);
+                if handle == 0 || self.referral_claimed.get((referrer, race_id)).unwrap_or(false) {
+                    continue;
+                }
+
+                // Commissions are paid out of what the race set aside when it settled, so
+                // archived races still pay and the last referrer takes the rounding
+                if let Some((commission, referred)) = self.race_referral_unclaimed.get(race_id) {
+                    let share = mul_div(commission, handle.min(referred), referred)?;
+                    self.race_referral_unclaimed
+                        .insert(race_id, &(commission - share, referred.saturating_sub(handle)));
+                    amount += share;
+                    self.referral_claimed.insert((referrer, race_id), &true);
+                }
+            }
+
+            if amount == 0 {
+                return Err(Error::NothingToClaim);
+            }
+            self.referral_owed -= amount;
+
+            if self.env().transfer(referrer, amount).is_err() {
+                return Err(Error::TransferFailed);
+            }
+            self.ensure_solvent()?;
+
+            self.env().emit_event(ReferralEarningsClaimed { referrer, amount });
+
+            Ok(amount)
+        }
+
+        /// Set the losing streak length after which betting requires an acknowledgement.
+        /// The threshold cannot be raised or disabled while a streak is pending acknowledgement.
+        #[ink(message)]
+        pub fn set_streak_threshold(&mut self, threshold: u32) -> Result<(), Error> {
+            let account = self.env().caller();
+            let mut streak = self.refresh_streak(account);
+
+            let locked = streak.threshold > 0 && streak.consecutive_losses >= streak.threshold;
+            let loosens = threshold == 0 || threshold > streak.threshold;
+            if locked && loosens {
+                return Err(Error::StreakAcknowledgementRequired);
+            }
+
+            streak.threshold = threshold;
+            self.streaks.insert(account, &streak);
+
+            Ok(())
+        }
+
+        /// Acknowledge a losing streak; betting resumes after a cooling-off delay
+        #[ink(message)]
+        pub fn acknowledge_streak(&mut self) -> Result<u32, Error> {
+            let account = self.env().caller();
+            let mut streak = self.refresh_streak(account);
+
+            if streak.threshold == 0 || streak.consecutive_losses < streak.threshold {
+                return Err(Error::NoStreakToAcknowledge);
+            }
+
+            let losses = streak.consecutive_losses;
+            streak.consecutive_losses = 0;
+            streak.cooling_off_until = self.env().block_number() + STREAK_COOLING_OFF_BLOCKS;
+            self.streaks.insert(account, &streak);
+
+            self.env().emit_event(StreakAcknowledged {
+                account,
+                losses,
+                cooling_off_until: streak.cooling_off_until,
+            });
+
+            Ok(streak.cooling_off_until)
+        }
+
+        /// Claim the vested part of the caller's purse from a race
+        #[ink(message)]
+        pub fn claim_purse(&mut self, race_id: u8) -> Result<Balance, Error> {
+            let owner = self.env().caller();
+            let mut escrow = self.purse_escrows.get((owner, race_id)).ok_or(Error::NothingToClaim)?;
+
+            let amount = self.vested_purse(&escrow) - escrow.claimed;
+            if amount == 0 {
+                return Err(Error::NothingToClaim);
+            }
+
+            escrow.claimed += amount;
+            if escrow.claimed == escrow.total {
+                self.purse_escrows.remove((owner, race_id));
+            } else {
+                self.purse_escrows.insert((owner, race_id), &escrow);
+            }
+
+            if self.env().transfer(owner, amount).is_err() {
+                return Err(Error::TransferFailed);
+            }
+            self.ensure_solvent()?;
+
+            self.env().emit_event(PurseClaimed { owner, race_id, amount });
+
+            Ok(amount)
+        }
+
+        /// Submit the caller's free pick for a race; one pick per race, while betting is open
+        #[ink(message)]
+        pub fn submit_pick(&mut self, race_id: u8, horse_id: u8) -> Result<(), Error> {
+            let account = self.env().caller();
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            self.check_market(&race, horse_id)?;
+
+            if self.picks.contains((account, race_id)) {
+                return Err(Error::AlreadyPicked);
+            }
+
+            self.picks.insert((account, race_id), &Pick { horse_id, scored: false });
+
+            self.env().emit_event(PickSubmitted {
+                account,
+                race_id,
+                horse_id,
+            });
+
+            Ok(())
+        }
+
+        /// Score an account's pick on a finished race, adding its points to the
+        /// race's season and the season leaderboard. Anyone may score any pick.
+        #[ink(message)]
+        pub fn score_pick(&mut self, account: AccountId, race_id: u8) -> Result<u32, Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            if race.status != RaceStatus::Finished && race.status != RaceStatus::Archived {
+                return Err(Error::RaceNotFinished);
+            }
+
+            let mut pick = self.picks.get((account, race_id)).ok_or(Error::NothingToClaim)?;
+            if pick.scored {
+                return Err(Error::AlreadyClaimed);
+            }
+            pick.scored = true;
+            self.picks.insert((account, race_id), &pick);
+
+            let points = if race.winners.contains(&pick.horse_id) { PICK_POINTS } else { 0 };
+            let total = self.season_points.get((account, race.season)).unwrap_or(0) + points;
+            if points > 0 {
+                self.season_points.insert((account, race.season), &total);
+                self.update_leaderboard(race.season, account, total);
+            }
+
+            self.env().emit_event(PickScored {
+                account,
+                race_id,
+                points,
+                season_points: total,
+            });
+
+            Ok(points)
+        }
+
+        /// Report the gross per-horse pools of a mirrored deployment of a race.
+        /// A later report from the same deployment replaces the earlier one.
+        #[ink(message)]
+        pub fn report_mirror_pools(&mut self, race_id: u8, deployment: u32, pools: Vec<Balance>) -> Result<(), Error> {
+            self.ensure_bridge()?;
+
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            if race.status != RaceStatus::Pending && race.status != RaceStatus::Active {
+                return Err(Error::RaceNotCancellable);
+            }
+
+            if pools.len() != race.field_size as usize {
+                return Err(Error::InvalidHorse { max: race.field_size - 1 });
+            }
+
+            if race.bet_mode != BetMode::Parimutuel {
+                return Err(Error::WrongBetMode);
+            }
+
+            if self.race_consolation.contains(race_id) {
+                return Err(Error::ConsolationUnavailable);
+            }
+
+            let mut deployments = self.mirror_deployments.get(race_id).unwrap_or_default();
+            if !deployments.contains(&deployment) {
+                if deployments.len() >= MAX_MIRRORS {
+                    return Err(Error::TooManyMirrors);
+                }
+                deployments.push(deployment);
+                self.mirror_deployments.insert(race_id, &deployments);
+            }
+
+            let total = pools.iter().sum();
+            self.mirror_pools.insert((race_id, deployment), &pools);
+
+            self.env().emit_event(MirrorPoolsReported {
+                race_id,
+                deployment,
+                total,
+            });
+
+            Ok(())
+        }
+
+        /// Settle the netting balance with a mirrored deployment. What this deployment
+        /// owes is transferred to the bridge; what it is owed must be sent with the call.
+        #[ink(message, payable)]
+        pub fn reconcile_mirror(&mut self, race_id: u8, deployment: u32) -> Result<(), Error> {
+            let bridge = self.ensure_bridge()?;
+
+            let amount = self
+                .mirror_settlements
+                .get((race_id, deployment))
+                .ok_or(Error::NothingToClaim)?;
+            let received = self.env().transferred_value();
+
+            if amount >= 0 {
+                if received != 0 {
+                    return Err(Error::InvalidBetAmount);
+                }
+            } else if received != amount.unsigned_abs() {
+                return Err(Error::InvalidBetAmount);
+            }
+
+            self.mirror_settlements.remove((race_id, deployment));
+
+            if amount > 0 && self.env().transfer(bridge, amount.unsigned_abs()).is_err() {
+                return Err(Error::TransferFailed);
+            }
+            self.ensure_solvent()?;
+
+            self.env().emit_event(MirrorReconciled {
+                race_id,
+                deployment,
+                amount,
+            });
+
+            Ok(())
+        }
+
+        /// Redeem loyalty points for stake on which the house pays the fee
+        #[ink(message)]
+        pub fn redeem_points(&mut self, points: u32) -> Result<Balance, Error> {
+            let account = self.env().caller();
+
+            if self.loyalty_point_value == 0 {
+                return Err(Error::RedemptionDisabled);
+            }
+
+            let mut loyalty = self.loyalty.get(account).unwrap_or_default();
+            if points == 0 || points > loyalty.points {
+                return Err(Error::InsufficientPoints);
+            }
+
+            let fee_free_stake = self.loyalty_point_value.saturating_mul(points as u128);
+            loyalty.points -= points;
+            loyalty.fee_free_stake = loyalty.fee_free_stake.saturating_add(fee_free_stake);
+            self.loyalty.insert(account, &loyalty);
+
+            self.env().emit_event(LoyaltyPointsRedeemed {
+                account,
+                points,
+                fee_free_stake,
+            });
+
+            Ok(fee_free_stake)
+        }
+
+        /// Switch a race between parimutuel and fixed-odds betting before it takes any bets.
+        /// A fixed-odds race hands its jackpot seed back, as the book keeps no pool to seed.
+        #[ink(message)]
+        pub fn set_bet_mode(&mut self, race_id: u8, mode: BetMode) -> Result<(), Error> {
+            let mut race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            self.ensure_race_operator(&race)?;
+
+            if race.status != RaceStatus::Pending {
+                return Err(Error::RaceAlreadyStarted);
+            }
+
+            // Boosts, mirrors and consolation tranches only make sense on a pool
+            if self.race_bettor_count.get(race_id).unwrap_or(0) > 0
+                || self.pool_boosts.contains(race_id)
+                || self.odds_reserves.contains(race_id)
+                || self.mirror_deployments.contains(race_id)
+                || self.race_consolation.contains(race_id)
+            {
+                return Err(Error::WrongBetMode);
+            }
+
+            if mode == BetMode::FixedOdds {
+                if let Some(seed) = self.race_jackpot_seed.take(race_id) {
+                    let total = self.total_pool.get(race_id).unwrap_or(0);
+                    self.total_pool.insert(race_id, &(total - seed));
+                    self.jackpot += seed;
+                }
+            }
+
+            race.bet_mode = mode;
+            self.races.insert(race_id, &race);
+
+            self.env().emit_event(BetModeSet { race_id, mode });
+
+            Ok(())
+        }
+
+        /// Post decimal odds in basis points for every horse of a fixed-odds race,
+        /// e.g. 35_000 pays 3.5 times the stake. Bets already placed keep their odds.
+        #[ink(message)]
+        pub fn set_fixed_odds(&mut self, race_id: u8, odds: Vec<u32>) -> Result<(), Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            self.ensure_race_operator(&race)?;
+
+            if race.bet_mode != BetMode::FixedOdds {
+                return Err(Error::WrongBetMode);
+            }
+
+            if race.status != RaceStatus::Pending {
+                return Err(Error::RaceAlreadyStarted);
+            }
+
+            if odds.len() != race.field_size as usize || odds.iter().any(|o| (*o as u128) < BPS_DENOMINATOR) {
+                return Err(Error::InvalidOdds);
+            }
+
+            for (horse_id, o) in odds.iter().enumerate() {
+                self.fixed_odds.insert((race_id, horse_id as u8), o);
+            }
+
+            self.env().emit_event(FixedOddsPosted { race_id, odds });
+
+            Ok(())
+        }
+
+        /// Add the transferred value to the reserve backing a fixed-odds race.
+        /// Whatever the book does not pay out goes back to the funder's deposit balance.
+        #[ink(message, payable)]
+        pub fn fund_odds_reserve(&mut self, race_id: u8) -> Result<Balance, Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            self.ensure_race_operator(&race)?;
+
+            if race.bet_mode != BetMode::FixedOdds {
+                return Err(Error::WrongBetMode);
+            }
+
+            if race.status != RaceStatus::Pending && race.status != RaceStatus::Active {
+                return Err(Error::BettingClosed {
+                    closes_at: race.settled_at,
+                });
+            }
+
+            let funder = self.env().caller();
+            let amount = self.env().transferred_value();
+            if amount == 0 {
+                return Err(Error::InvalidBetAmount);
+            }
+
+            // One funder per race so the leftover has a single owner
+            let reserve = match self.odds_reserves.get(race_id) {
+                Some((owner, _)) if owner != funder => return Err(Error::Unauthorized),
+                Some((_, reserve)) => reserve + amount,
+                None => amount,
+            };
+            self.odds_reserves.insert(race_id, &(funder, reserve));
+
+            self.env().emit_event(OddsReserveFunded {
+                race_id,
+                funder,
+                amount,
+                reserve,
+            });
+
+            Ok(reserve)
+        }
+
+        /// Propose a parameter change, open for votes for the configured voting period.
+        /// The proposal threshold is locked, pulled with `PSP22::transfer_from`, until voting ends.
+        #[ink(message)]
+        pub fn propose(&mut self, change: ParameterChange) -> Result<u32, Error> {
+            let config = self.governance.ok_or(Error::GovernanceNotEnabled)?;
+            let proposer = self.env().caller();
+
+            if self.governance_weight(config.token, proposer)? < config.proposal_threshold {
+                return Err(Error::BelowProposalThreshold);
+            }
+
+            Self::check_parameter(&change)?;
+
+            let proposal_id = self.next_proposal_id;
+            self.next_proposal_id += 1;
+
+            if config.proposal_threshold > 0 {
+                self.token_transfer_from(config.token, proposer, config.proposal_threshold)?;
+                self.governance_locks.insert((proposal_id, proposer), &config.proposal_threshold);
+            }
+
+            let end_block = self.env().block_number() + config.voting_period_blocks;
+            self.proposals.insert(proposal_id, &Proposal {
+                id: proposal_id,
+                proposer,
+                change: change.clone(),
+                end_block,
+                votes_for: 0,
+                votes_against: 0,
+                executed: false,
+            });
+
+            self.env().emit_event(ProposalCreated {
+                proposal_id,
+                proposer,
+                change,
+                end_block,
+            });
+
+            Ok(proposal_id)
+        }
+
+        /// Vote on an open proposal with `amount` of the governance token, pulled with
+        /// `PSP22::transfer_from` and locked until voting ends so it cannot vote twice
+        #[ink(message)]
+        pub fn vote(&mut self, proposal_id: u32, support: bool, amount: Balance) -> Result<Balance, Error> {
+            let config = self.governance.ok_or(Error::GovernanceNotEnabled)?;
+            let voter = self.env().caller();
+            let mut proposal = self.proposals.get(proposal_id).ok_or(Error::ProposalNotFound)?;
+
+            if self.env().block_number() >= proposal.end_block {
+                return Err(Error::VotingClosed);
+            }
+
+            if self.proposal_votes.contains((proposal_id, voter)) {
+                return Err(Error::AlreadyVoted);
+            }
+
+            let weight = amount;
+            if weight == 0 {
+                return Err(Error::NoVotingPower);
+            }
+
+            self.token_transfer_from(config.token, voter, weight)?;
+            let locked = self.governance_locks.get((proposal_id, voter)).unwrap_or(0);
+            self.governance_locks.insert((proposal_id, voter), &(locked + weight));
+
+            if support {
+                proposal.votes_for += weight;
+            } else {
+                proposal.votes_against += weight;
+            }
+            self.proposals.insert(proposal_id, &proposal);
+            self.proposal_votes.insert((proposal_id, voter), &support);
+
+            self.env().emit_event(VoteCast {
+                proposal_id,
+                voter,
+                support,
+                weight,
+            });
+
+            Ok(weight)
+        }
+
+        /// Close a proposal once voting has ended, applying its change if it passed.
+        /// Anyone can execute a proposal.
+        #[ink(message)]
+        pub fn execute_proposal(&mut self, proposal_id: u32) -> Result<bool, Error> {
+            let config = self.governance.ok_or(Error::GovernanceNotEnabled)?;
+            let mut proposal = self.proposals.get(proposal_id).ok_or(Error::ProposalNotFound)?;
+
+            if self.env().block_number() < proposal.end_block {
+                return Err(Error::VotingOpen);
+            }
+
+            if proposal.executed {
+                return Err(Error::AlreadyClaimed);
+            }
+
+            proposal.executed = true;
+            self.proposals.insert(proposal_id, &proposal);
+
+            let passed = proposal.votes_for > proposal.votes_against && proposal.votes_for >= config.quorum;
+            if passed {
+                self.apply_parameter(proposal.change)?;
+            }
+
+            self.env().emit_event(ProposalExecuted { proposal_id, passed });
+
+            Ok(passed)
+        }
+
+        /// Return the governance tokens the caller locked on a proposal once its voting has ended
+        #[ink(message)]
+        pub fn withdraw_governance_tokens(&mut self, proposal_id: u32) -> Result<Balance, Error> {
+            let config = self.governance.ok_or(Error::GovernanceNotEnabled)?;
+            let account = self.env().caller();
+            let proposal = self.proposals.get(proposal_id).ok_or(Error::ProposalNotFound)?;
+
+            if self.env().block_number() < proposal.end_block {
+                return Err(Error::VotingOpen);
+            }
+
+            let amount = self.governance_locks.take((proposal_id, account)).ok_or(Error::NothingToClaim)?;
+            self.token_transfer(config.token, account, amount)?;
+
+            self.env().emit_event(GovernanceTokensWithdrawn {
+                proposal_id,
+                account,
+                amount,
+            });
+
+            Ok(amount)
+        }
+
+        /// Get the governance tokens `account` has locked on a proposal
+        #[ink(message)]
+        pub fn get_governance_lock(&self, proposal_id: u32, account: AccountId) -> Balance {
+            self.governance_locks.get((proposal_id, account)).unwrap_or(0)
+        }
+
+        /// Lock `amount` of the staking token, pulled with `PSP22::transfer_from`.
+        /// New stake shares in fees from the next epoch on.
+        #[ink(message)]
+        pub fn stake(&mut self, amount: Balance) -> Result<(), Error> {
+            let config = self.staking.ok_or(Error::StakingNotEnabled)?;
+            let staker = self.env().caller();
+
+            if amount == 0 {
+                return Err(Error::InvalidBetAmount);
+            }
+
+            self.token_transfer_from(config.token, staker, amount)?;
+            let active_from_epoch = self.add_stake(staker, amount);
+
+            self.env().emit_event(Staked {
+                staker,
+                amount,
+                active_from_epoch,
+            });
+
+            Ok(())
+        }
+
+        /// Withdraw staked tokens, pending stake first. Stake withdrawn before an epoch
+        /// closes takes no share of that epoch's fees.
+        #[ink(message)]
+        pub fn unstake(&mut self, amount: Balance) -> Result<(), Error> {
+            let config = self.staking.ok_or(Error::StakingNotEnabled)?;
+            let staker = self.env().caller();
+
+            self.remove_stake(staker, amount)?;
+            self.token_transfer(config.token, staker, amount)?;
+
+            self.env().emit_event(Unstaked { staker, amount });
+
+            Ok(())
+        }
+
+        /// Claim the caller's share of the fees of every closed epoch
+        #[ink(message)]
+        pub fn claim_rewards(&mut self) -> Result<Balance, Error> {
+            let staker = self.env().caller();
+            let mut stake = self.sync_stake(staker);
+
+            let amount = core::mem::take(&mut stake.rewards);
+            if amount == 0 {
+                return Err(Error::NothingToClaim);
+            }
+
+            self.stakes.insert(staker, &stake);
+            self.staking_rewards_owed = self.staking_rewards_owed.saturating_sub(amount);
+
+            if self.env().transfer(staker, amount).is_err() {
+                return Err(Error::TransferFailed);
+            }
+            self.ensure_solvent()?;
+
+            self.env().emit_event(StakingRewardsClaimed { staker, amount });
+
+            Ok(amount)
+        }
+
+        /// Move the winnings left unclaimed on a race whose claim window has expired to the
+        /// treasury or jackpot. Claims on the race are closed from then on.
+        #[ink(message)]
+        pub fn sweep_expired(&mut self, race_id: u8) -> Result<Balance, Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            if race.status != RaceStatus::Finished {
+                return Err(Error::RaceNotFinished);
+            }
+
+            if self.ensure_claim_open(&race).is_ok() {
+                return Err(Error::ClaimWindowOpen);
+            }
+
+            let amount = self.race_unclaimed.take(race_id).unwrap_or(0);
+            if amount == 0 {
+                return Err(Error::NothingToClaim);
+            }
+
+            self.unclaimed_winnings = self.unclaimed_winnings.saturating_sub(amount);
+            self.race_swept.insert(race_id, &amount);
+            self.pool_winnings_unpaid.remove(race_id);
+            match self.sweep_destination {
+                SweepDestination::Treasury => self.accrued_fees += amount,
+                SweepDestination::Jackpot => self.jackpot += amount,
+            }
+
+            self.env().emit_event(ExpiredWinningsSwept {
+                race_id,
+                amount,
+                destination: self.sweep_destination,
+            });
+
+            Ok(amount)
+        }
+
+        /// Cancel a race that has not finished yet, enabling refunds
+        #[ink(message)]
+        pub fn cancel_race(&mut self, race_id: u8) -> Result<(), Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            self.ensure_race_operator(&race)?;
+
+            if race.status != RaceStatus::Pending && race.status != RaceStatus::Active {
+                return Err(Error::RaceNotCancellable);
+            }
+
+            self.cancel(race);
+
+            Ok(())
+        }
+
+        /// Settle a race nobody has driven to the finish, once it is `FORCE_SETTLE_TIMEOUT_BLOCKS`
+        /// past its scheduled end. The race finishes from the current positions, or is
+        /// cancelled for refunds when no horse has moved. Anyone can call this.
+        #[ink(message)]
+        pub fn force_settle(&mut self, race_id: u8) -> Result<(), Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            if race.status != RaceStatus::Active {
+                return Err(Error::RaceNotActive);
+            }
+
+            let deadline = race.start_block + race.duration_blocks + FORCE_SETTLE_TIMEOUT_BLOCKS;
+            if self.env().block_number() < deadline {
+                return Err(Error::SettleTimeoutPending);
+            }
+
+            let moved = self.load_horses(race_id).iter().any(|horse| horse.position > 0);
+            if moved {
+                self.finish_race(race_id)?;
+            } else {
+                self.cancel(race);
+            }
+
+            self.env().emit_event(RaceForceSettled {
+                race_id,
+                cancelled: !moved,
+            });
+
+            Ok(())
+        }
+
+        /// Refund all bets placed on a cancelled race.
+        /// Pool boosts are not refundable.
+        #[ink(message)]
+        pub fn claim_refund(&mut self, race_id: u8) -> Result<Balance, Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            if race.status != RaceStatus::Cancelled {
+                return Err(Error::RaceNotCancelled);
+            }
+
+            let caller = self.env().caller();
+
+            // Tickets on scratched horses may already have been refunded and burned
+            let held = self.held_tickets(caller, race_id);
+            let refund: Balance = held.iter().map(|(_, _, bet)| bet.amount).sum();
+            let asset_refunded = self.refund_asset_bets(caller, race_id)?;
+
+            if refund == 0 {
+                if asset_refunded {
+                    return Ok(0);
+                }
+                if self.payouts_claimed.get((caller, race_id)).unwrap_or(false) {
+                    return Err(Error::AlreadyClaimed);
+                }
+                return Err(Error::NothingToRefund);
+            }
+
+            for (_, _, bet) in held {
+                self.burn_ticket(caller, bet.id);
+            }
+            self.acquired_tickets.remove((caller, race_id));
+
+            // Mark as claimed
+            self.payouts_claimed.insert((caller, race_id), &true);
+            self.day_totals.refunds += refund;
+            self.record_returned(caller, race.season, refund);
+            self.record_settled_stake(race_id, refund);
+
+            if self.env().transfer(caller, refund).is_err() {
+                return Err(Error::TransferFailed);
+            }
+            self.ensure_solvent()?;
+
+            self.env().emit_event(BetRefunded {
+                bettor: caller,
+                race_id,
+                amount: refund,
+            });
+
+            Ok(refund)
+        }
+
+        /// Place an integrity hold on a horse, blocking new bets on it.
+        /// A hold still standing when the race starts scratches the horse.
+        #[ink(message)]
+        pub fn hold_horse(&mut self, race_id: u8, horse_id: u8, reason: HoldReason) -> Result<(), Error> {
+            self.ensure_role(Role::RaceManager)?;
+
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            if race.status != RaceStatus::Pending {
+                return Err(Error::RaceAlreadyStarted);
+            }
+
+            if horse_id >= race.field_size {
+                return Err(Error::InvalidHorse { max: race.field_size - 1 });
+            }
+
+            self.horse_holds.insert((race_id, horse_id), &reason);
+
+            self.env().emit_event(HorseHeld {
+                race_id,
+                horse_id,
+                reason,
+            });
+
+            Ok(())
+        }
+
+        /// Release an integrity hold, reopening betting on the horse
+        #[ink(message)]
+        pub fn release_horse(&mut self, race_id: u8, horse_id: u8) -> Result<(), Error> {
+            self.ensure_role(Role::RaceManager)?;
+
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            if race.status != RaceStatus::Pending {
+                return Err(Error::RaceAlreadyStarted);
+            }
+
+            if self.horse_holds.take((race_id, horse_id)).is_none() {
+                return Err(Error::HorseNotOnHold);
+            }
+
+            self.env().emit_event(HorseReleased { race_id, horse_id });
+
+            Ok(())
+        }
+
+        /// Scratch a horse before the race starts; bets on it become refundable
+        #[ink(message)]
+        pub fn scratch_horse(&mut self, race_id: u8, horse_id: u8) -> Result<(), Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            self.ensure_race_operator(&race)?;
+
+            if race.status != RaceStatus::Pending {
+                return Err(Error::RaceAlreadyStarted);
+            }
+
+            if horse_id >= race.field_size {
+                return Err(Error::InvalidHorse { max: race.field_size - 1 });
+            }
+
+            if self.is_scratched(race_id, horse_id) {
+                return Err(Error::HorseNotRunning);
+            }
+
+            self.scratch(race_id, horse_id)
+        }
+
+        /// Refund bets placed on horses scratched from a race
+        #[ink(message)]
+        pub fn claim_scratch_refund(&mut self, race_id: u8) -> Result<Balance, Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            let caller = self.env().caller();
+            let mut refund = 0u128;
+
+            let horses = self.load_horses(race_id);
+            for (placed_by, _, bet) in self.held_tickets(caller, race_id) {
+                if Self::scratched_in(&horses, bet.horse_id) {
+                    refund += bet.amount;
+                    self.burn_ticket(caller, bet.id);
+                    // Commissions are fixed once the race settles
+                    if race.status == RaceStatus::Pending || race.status == RaceStatus::Active {
+                        if let Some(referrer) = self.referrers.get(placed_by) {
+                            self.add_referred_handle(referrer, race_id, -(bet.amount as i128));
+                        }
+                    }
+                }
+            }
+
+            if refund == 0 {
+                if self.scratch_refunds_claimed.get((caller, race_id)).unwrap_or(false) {
+                    return Err(Error::AlreadyClaimed);
+                }
+                return Err(Error::NothingToRefund);
+            }
+
+            // Mark as claimed
+            self.scratch_refunds_claimed.insert((caller, race_id), &true);
+            let unrefunded = self.scratch_unrefunded.get(race_id).unwrap_or(0);
+            self.scratch_unrefunded.insert(race_id, &unrefunded.saturating_sub(refund));
+            self.day_totals.refunds += refund;
+            self.record_returned(caller, race.season, refund);
+            self.record_settled_stake(race_id, refund);
+            self.sweep_rounding_dust(&race);
+
+            if self.env().transfer(caller, refund).is_err() {
+                return Err(Error::TransferFailed);
+            }
+            self.ensure_solvent()?;
+
+            self.env().emit_event(BetRefunded {
+                bettor: caller,
+                race_id,
+                amount: refund,
+            });
+
+            Ok(refund)
+        }
+
+        /// Grant a role to an account, replacing any role it already holds
+        #[ink(message)]
+        pub fn grant_role(&mut self, account: AccountId, role: Role) -> Result<(), Error> {
+            self.ensure_owner()?;
+
+            if account == self.owner {
+                return Err(Error::CannotChangeOwnerRole);
+            }
+
+            self.roles.insert(account, &role);
+
+            self.env().emit_event(RoleGranted { account, role });
+
+            Ok(())
+        }
+
+        /// Revoke the role held by an account
+        #[ink(message)]
+        pub fn revoke_role(&mut self, account: AccountId) -> Result<(), Error> {
+            self.ensure_owner()?;
+
+            if account == self.owner {
+                return Err(Error::CannotChangeOwnerRole);
+            }
+
+            let role = self.roles.take(account).ok_or(Error::RoleNotFound)?;
+
+            self.env().emit_event(RoleRevoked { account, role });
+
+            Ok(())
+        }
+
+        /// Start a new season, resetting every account's loss cap tally
+        #[ink(message)]
+        pub fn start_season(&mut self) -> Result<u32, Error> {
+            self.ensure_role(Role::RaceManager)?;
+
+            self.current_season += 1;
+
+            self.env().emit_event(SeasonStarted {
+                season: self.current_season,
+            });
+
+            Ok(self.current_season)
+        }
+
+        /// Set (or remove, with `None`) the caller's per-season loss cap.
+        /// Lowering a cap applies immediately; raising or removing it applies next season.
+        #[ink(message)]
+        pub fn set_loss_cap(&mut self, cap: Option<Balance>) -> Result<(), Error> {
+            let account = self.env().caller();
+            let current = self.get_loss_cap(account);
+
+            let effective_season = match cap {
+                Some(new) if current.is_none_or(|old| new <= old) => {
+                    self.loss_caps.insert(account, &new);
+                    self.pending_loss_caps.remove(account);
+                    self.current_season
+                }
+                _ => {
+                    let next_season = self.current_season + 1;
+                    self.pending_loss_caps.insert(account, &(cap, next_season));
+                    next_season
+                }
+            };
+
+            self.env().emit_event(LossCapUpdated {
+                account,
+                cap,
+                effective_season,
+            });
+
+            Ok(())
+        }
+
+        /// Publish calibrated win probabilities (basis points per horse, summing to 10000)
+        #[ink(message)]
+        pub fn set_win_probabilities(&mut self, race_id: u8, probabilities: Vec<u16>) -> Result<(), Error> {
+            self.ensure_role(Role::RaceManager)?;
+
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            if race.status != RaceStatus::Pending {
+                return Err(Error::RaceAlreadyStarted);
+            }
+
+            let total: u128 = probabilities.iter().map(|p| *p as u128).sum();
+            if probabilities.len() != race.field_size as usize || total != BPS_DENOMINATOR {
+                return Err(Error::InvalidProbabilities);
+            }
+
+            self.published_probabilities.insert(race_id, &probabilities);
+
+            Ok(())
+        }
+
+        /// Set the divergence between published and pool odds that gets flagged
+        #[ink(message)]
+        pub fn set_divergence_threshold(&mut self, threshold_bps: u16) -> Result<(), Error> {
+            self.ensure_role(Role::RaceManager)?;
+
+            if threshold_bps as u128 > BPS_DENOMINATOR {
+                return Err(Error::InvalidProbabilities);
+            }
+
+            self.divergence_threshold_bps = threshold_bps;
+
+            Ok(())
+        }
+
+        /// Configure steam detection: a pool share move of at least `threshold_bps`
+        /// within `window_blocks` emits `SteamMove`. A zero threshold disables it.
+        #[ink(message)]
+        pub fn set_steam_detection(&mut self, threshold_bps: u16, window_blocks: u32) -> Result<(), Error> {
+            self.ensure_role(Role::RaceManager)?;
+
+            if threshold_bps as u128 > BPS_DENOMINATOR || window_blocks == 0 {
+                return Err(Error::InvalidSchedule);
+            }
+
+            self.steam_threshold_bps = threshold_bps;
+            self.steam_window_blocks = window_blocks;
+
+            Ok(())
+        }
+
+        /// Set the staked pool a race needs at its start; zero disables the requirement
+        #[ink(message)]
+        pub fn set_min_pool(&mut self, min_pool: Balance) -> Result<(), Error> {
+            self.ensure_role(Role::RaceManager)?;
+            self.min_pool = min_pool;
+            Ok(())
+        }
+
+        /// Set the account allowed to report and reconcile mirrored deployment pools
+        #[ink(message)]
+        pub fn set_bridge(&mut self, bridge: Option<AccountId>) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.bridge = bridge;
+            Ok(())
+        }
+
+        /// Set the fee-free stake granted per redeemed loyalty point
+        #[ink(message)]
+        pub fn set_loyalty_point_value(&mut self, value: Balance) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.loyalty_point_value = value;
+            Ok(())
+        }
+
+        /// Approve a community organizer with a share of the house fee on their races
+        #[ink(message)]
+        pub fn approve_organizer(&mut self, organizer: AccountId, fee_share_bps: u16) -> Result<(), Error> {
+            self.ensure_owner()?;
+
+            let max_bps = self.organizer_guardrails.max_fee_share_bps;
+            if fee_share_bps > max_bps {
+                return Err(Error::FeeTooHigh { max_bps });
+            }
+
+            let mut account = self.organizers.get(organizer).unwrap_or_default();
+            account.active = true;
+            account.fee_share_bps = fee_share_bps;
+            self.organizers.insert(organizer, &account);
+
+            self.env().emit_event(OrganizerApproved {
+                organizer,
+                fee_share_bps,
+            });
+
+            Ok(())
+        }
+
+        /// Revoke an organizer, returning their bond minus `slash`
+        #[ink(message)]
+        pub fn revoke_organizer(&mut self, organizer: AccountId, slash: Balance) -> Result<(), Error> {
+            self.ensure_owner()?;
+
+            let mut account = self.organizers.get(organizer).ok_or(Error::OrganizerNotFound)?;
+
+            if slash > account.bond {
+                return Err(Error::InsufficientBond);
+            }
+
+            let refund = account.bond - slash;
+            self.organizer_bonds -= account.bond;
+            account.active = false;
+            account.bond = 0;
+            self.organizers.insert(organizer, &account);
+            self.accrued_fees += slash;
+
+            if refund > 0 && self.env().transfer(organizer, refund).is_err() {
+                return Err(Error::TransferFailed);
+            }
+            self.ensure_solvent()?;
+
+            self.env().emit_event(OrganizerRevoked {
+                organizer,
+                slashed: slash,
+            });
+
+            Ok(())
+        }
+
+        /// Set the limits community organizers operate within
+        #[ink(message)]
+        pub fn set_organizer_guardrails(&mut self, guardrails: OrganizerGuardrails) -> Result<(), Error> {
+            self.ensure_owner()?;
+
+            if guardrails.max_fee_share_bps as u128 > BPS_DENOMINATOR {
+                return Err(Error::FeeTooHigh {
+                    max_bps: BPS_DENOMINATOR as u16,
+                });
+            }
+
+            self.organizer_guardrails = guardrails;
+
+            Ok(())
+        }
+
+        /// Add to the caller's organizer bond
+        #[ink(message, payable)]
+        pub fn post_organizer_bond(&mut self) -> Result<(), Error> {
+            let organizer = self.env().caller();
+            let mut account = self.organizers.get(organizer).ok_or(Error::OrganizerNotFound)?;
+
+            if !account.active {
+                return Err(Error::Unauthorized);
+            }
+
+            account.bond += self.env().transferred_value();
+            self.organizers.insert(organizer, &account);
+            self.organizer_bonds += self.env().transferred_value();
+
+            self.env().emit_event(OrganizerBonded {
+                organizer,
+                bond: account.bond,
+            });
+
+            Ok(())
+        }
+
+        /// Step down as an organizer and reclaim the bond once no races are open
+        #[ink(message)]
+        pub fn resign_organizer(&mut self) -> Result<Balance, Error> {
+            let organizer = self.env().caller();
+            let mut account = self.organizers.get(organizer).ok_or(Error::OrganizerNotFound)?;
+
+            if account.open_races > 0 {
+                return Err(Error::OrganizerHasOpenRaces);
+            }
+
+            let refund = account.bond;
+            account.active = false;
+            account.bond = 0;
+            self.organizers.insert(organizer, &account);
+            self.organizer_bonds -= refund;
+
+            if refund > 0 && self.env().transfer(organizer, refund).is_err() {
+                return Err(Error::TransferFailed);
+            }
+            self.ensure_solvent()?;
+
+            self.env().emit_event(OrganizerRevoked {
+                organizer,
+                slashed: 0,
+            });
+
+            Ok(refund)
+        }
+
+        /// Withdraw the caller's earned organizer fees
+        #[ink(message)]
+        pub fn withdraw_organizer_fees(&mut self) -> Result<Balance, Error> {
+            let organizer = self.env().caller();
+            let mut account = self.organizers.get(organizer).ok_or(Error::OrganizerNotFound)?;
+
+            let amount = account.fees_earned - account.fees_withdrawn;
+            if amount == 0 {
+                return Err(Error::InsufficientFees);
+            }
+
+            account.fees_withdrawn = account.fees_earned;
+            self.organizers.insert(organizer, &account);
+            self.organizer_fees_owed -= amount;
+
+            if self.env().transfer(organizer, amount).is_err() {
+                return Err(Error::TransferFailed);
+            }
+            self.ensure_solvent()?;
+
+            self.env().emit_event(OrganizerFeesWithdrawn { organizer, amount });
+
+            Ok(amount)
+        }
+
+        /// Clear storage of a settled race once every payout and refund is claimed.
+        /// Bettor records are removed in batches; returns true once archiving completes.
+        #[ink(message)]
+        pub fn archive_race(&mut self, race_id: u8) -> Result<bool, Error> {
+            self.ensure_owner()?;
+
+            let mut race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            if race.status != RaceStatus::Finished && race.status != RaceStatus::Cancelled {
+                return Err(Error::RaceNotSettled);
+            }
+
+            let archivable_at = race.settled_at + ARCHIVE_GRACE_BLOCKS;
+            if self.env().block_number() < archivable_at {
+                return Err(Error::ArchiveGracePeriod { archivable_at });
+            }
+
+            // Once expired winnings are swept nothing more is owed on the race
+            let settled = self.settled_stake.get(race_id).unwrap_or(0);
+            if !self.race_swept.contains(race_id) && settled < self.owed_stake(&race) {
+                return Err(Error::UnclaimedPayouts);
+            }
+            // Asset claims on a finished race close with its claim window; refunds never do
+            let asset_claims_open = race.status == RaceStatus::Cancelled || self.ensure_claim_open(&race).is_ok();
+            if asset_claims_open && self.asset_stake_unsettled(&race) {
+                return Err(Error::UnclaimedPayouts);
+            }
+
+            // Clear a batch of per-bettor records
+            let bettors = self.race_bettor_count.get(race_id).unwrap_or(0);
+            let cursor = self.archive_cursor.get(race_id).unwrap_or(0);
+            let end = bettors.min(cursor + MAX_ARCHIVE_BATCH);
+
+            for index in cursor..end {
+                if let Some(bettor) = self.race_bettors.take((race_id, index)) {
+                    let count = self.bet_count.take((bettor, race_id)).unwrap_or(0);
+                    for i in 0..count {
+                        if let Some(bet) = self.bets.take((bettor, race_id, i)) {
+                            self.bets_by_id.remove(bet.id);
+                            // Tickets still outstanding on the race are burned with it
+                            if let Some(holder) = self.ticket_holder(&bet) {
+                                self.burn_ticket(holder, bet.id);
+                                self.acquired_tickets.remove((holder, race_id));
+                            }
+                            self.burned_tickets.remove(bet.id);
+                            self.ticket_approvals.remove(bet.id);
+                        }
+                    }
+                    self.payouts_claimed.remove((bettor, race_id));
+                    self.scratch_refunds_claimed.remove((bettor, race_id));
+                }
+            }
+
+            if end < bettors {
+                self.archive_cursor.insert(race_id, &end);
+                return Ok(false);
+            }
+
+            // All bettors cleared, drop the race-level entries
+            self.race_horses.remove(race_id);
+            for i in 0..race.field_size {
+                self.horses.remove((race_id, i));
+                self.horse_pools.remove((race_id, i));
+                self.horse_holds.remove((race_id, i));
+                self.horse_metadata.remove((race_id, i));
+                self.steam_references.remove((race_id, i));
+            }
+            self.total_pool.remove(race_id);
+            self.pool_boosts.remove(race_id);
+            self.race_jackpot_seed.remove(race_id);
+            self.published_probabilities.remove(race_id);
+            self.race_bettor_count.remove(race_id);
+            self.settled_stake.remove(race_id);
+            self.scratch_unrefunded.remove(race_id);
+            self.cancel_unrefunded.remove(race_id);
+            self.archive_cursor.remove(race_id);
+
+            race.status = RaceStatus::Archived;
+            self.races.insert(race_id, &race);
+
+            self.env().emit_event(RaceArchived {
+                race_id,
+                bettors_cleared: bettors,
+            });
+
+            Ok(true)
+        }
+
+        /// Configure how many bets an account may place per race and per block window
+        #[ink(message)]
+        pub fn set_bet_rate_limits(&mut self, limits: BetRateLimits) -> Result<(), Error> {
+            self.ensure_owner()?;
+
+            if limits.max_bets_per_window > 0 && limits.window_blocks == 0 {
+                return Err(Error::InvalidSchedule);
+            }
+
+            self.bet_rate_limits = limits;
+            Ok(())
+        }
+
+        /// Configure the bet size ramp applied to new accounts
+        #[ink(message)]
+        pub fn set_new_account_limits(&mut self, limits: NewAccountLimits) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.ensure_ungoverned()?;
+            self.apply_parameter(ParameterChange::NewAccountLimits(limits))
+        }
+
+        /// Set the most a parlay placed from now on can pay, as a multiple of its stake
+        #[ink(message)]
+        pub fn set_parlay_max_multiple(&mut self, multiple: u32) -> Result<(), Error> {
+            self.ensure_owner()?;
+
+            if multiple == 0 {
+                return Err(Error::InvalidParlay);
+            }
+
+            self.parlay_max_multiple = multiple;
+
+            Ok(())
+        }
+
+        /// Set the share of the house fee paid to referrers on referred bets
+        #[ink(message)]
+        pub fn set_referral_share(&mut self, referral_bps: u16) -> Result<(), Error> {
+            self.ensure_owner()?;
+
+            if referral_bps > MAX_REFERRAL_BPS {
+                return Err(Error::FeeTooHigh {
+                    max_bps: MAX_REFERRAL_BPS,
+                });
+            }
+
+            self.referral_bps = referral_bps;
+
+            Ok(())
+        }
+
+        /// Set how far ahead new races are scheduled and how early betting closes
+        #[ink(message)]
+        pub fn set_betting_schedule(&mut self, race_lead_blocks: u32, betting_cutoff_blocks: u32) -> Result<(), Error> {
+            self.ensure_role(Role::RaceManager)?;
+
+            if betting_cutoff_blocks >= race_lead_blocks {
+                return Err(Error::InvalidSchedule);
+            }
+
+            self.race_lead_blocks = race_lead_blocks;
+            self.betting_cutoff_blocks = betting_cutoff_blocks;
+
+            Ok(())
+        }
+
+        /// Group races into a card that can be bet in a single call
+        #[ink(message)]
+        pub fn create_card(&mut self, races: Vec<u8>) -> Result<u32, Error> {
+            self.ensure_role(Role::RaceManager)?;
+
+            if races.is_empty() || races.len() > TOTAL_RACES as usize {
+                return Err(Error::InvalidCard);
+            }
+
+            for (index, race_id) in races.iter().enumerate() {
+                if !self.races.contains(*race_id) || races[..index].contains(race_id) {
+                    return Err(Error::InvalidCard);
+                }
+            }
+
+            let card_id = self.next_card_id;
+            self.cards.insert(card_id, &races);
+            self.next_card_id += 1;
+
+            self.env().emit_event(CardCreated { card_id, races });
+
+            Ok(card_id)
+        }
+
+        /// Register the owner of a horse entered in a race
+        #[ink(message)]
+        pub fn set_horse_owner(&mut self, race_id: u8, horse_id: u8, owner: AccountId) -> Result<(), Error> {
+            self.ensure_role(Role::RaceManager)?;
+
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            if race.status != RaceStatus::Pending {
+                return Err(Error::RaceAlreadyStarted);
+            }
+
+            if horse_id >= race.field_size {
+                return Err(Error::InvalidHorse { max: race.field_size - 1 });
+            }
+
+            self.horse_owners.insert((race_id, horse_id), &owner);
+
+            Ok(())
+        }
+
+        /// Add the transferred value to a race's purse
+        #[ink(message, payable)]
+        pub fn fund_purse(&mut self, race_id: u8) -> Result<(), Error> {
+            self.ensure_role(Role::RaceManager)?;
+
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            if race.status != RaceStatus::Pending && race.status != RaceStatus::Active {
+                return Err(Error::RaceNotCancellable);
+            }
+
+            let amount = self.env().transferred_value();
+            if amount == 0 {
+                return Err(Error::InvalidBetAmount);
+            }
+
+            let purse = self.race_purses.get(race_id).unwrap_or(0) + amount;
+            self.race_purses.insert(race_id, &purse);
+
+            self.env().emit_event(PurseFunded { race_id, amount, purse });
+
+            Ok(())
+        }
+
+        /// Set the vesting schedule for purses of races finishing from now on
+        #[ink(message)]
+        pub fn set_purse_vesting(&mut self, vesting: PurseVesting) -> Result<(), Error> {
+            self.ensure_owner()?;
+
+            if vesting.cliff_blocks > vesting.duration_blocks {
+                return Err(Error::InvalidSchedule);
+            }
+
+            self.purse_vesting = vesting;
+
+            Ok(())
+        }
+
+        /// Cap the pool each horse, and the race as a whole, can take in bets.
+        /// Caps below the current pools stop further bets without touching existing ones.
+        #[ink(message)]
+        pub fn set_pool_caps(&mut self, race_id: u8, caps: PoolCaps) -> Result<(), Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            self.ensure_race_operator(&race)?;
+
+            if race.status != RaceStatus::Pending {
+                return Err(Error::RaceAlreadyStarted);
+            }
+
+            self.pool_caps.insert(race_id, &caps);
+
+            self.env().emit_event(PoolCapsSet { race_id, caps });
+
+            Ok(())
+        }
+
+        /// Cap what a winning ticket on a race may return at `multiple_bps` of its stake,
+        /// or lift the cap with None. Parimutuel winnings above the cap roll into the jackpot;
+        /// fixed-odds bets lock the capped odds, so the cap must precede the first locked bet.
+        #[ink(message)]
+        pub fn set_payout_cap(&mut self, race_id: u8, multiple_bps: Option<u32>) -> Result<(), Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            self.ensure_race_operator(&race)?;
+
+            if race.status != RaceStatus::Pending {
+                return Err(Error::RaceAlreadyStarted);
+            }
+
+            if multiple_bps.is_some_and(|multiple| (multiple as u128) < BPS_DENOMINATOR) {
+                return Err(Error::InvalidPayoutCap);
+            }
+
+            if race.bet_mode == BetMode::FixedOdds
+                && (0..self.field_size(race_id)).any(|horse_id| self.fixed_liabilities.contains((race_id, horse_id)))
+            {
+                return Err(Error::InvalidPayoutCap);
+            }
+
+            match multiple_bps {
+                Some(multiple) => {
+                    self.payout_caps.insert(race_id, &multiple);
+                }
+                None => self.payout_caps.remove(race_id),
+            }
+
+            self.env().emit_event(PayoutCapSet { race_id, multiple_bps });
+
+            Ok(())
+        }
+
+        /// Have a race's result reported by an oracle contract instead of simulated,
+        /// for races run in the real world. `None` returns the race to the simulation.
+        #[ink(message)]
+        pub fn set_race_oracle(&mut self, race_id: u8, oracle: Option<AccountId>) -> Result<(), Error> {
+            let mut race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            self.ensure_race_operator(&race)?;
+
+            if race.status != RaceStatus::Pending {
+                return Err(Error::RaceAlreadyStarted);
+            }
+
+            race.oracle = oracle;
+            self.races.insert(race_id, &race);
+
+            self.env().emit_event(RaceOracleSet { race_id, oracle });
+
+            Ok(())
+        }
+
+        /// Report the finishing order of a started oracle race, settling it. Every running
+        /// horse must appear exactly once; the first is the winner.
+        #[ink(message)]
+        pub fn submit_result(&mut self, race_id: u8, rankings: Vec<u8>) -> Result<(), Error> {
+            let mut race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            let oracle = self.env().caller();
+
+            if race.oracle != Some(oracle) {
+                return Err(Error::Unauthorized);
+            }
+
+            if race.status != RaceStatus::Active {
+                return Err(Error::RaceNotActive);
+            }
+
+            let horses = self.load_horses(race_id);
+            let runners = (0..race.field_size).filter(|i| !Self::scratched_in(&horses, *i)).count();
+            if rankings.len() != runners {
+                return Err(Error::InvalidRankings);
+            }
+            for (index, horse_id) in rankings.iter().enumerate() {
+                let repeated = rankings[..index].contains(horse_id);
+                if *horse_id >= race.field_size || Self::scratched_in(&horses, *horse_id) || repeated {
+                    return Err(Error::InvalidRankings);
+                }
+            }
+
+            race.winners = Vec::from([rankings[0]]);
+            race.rankings = rankings.clone();
+
+            self.env().emit_event(ResultSubmitted {
+                race_id,
+                oracle,
+                rankings,
+            });
+
+            self.settle_race(race)
+        }
+
+        /// Choose the track condition of a race before it starts
+        #[ink(message)]
+        pub fn set_track_condition(&mut self, race_id: u8, condition: TrackCondition) -> Result<(), Error> {
+            let mut race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            self.ensure_race_operator(&race)?;
+
+            if race.status != RaceStatus::Pending {
+                return Err(Error::RaceAlreadyStarted);
+            }
+
+            race.track_condition = Some(condition);
+            self.races.insert(race_id, &race);
+
+            Ok(())
+        }
+
+        /// Freeze an account's new bets and withdrawals for `duration_blocks`.
+        /// Winnings and refunds on existing bets can still be claimed.
+        #[ink(message)]
+        pub fn freeze_account(&mut self, account: AccountId, reason: FreezeReason, duration_blocks: u32) -> Result<(), Error> {
+            self.ensure_role(Role::Integrity)?;
+
+            if duration_blocks == 0 || duration_blocks > MAX_FREEZE_BLOCKS {
+                return Err(Error::InvalidSchedule);
+            }
+
+            let now = self.env().block_number();
+            let expires_at = now + duration_blocks;
+            self.frozen_accounts.insert(account, &AccountFreeze {
+                reason,
+                frozen_at: now,
+                expires_at,
+            });
+
+            self.env().emit_event(AccountFrozen {
+                account,
+                reason,
+                expires_at,
+            });
+
+            Ok(())
+        }
+
+        /// Lift an account freeze before it expires
+        #[ink(message)]
+        pub fn unfreeze_account(&mut self, account: AccountId) -> Result<(), Error> {
+            self.ensure_role(Role::Integrity)?;
+
+            if self.frozen_accounts.take(account).is_none() {
+                return Err(Error::AccountNotFrozen);
+            }
+
+            self.env().emit_event(AccountUnfrozen { account });
+
+            Ok(())
+        }
+
+        /// Restrict an account from placing new bets until it is unrestricted.
+        /// Winnings and refunds on existing bets can still be claimed.
+        #[ink(message)]
+        pub fn restrict_account(&mut self, account: AccountId) -> Result<(), Error> {
+            self.ensure_role(Role::Treasurer)?;
+
+            if self.restricted_accounts.contains(account) {
+                return Err(Error::AccountRestricted);
+            }
+            self.restricted_accounts.insert(account, &());
+
+            self.env().emit_event(AccountRestricted { account });
+
+            Ok(())
+        }
+
+        /// Lift an account restriction
+        #[ink(message)]
+        pub fn unrestrict_account(&mut self, account: AccountId) -> Result<(), Error> {
+            self.ensure_role(Role::Treasurer)?;
+
+            if self.restricted_accounts.take(account).is_none() {
+                return Err(Error::AccountNotRestricted);
+            }
+
+            self.env().emit_event(AccountUnrestricted { account });
+
+            Ok(())
+        }
+
+        /// Set the display name and metadata URI of a horse in a race
+        #[ink(message)]
+        pub fn set_horse_metadata(&mut self, race_id: u8, horse_id: u8, name: String, metadata_uri: String) -> Result<(), Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            self.ensure_race_operator(&race)?;
+
+            if race.status == RaceStatus::Archived {
+                return Err(Error::RaceNotFound);
+            }
+
+            if horse_id >= race.field_size {
+                return Err(Error::InvalidHorse { max: race.field_size - 1 });
+            }
+
+            if name.len() > MAX_HORSE_NAME_LEN || metadata_uri.len() > MAX_METADATA_URI_LEN {
+                return Err(Error::MetadataTooLong);
+            }
+
+            self.horse_metadata.insert((race_id, horse_id), &HorseMetadata {
+                name: name.clone(),
+                metadata_uri: metadata_uri.clone(),
+            });
+
+            self.env().emit_event(HorseMetadataUpdated {
+                race_id,
+                horse_id,
+                name,
+                metadata_uri,
+            });
+
+            Ok(())
+        }
+
+        /// Start the timelock for an emergency withdrawal of unattributed funds
+        #[ink(message)]
+        pub fn initiate_emergency_withdraw(&mut self) -> Result<u32, Error> {
+            self.ensure_owner()?;
+            self.ensure_no_multisig()?;
+            self.start_emergency_withdraw()
+        }
+
+        /// Cancel a started emergency withdrawal
+        #[ink(message)]
+        pub fn cancel_emergency_withdraw(&mut self) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.ensure_no_multisig()?;
+            self.stop_emergency_withdraw()
+        }
+
+        /// Withdraw every fund not attributable to open races or owed to bettors,
+        /// once the emergency timelock has passed
+        #[ink(message)]
+        pub fn finalize_emergency_withdraw(&mut self, to: AccountId) -> Result<Balance, Error> {
+            self.ensure_owner()?;
+            self.ensure_no_multisig()?;
+            self.complete_emergency_withdraw(to)
+        }
+
+        /// Pay `share_bps` of a race's net pool to backers of the runner-up; zero turns it off
+        #[ink(message)]
+        pub fn set_consolation(&mut self, race_id: u8, share_bps: u16) -> Result<(), Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            self.ensure_race_operator(&race)?;
+
+            if race.status != RaceStatus::Pending {
+                return Err(Error::RaceAlreadyStarted);
+            }
+
+            if share_bps > MAX_CONSOLATION_BPS {
+                return Err(Error::FeeTooHigh {
+                    max_bps: MAX_CONSOLATION_BPS,
+                });
+            }
+
+            if race.bet_mode != BetMode::Parimutuel {
+                return Err(Error::WrongBetMode);
+            }
+
+            // Mirrored deployments are netted on the win pool only
+            if self.mirror_deployments.contains(race_id) {
+                return Err(Error::ConsolationUnavailable);
+            }
+
+            if share_bps == 0 {
+                self.race_consolation.remove(race_id);
+            } else {
+                self.race_consolation.insert(race_id, &share_bps);
+            }
+
+            self.env().emit_event(ConsolationSet { race_id, share_bps });
+
+            Ok(())
+        }
+
+        /// Register pending races into a tournament scored with `points` per finishing position
+        #[ink(message)]
+        pub fn create_tournament(&mut self, races: Vec<u8>, points: Vec<u32>) -> Result<u32, Error> {
+            self.ensure_owner()?;
+
+            if races.len() < 2 || races.len() > MAX_TOURNAMENT_RACES {
+                return Err(Error::InvalidTournament);
+            }
+
+            if points.is_empty() || points.len() > MAX_FIELD_SIZE as usize {
+                return Err(Error::InvalidTournament);
+            }
+
+            let tournament_id = self.next_tournament_id;
+            let mut field_size = 0;
+            for (index, race_id) in races.iter().enumerate() {
+                let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+                if race.status != RaceStatus::Pending {
+                    return Err(Error::RaceAlreadyStarted);
+                }
+                if races[..index].contains(race_id) || self.race_tournament.contains(race_id) {
+                    return Err(Error::InvalidTournament);
+                }
+                self.race_tournament.insert(race_id, &tournament_id);
+                field_size = field_size.max(race.field_size);
+            }
+
+            self.next_tournament_id += 1;
+            self.tournaments.insert(tournament_id, &Tournament {
+                id: tournament_id,
+                races: races.clone(),
+                points: points.clone(),
+                field_size,
+                total_pool: 0,
+                settled: false,
+                refunded: false,
+                champions: Vec::new(),
+                net_pool: 0,
+                winning_pool: 0,
+            });
+
+            self.env().emit_event(TournamentCreated {
+                tournament_id,
+                races,
+                points,
+            });
+
+            Ok(tournament_id)
+        }
+
+        /// Add a horse to the registry races draw their fields from
+        #[ink(message)]
+        pub fn register_horse(&mut self, name: String) -> Result<u32, Error> {
+            self.ensure_role(Role::RaceManager)?;
+
+            if name.len() > MAX_HORSE_NAME_LEN {
+                return Err(Error::MetadataTooLong);
+            }
+
+            let horse_id = self.horse_career_count;
+            self.horse_career_count += 1;
+            self.horse_careers.insert(horse_id, &HorseCareer {
+                id: horse_id,
+                name: name.clone(),
+                starts: 0,
+                wins: 0,
+                places: 0,
+                experience: 0,
+                form: Vec::new(),
+            });
+
+            self.env().emit_event(HorseRegistered { horse_id, name });
+
+            Ok(horse_id)
+        }
+
+        /// Open the revenue-sharing vault on a PSP22 token, or adjust its fee share and
+        /// epoch length. The token cannot be changed once set.
+        #[ink(message)]
+        pub fn set_staking(&mut self, config: StakingConfig) -> Result<(), Error> {
+            self.ensure_owner()?;
+
+            if config.fee_share_bps as u128 > BPS_DENOMINATOR {
+                return Err(Error::FeeTooHigh {
+                    max_bps: BPS_DENOMINATOR as u16,
+                });
+            }
+
+            if config.epoch_blocks == 0 {
+                return Err(Error::InvalidSchedule);
+            }
+
+            match self.staking {
+                Some(current) if current.token != config.token => return Err(Error::Unauthorized),
+                Some(_) => {}
+                None => self.staking_epoch_start = self.env().block_number(),
+            }
+
+            self.staking = Some(config);
+
+            Ok(())
+        }
+
+        /// Set how long winners have to claim after a race finishes, and where expired
+        /// winnings are swept. A window of 0 lets winnings wait forever.
+        #[ink(message)]
+        pub fn set_claim_window(&mut self, blocks: u32, destination: SweepDestination) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.claim_window_blocks = blocks;
+            self.sweep_destination = destination;
+            Ok(())
+        }
+
+        /// Let bets and claims advance the running race as they go, so races keep moving
+        /// without a dedicated `update_race` keeper
+        #[ink(message)]
+        pub fn set_lazy_progression(&mut self, enabled: bool) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.lazy_progression = enabled;
+            Ok(())
+        }
+
+        /// Put house fee changes, upgrades and emergency withdrawals behind `threshold` of
+        /// `signers`. Once enabled the signer set can only be changed by a signed action.
+        #[ink(message)]
+        pub fn enable_multisig(&mut self, signers: Vec<AccountId>, threshold: u32) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.ensure_no_multisig()?;
+            self.set_signers(signers, threshold)
+        }
+
+        /// Queue a sensitive owner action, confirmed by the submitting signer
+        #[ink(message)]
+        pub fn submit_action(&mut self, action: OwnerAction) -> Result<u32, Error> {
+            let proposer = self.ensure_signer()?;
+
+            let action_id = self.next_action_id;
+            self.next_action_id += 1;
+            self.pending_actions.insert(action_id, &PendingAction {
+                action: action.clone(),
+                proposer,
+                confirmations: Vec::new(),
+                executed: false,
+            });
+
+            self.env().emit_event(ActionSubmitted {
+                action_id,
+                proposer,
+                action,
+            });
+
+            self.confirm_action(action_id)?;
+
+            Ok(action_id)
+        }
+
+        /// Confirm a queued action, executing it once enough current signers have confirmed.
+        /// If execution fails the confirmation is not recorded and can be given again later.
+        #[ink(message)]
+        pub fn confirm_action(&mut self, action_id: u32) -> Result<bool, Error> {
+            let signer = self.ensure_signer()?;
+            let mut pending = self.pending_actions.get(action_id).ok_or(Error::ActionNotFound)?;
+
+            if pending.executed {
+                return Err(Error::ActionAlreadyExecuted);
+            }
+
+            if pending.confirmations.contains(&signer) {
+                return Err(Error::AlreadyConfirmed);
+            }
+
+            pending.confirmations.push(signer);
+            let confirmations = pending.confirmations.iter().filter(|s| self.signers.contains(s)).count() as u32;
+
+            self.env().emit_event(ActionConfirmed {
+                action_id,
+                signer,
+                confirmations,
+            });
+
+            if confirmations >= self.approval_threshold {
+                pending.executed = true;
+                self.pending_actions.insert(action_id, &pending);
+                self.execute_action(pending.action)?;
+                self.env().emit_event(ActionExecuted { action_id });
+                return Ok(true);
+            }
+
+            self.pending_actions.insert(action_id, &pending);
+
+            Ok(false)
+        }
+
+        /// Withdraw the caller's confirmation of an action not yet executed
+        #[ink(message)]
+        pub fn revoke_confirmation(&mut self, action_id: u32) -> Result<(), Error> {
+            let signer = self.ensure_signer()?;
+            let mut pending = self.pending_actions.get(action_id).ok_or(Error::ActionNotFound)?;
+
+            if pending.executed {
+                return Err(Error::ActionAlreadyExecuted);
+            }
+
+            pending.confirmations.retain(|s| *s != signer);
+            self.pending_actions.insert(action_id, &pending);
+
+            Ok(())
+        }
+
+        /// Set the house fee taken from each race pool at settlement
+        #[ink(message)]
+        pub fn set_house_fee(&mut self, fee_bps: u16) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.ensure_no_multisig()?;
+            self.ensure_ungoverned()?;
+            self.apply_parameter(ParameterChange::HouseFee(fee_bps))
+        }
+
+        /// Set the number of blocks races started from now on run for
+        #[ink(message)]
+        pub fn set_race_duration(&mut self, duration_blocks: u32) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.ensure_ungoverned()?;
+            self.apply_parameter(ParameterChange::RaceDuration(duration_blocks))
+        }
+
+        /// Hand the house fee, race duration and bet limits over to holders of a PSP22 token.
+        /// This cannot be undone.
+        #[ink(message)]
+        pub fn enable_governance(&mut self, config: GovernanceConfig) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.ensure_ungoverned()?;
+
+            if config.voting_period_blocks == 0 {
+                return Err(Error::InvalidSchedule);
+            }
+
+            self.governance = Some(config);
+
+            self.env().emit_event(GovernanceEnabled {
+                token: config.token,
+                voting_period_blocks: config.voting_period_blocks,
+                quorum: config.quorum,
+            });
+
+            Ok(())
+        }
+
+        /// Set the grade of a race that has not started yet
+        #[ink(message)]
+        pub fn set_race_grade(&mut self, race_id: u8, grade: RaceGrade) -> Result<(), Error> {
+            self.ensure_role(Role::RaceManager)?;
+
+            let mut race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+            if race.status != RaceStatus::Pending {
+                return Err(Error::RaceAlreadyStarted);
+            }
+
+            race.grade = grade;
+            self.races.insert(race_id, &race);
+
+            self.env().emit_event(RaceGraded { race_id, grade });
+
+            Ok(())
+        }
+
+        /// Schedule a reduced house fee for races of `grades` starting between
+        /// `start_block` and `end_block` inclusive
+        #[ink(message)]
+        pub fn schedule_promotion(
+            &mut self,
+            start_block: u32,
+            end_block: u32,
+            fee_bps: u16,
+            grades: Vec<RaceGrade>,
+        ) -> Result<u32, Error> {
+            self.ensure_owner()?;
+
+            let now = self.env().block_number();
+            if end_block < start_block || end_block < now || grades.is_empty() {
+                return Err(Error::InvalidSchedule);
+            }
+
+            if fee_bps >= self.house_fee_bps {
+                return Err(Error::FeeTooHigh {
+                    max_bps: self.house_fee_bps.saturating_sub(1),
+                });
+            }
+
+            self.promotions.retain(|promotion| promotion.end_block >= now);
+            if self.promotions.len() >= MAX_PROMOTIONS {
+                return Err(Error::TooManyPromotions);
+            }
+
+            let promotion_id = self.next_promotion_id;
+            self.next_promotion_id += 1;
+
+            self.promotions.push(TakeoutPromotion {
+                id: promotion_id,
+                start_block,
+                end_block,
+                fee_bps,
+                grades: grades.clone(),
+            });
+
+            self.env().emit_event(PromotionScheduled {
+                promotion_id,
+                start_block,
+                end_block,
+                fee_bps,
+                grades,
+            });
+
+            Ok(promotion_id)
+        }
+
+        /// Cancel a scheduled takeout promotion
+        #[ink(message)]
+        pub fn cancel_promotion(&mut self, promotion_id: u32) -> Result<(), Error> {
+            self.ensure_owner()?;
+
+            let index = self
+                .promotions
+                .iter()
+                .position(|promotion| promotion.id == promotion_id)
+                .ok_or(Error::PromotionNotFound)?;
+            self.promotions.remove(index);
+
+            self.env().emit_event(PromotionCancelled { promotion_id });
+
+            Ok(())
+        }
+
+        /// Withdraw accrued house fees
+        #[ink(message)]
+        pub fn withdraw_fees(&mut self, to: AccountId, amount: Balance) -> Result<(), Error> {
+            self.ensure_role(Role::Treasurer)?;
+
+            if amount > self.accrued_fees {
+                return Err(Error::InsufficientFees);
+            }
+
+            self.accrued_fees -= amount;
+
+            if self.env().transfer(to, amount).is_err() {
+                return Err(Error::TransferFailed);
+            }
+            self.ensure_solvent()?;
+
+            self.env().emit_event(FeesWithdrawn { to, amount });
+
+            Ok(())
+        }
+
+        /// Allow or stop bets in a PSP22 asset. Pools already open in the asset still settle.
+        #[ink(message)]
+        pub fn set_asset_approved(&mut self, asset: AccountId, approved: bool) -> Result<(), Error> {
+            self.ensure_owner()?;
+
+            if approved {
+                self.approved_assets.insert(asset, &());
+            } else {
+                self.approved_assets.remove(asset);
+            }
+
+            self.env().emit_event(AssetApprovalSet { asset, approved });
+
+            Ok(())
+        }
+
+        /// Withdraw fees taken in a PSP22 asset
+        #[ink(message)]
+        pub fn withdraw_asset_fees(&mut self, asset: AccountId, to: AccountId, amount: Balance) -> Result<(), Error> {
+            self.ensure_role(Role::Treasurer)?;
+
+            let fees = self.asset_fees.get(asset).unwrap_or(0);
+            if amount > fees {
+                return Err(Error::InsufficientFees);
+            }
+
+            self.asset_fees.insert(asset, &(fees - amount));
+            self.token_transfer(asset, to, amount)?;
+
+            self.env().emit_event(AssetFeesWithdrawn { asset, to, amount });
+
+            Ok(())
+        }
+
+        /// Close the current accounting day, storing and emitting its report
+        #[ink(message)]
+        pub fn close_day(&mut self) -> Result<DayReport, Error> {
+            self.ensure_role(Role::Treasurer)?;
+
+            let mut report = core::mem::take(&mut self.day_totals);
+            report.day = self.current_day;
+            report.closed_at = self.env().block_number();
+            report.unclaimed_balance = self.unclaimed_winnings;
+
+            self.day_reports.insert(report.day, &report);
+            self.current_day += 1;
+
+            self.env().emit_event(DayClosed {
+                day: report.day,
+                report: report.clone(),
+            });
+
+            Ok(report)
+        }
+
+        /// Schedule a code upgrade, applicable after the timelock expires
+        #[ink(message)]
+        pub fn schedule_upgrade(&mut self, code_hash: Hash) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.ensure_no_multisig()?;
+            self.start_upgrade_timelock(code_hash)
+        }
+
+        /// Cancel a scheduled code upgrade
+        #[ink(message)]
+        pub fn cancel_upgrade(&mut self) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.ensure_no_multisig()?;
+            self.stop_upgrade()
+        }
+
+        /// Replace the contract code with a previously scheduled code hash.
+        /// The new code must call `migrate` if it changes the storage layout.
+        #[ink(message)]
+        pub fn upgrade(&mut self, code_hash: Hash) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.ensure_no_multisig()?;
+            self.apply_upgrade(code_hash)
+        }
+
+        /// Apply pending storage migrations after an upgrade, in batches.
+        /// Returns true once the storage is at the current version.
+        #[ink(message)]
+        pub fn migrate(&mut self) -> Result<bool, Error> {
+            self.ensure_owner()?;
+
+            let from_version = self.storage_version;
+            if from_version >= STORAGE_VERSION {
+                return Err(Error::AlreadyMigrated);
+            }
+
+            // v1 -> v2: pack each race's horses into one `race_horses` entry
+            if self.storage_version == 1 {
+                let cursor = self.migration_cursor.get().unwrap_or(0);
+                let end = self.current_race_id.min(cursor.saturating_add(MAX_MIGRATION_BATCH));
+                for race_id in cursor..end {
+                    if self.race_horses.contains(race_id) {
+                        continue;
+                    }
+                    let horses = self.load_horses(race_id);
+                    for i in 0..horses.len() as u8 {
+                        self.horses.remove((race_id, i));
+                    }
+                    if !horses.is_empty() {
+                        self.race_horses.insert(race_id, &horses);
+                    }
+                }
+                self.migration_cursor.set(&end);
+
+                if end < self.current_race_id {
+                    return Ok(false);
+                }
+                self.storage_version = 2;
+            }
+
+            // Each future layout change adds a step here, e.g.
+            // `if self.storage_version == 2 { ...; self.storage_version = 3; }`
+            self.storage_version = STORAGE_VERSION;
+
+            self.env().emit_event(StorageMigrated {
+                from_version,
+                to_version: STORAGE_VERSION,
+            });
+
+            Ok(true)
+        }
+
+        /// Get race details
+        #[ink(message)]
+        pub fn get_race(&self, race_id: u8) -> Option<Race> {
+            self.races.get(race_id)
+        }
+
+        /// Pending races by id with the blocks left until their scheduled start
+        #[ink(message)]
+        pub fn get_upcoming_races(&self) -> Vec<UpcomingRace> {
+            let now = self.env().block_number();
+            (0..self.current_race_id)
+                .filter_map(|race_id| self.races.get(race_id))
+                .filter(|race| race.status == RaceStatus::Pending)
+                .map(|race| UpcomingRace {
+                    race_id: race.id,
+                    scheduled_start: race.scheduled_start,
+                    blocks_until_start: race.scheduled_start.saturating_sub(now),
+                    auto_start: self.auto_start_races.contains(race.id),
+                })
+                .collect()
+        }
+
+        /// Get horse details
+        #[ink(message)]
+        pub fn get_horse(&self, race_id: u8, horse_id: u8) -> Option<Horse> {
+            let mut horse = self.load_horse(race_id, horse_id)?;
+            horse.metadata = self.horse_metadata.get((race_id, horse_id));
+            Some(horse)
+        }
+
+        /// Get all horses for a race
+        #[ink(message)]
+        pub fn get_all_horses(&self, race_id: u8) -> Vec<Horse> {
+            let mut horses = self.load_horses(race_id);
+            for horse in horses.iter_mut() {
+                horse.metadata = self.horse_metadata.get((race_id, horse.id));
+            }
+            horses
+        }
+
+        /// Get a page of a bettor's bets on a race
+        #[ink(message)]
+        pub fn get_bets(&self, bettor: AccountId, race_id: u8, offset: u32, limit: u32) -> Vec<Bet> {
+            let count = self.bet_count.get((bettor, race_id)).unwrap_or(0);
+            let end = offset.saturating_add(limit.min(MAX_PAGE_SIZE)).min(count);
+
+            let mut bets = Vec::new();
+            for i in offset..end {
+                if let Some(bet) = self.bets.get((bettor, race_id, i)) {
+                    bets.push(bet);
+                }
+            }
+            bets
+        }
+
+        /// Get a bet by its global id
+        #[ink(message)]
+        pub fn get_bet_by_id(&self, bet_id: u64) -> Option<Bet> {
+            self.bets_by_id.get(bet_id)
+        }
+
+        /// Get a page of the most recent bets across all races, newest first.
+        /// Bets of archived races are skipped.
+        #[ink(message)]
+        pub fn get_recent_bets(&self, offset: u64, limit: u32) -> Vec<Bet> {
+            let newest = self.next_bet_id.saturating_sub(offset);
+            let oldest = newest.saturating_sub(limit.min(MAX_PAGE_SIZE) as u64);
+
+            (oldest..newest).rev().filter_map(|bet_id| self.bets_by_id.get(bet_id)).collect()
+        }
+
+        /// Get the caller's bets on a race
+        #[ink(message)]
+        pub fn get_my_bets_for_race(&self, race_id: u8) -> Vec<Bet> {
+            let bettor = self.env().caller();
+            let count = self.bet_count.get((bettor, race_id)).unwrap_or(0);
+
+            let mut bets = Vec::new();
+            for i in 0..count {
+                if let Some(bet) = self.bets.get((bettor, race_id, i)) {
+                    bets.push(bet);
+                }
+            }
+            bets
+        }
+
+        /// Get the caller's bets on every race they backed, with whether a winning
+        /// payout is waiting to be claimed on it
+        #[ink(message)]
+        pub fn get_my_positions(&self) -> Vec<(u8, Vec<Bet>, bool)> {
+            let bettor = self.env().caller();
+
+            let mut positions = Vec::new();
+            for race_id in 0..self.current_race_id {
+                if self.bet_count.get((bettor, race_id)).unwrap_or(0) == 0 {
+                    continue;
+                }
+                let claimable = self
+                    .races
+                    .get(race_id)
+                    .is_some_and(|race| self.winnings_of(bettor, &race).is_ok_and(|(_, payout)| payout > 0));
+                positions.push((race_id, self.get_my_bets_for_race(race_id), claimable));
+            }
+            positions
+        }
+
+        /// Get a race's unique bettor count, bet count and largest single bet
+        #[ink(message)]
+        pub fn get_race_stats(&self, race_id: u8) -> RaceStats {
+            self.race_stats.get(race_id).unwrap_or_default()
+        }
+
+        /// Get the number of bets a bettor placed on a race
+        #[ink(message)]
+        pub fn get_bet_count(&self, bettor: AccountId, race_id: u8) -> u32 {
+            self.bet_count.get((bettor, race_id)).unwrap_or(0)
+        }
+
+        /// Get current race count
+        #[ink(message)]
+        pub fn get_race_count(&self) -> u8 {
+            self.current_race_id
+        }
+
+        /// Get total pool for a race
+        #[ink(message)]
+        pub fn get_total_pool(&self, race_id: u8) -> Balance {
+            self.total_pool.get(race_id).unwrap_or(0)
+        }
+
+        /// Get horse pool
+        #[ink(message)]
+        pub fn get_horse_pool(&self, race_id: u8, horse_id: u8) -> Balance {
+            self.horse_pools.get((race_id, horse_id)).unwrap_or(0)
+        }
+
+        /// Get total boost contributions for a race
+        #[ink(message)]
+        pub fn get_pool_boost(&self, race_id: u8) -> Balance {
+            self.pool_boosts.get(race_id).unwrap_or(0)
+        }
+
+        /// Get the integrity hold on a horse, if any
+        #[ink(message)]
+        pub fn get_horse_hold(&self, race_id: u8, horse_id: u8) -> Option<HoldReason> {
+            self.horse_holds.get((race_id, horse_id))
+        }
+
+        /// Get the house fee in basis points
+        #[ink(message)]
+        pub fn get_house_fee(&self) -> u16 {
+            self.house_fee_bps
+        }
+
+        /// Get house fees available for withdrawal
+        #[ink(message)]
+        pub fn get_accrued_fees(&self) -> Balance {
+            self.accrued_fees
+        }
+
+        /// Get the index of the currently open accounting day
+        #[ink(message)]
+        pub fn get_current_day(&self) -> u32 {
+            self.current_day
+        }
+
+        /// Get the settlement report of a closed day
+        #[ink(message)]
+        pub fn get_day_report(&self, day: u32) -> Option<DayReport> {
+            self.day_reports.get(day)
+        }
+
+        /// Get the role held by an account
+        #[ink(message)]
+        pub fn get_role(&self, account: AccountId) -> Option<Role> {
+            self.roles.get(account)
+        }
+
+        /// Get the current season
+        #[ink(message)]
+        pub fn get_current_season(&self) -> u32 {
+            self.current_season
+        }
+
+        /// Get the loss cap currently in force for an account
+        #[ink(message)]
+        pub fn get_loss_cap(&self, account: AccountId) -> Option<Balance> {
+            match self.pending_loss_caps.get(account) {
+                Some((cap, effective_season)) if effective_season <= self.current_season => cap,
+                _ => self.loss_caps.get(account),
+            }
+        }
+
+        /// Get an account's stakes and returns in races of a season
+        #[ink(message)]
+        pub fn get_season_pnl(&self, account: AccountId, season: u32) -> SeasonPnl {
+            self.season_pnl.get((account, season)).unwrap_or_default()
+        }
+
+        /// Compare published win probabilities with pool-implied probabilities
+        #[ink(message)]
+        pub fn get_odds_divergence(&self, race_id: u8) -> Vec<OddsDivergence> {
+            let mut divergences = Vec::new();
+            let published = match self.published_probabilities.get(race_id) {
+                Some(published) => published,
+                None => return divergences,
+            };
+
+            // Only staked money implies odds, so boosts are left out
+            let horses = self.load_horses(race_id);
+            let mut staked = 0u128;
+            for i in 0..self.field_size(race_id) {
+                if !Self::scratched_in(&horses, i) {
+                    staked += self.horse_pools.get((race_id, i)).unwrap_or(0);
+                }
+            }
+
+            for (horse_id, published_bps) in (0..self.field_size(race_id)).zip(published) {
+                if Self::scratched_in(&horses, horse_id) {
+                    continue;
+                }
+
+                let pool = self.horse_pools.get((race_id, horse_id)).unwrap_or(0);
+                let pool_bps = (pool * BPS_DENOMINATOR).checked_div(staked).unwrap_or(0) as u16;
+                let divergence_bps = published_bps.abs_diff(pool_bps);
+
+                divergences.push(OddsDivergence {
+                    horse_id,
+                    published_bps,
+                    pool_bps,
+                    divergence_bps,
+                    flagged: staked > 0 && divergence_bps >= self.divergence_threshold_bps,
+                });
+            }
+            divergences
+        }
+
+        /// Get an organizer's account
+        #[ink(message)]
+        pub fn get_organizer(&self, organizer: AccountId) -> Option<Organizer> {
+            self.organizers.get(organizer)
+        }
+
+        /// Get the limits community organizers operate within
+        #[ink(message)]
+        pub fn get_organizer_guardrails(&self) -> OrganizerGuardrails {
+            self.organizer_guardrails.clone()
+        }
+
+        /// Get the combined revealed entropy of a race
+        #[ink(message)]
+        pub fn get_race_entropy(&self, race_id: u8) -> Option<[u8; 32]> {
+            self.race_entropy.get(race_id)
+        }
+
+        /// Get the rolling hash of the bets placed on a race, mixed into its acceleration rolls
+        #[ink(message)]
+        pub fn get_bet_entropy(&self, race_id: u8) -> Option<[u8; 32]> {
+            self.bet_entropy.get(race_id)
+        }
+
+        /// Get a claim code by its commitment
+        #[ink(message)]
+        pub fn get_claim_code(&self, commitment: Hash) -> Option<ClaimCode> {
+            self.claim_codes.get(commitment)
+        }
+
+        /// Replay a finished race from its recorded inputs using the algorithm
+        /// version it ran with, and check the replay matches the stored result
+        #[ink(message)]
+        pub fn verify_race(&self, race_id: u8) -> Result<bool, Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            if race.oracle.is_some() {
+                return Err(Error::OracleSettled);
+            }
+
+            if race.status != RaceStatus::Finished && race.status != RaceStatus::Archived {
+                return Err(Error::RaceNotFinished);
+            }
+
+            let entropy = self.entropy_seed(race_id);
+            let mut runners: Vec<u8> = race.rankings.clone();
+            runners.sort();
+            let seed = self.draw_seeds.get(race_id).unwrap_or([0u8; 32]);
+            let handicaps = self.get_handicaps(race_id);
+            let mut horses: Vec<Horse> = runners
+                .into_iter()
+                .map(|id| Horse {
+                    affinity: Self::affinity(&seed, id),
+                    experience: self.race_entrants.get((race_id, id)).map(|(_, level)| level).unwrap_or(0),
+                    handicap: handicaps.get(id as usize).copied().unwrap_or(0),
+                    ..Self::fresh_horse(id)
+                })
+                .collect();
+
+            let steps = self.race_step_count.get(race_id).unwrap_or(0);
+            for step in 0..steps {
+                let (block, timestamp) = self.race_steps.get((race_id, step)).ok_or(Error::MissingReplayData)?;
+                let blocks_elapsed = block - race.start_block;
+
+                for horse in horses.iter_mut().filter(|horse| !horse.finished) {
+                    horse.position += Self::acceleration(
+                        race.sim_version,
+                        horse,
+                        block,
+                        timestamp,
+                        entropy,
+                        race.track_condition.unwrap_or_default(),
+                    )
+                    .ok_or(Error::UnsupportedSimVersion)?;
+
+                    if horse.position >= FINISH_LINE {
+                        horse.position = FINISH_LINE;
+                        horse.finished = true;
+                        horse.finish_time = Some(blocks_elapsed);
+                    }
+                }
+            }
+
+            Self::rank_horses(&mut horses, &race, race.current_block);
+            let rankings: Vec<u8> = horses.iter().map(|horse| horse.id).collect();
+
+            Ok(rankings == race.rankings && Self::dead_heat_winners(&horses) == race.winners)
+        }
+
+        /// Get the replay snapshots of a race for block offsets `from` up to, not including,
+        /// `to`, as (block offset, positions of the horses that moved). Horses missing from a
+        /// snapshot kept their previous position.
+        #[ink(message)]
+        pub fn get_replay(&self, race_id: u8, from: u32, to: u32) -> Vec<(u32, Vec<(u8, u32)>)> {
+            let to = to.min(from.saturating_add(MAX_PAGE_SIZE));
+            (from..to)
+                .filter_map(|offset| self.replay_snapshots.get((race_id, offset)).map(|positions| (offset, positions)))
+                .collect()
+        }
+
+        /// Get the inputs of one recorded simulation step: (block, timestamp)
+        #[ink(message)]
+        pub fn get_race_step(&self, race_id: u8, step: u32) -> Option<(u32, u64)> {
+            self.race_steps.get((race_id, step))
+        }
+
+        /// Get the jackpot waiting to be carried into the next race
+        #[ink(message)]
+        pub fn get_jackpot(&self) -> Balance {
+            self.jackpot
+        }
+
+        /// Get the bet size ramp applied to new accounts
+        #[ink(message)]
+        pub fn get_new_account_limits(&self) -> NewAccountLimits {
+            self.new_account_limits.clone()
+        }
+
+        /// Get the maximum single bet currently allowed for an account, if limited
+        #[ink(message)]
+        pub fn get_account_bet_limit(&self, account: AccountId) -> Option<Balance> {
+            let history = self.account_history.get(account).unwrap_or(AccountHistory {
+                first_bet_block: self.env().block_number(),
+                races_entered: 0,
+            });
+            self.bet_limit_for(&history)
+        }
+
+        /// Get the referrer recorded for an account
+        #[ink(message)]
+        pub fn get_referrer(&self, referee: AccountId) -> Option<AccountId> {
+            self.referrers.get(referee)
+        }
+
+        /// Get the share of the house fee paid to referrers, in basis points
+        #[ink(message)]
+        pub fn get_referral_share(&self) -> u16 {
+            self.referral_bps
+        }
+
+        /// Get the first block at which bets on a race are rejected
+        #[ink(message)]
+        pub fn get_betting_deadline(&self, race_id: u8) -> Option<u32> {
+            self.races.get(race_id).map(|race| self.betting_deadline(&race))
+        }
+
+        /// Get the highlights of a finished race
+        #[ink(message)]
+        pub fn get_highlights(&self, race_id: u8) -> Option<RaceHighlights> {
+            self.race_highlights.get(race_id)
+        }
+
+        /// Get the races grouped in a card
+        #[ink(message)]
+        pub fn get_card(&self, card_id: u32) -> Option<Vec<u8>> {
+            self.cards.get(card_id)
+        }
+
+        /// Get an account's losing streak state, including races settled since its last bet
+        #[ink(message)]
+        pub fn get_streak(&self, account: AccountId) -> StreakState {
+            self.evaluate_streak(account).0
+        }
+
+        /// Get the scheduled takeout promotions
+        #[ink(message)]
+        pub fn get_promotions(&self) -> Vec<TakeoutPromotion> {
+            self.promotions.clone()
+        }
+
+        /// Get an account's prepaid balance for signed bets
+        #[ink(message)]
+        pub fn get_deposit(&self, account: AccountId) -> Balance {
+            self.deposits.get(account).unwrap_or(0)
+        }
+
+        /// Get the nonce the next signed bet from an account must carry
+        #[ink(message)]
+        pub fn get_bet_nonce(&self, account: AccountId) -> u64 {
+            self.bet_nonces.get(account).unwrap_or(0)
+        }
+
+        /// Get a race's horse ids in post position order
+        #[ink(message)]
+        pub fn get_post_positions(&self, race_id: u8) -> Vec<u8> {
+            self.post_positions.get(race_id).unwrap_or_default()
+        }
+
+        /// Get the seed a race's post positions were drawn from
+        #[ink(message)]
+        pub fn get_draw_seed(&self, race_id: u8) -> Option<[u8; 32]> {
+            self.draw_seeds.get(race_id)
+        }
+
+        /// Redo a race's post position draw from its stored seed and compare
+        #[ink(message)]
+        pub fn verify_draw(&self, race_id: u8) -> Result<bool, Error> {
+            let seed = self.draw_seeds.get(race_id).ok_or(Error::RaceNotFound)?;
+            Ok(self.draw_post_positions(&seed, self.field_size(race_id)) == self.get_post_positions(race_id))
+        }
+
+        /// Run the checks `place_slip` would for `bettor` sending `value`, returning one result per leg
+        #[ink(message)]
+        pub fn validate_slip(
+            &self,
+            bettor: AccountId,
+            legs: Vec<SlipLeg>,
+            value: Balance,
+        ) -> Result<Vec<Result<(), Error>>, Error> {
+            if legs.is_empty() || legs.len() > MAX_SLIP_LEGS {
+                return Err(Error::InvalidSlip);
+            }
+
+            if Self::slip_total(&legs)? != value {
+                return Err(Error::InvalidBetAmount);
+            }
+
+            // Earlier legs count towards the loss cap, pool limits and bet counts of later ones
+            let mut results = Vec::new();
+            let mut accepted: Vec<(u32, SlipLeg)> = Vec::new();
+
+            for leg in legs {
+                let race = match self.races.get(leg.race_id) {
+                    Some(race) => race,
+                    None => {
+                        results.push(Err(Error::RaceNotFound));
+                        continue;
+                    }
+                };
+
+                let mut prior = PriorLegs {
+                    bets: accepted.len() as u32,
+                    ..Default::default()
+                };
+                for (season, earlier) in accepted.iter() {
+                    if *season == race.season {
+                        prior.stake += earlier.amount;
+                    }
+                    if earlier.race_id == race.id {
+                        prior.pool += earlier.amount;
+                        prior.race_bets += 1;
+                        if earlier.horse_id == leg.horse_id {
+                            prior.horse_pool += earlier.amount;
+                        }
+                    }
+                }
+
+                let result = self.check_bet(bettor, &race, leg.horse_id, leg.amount, &prior);
+                if result.is_ok() {
+                    accepted.push((race.season, leg));
+                }
+                results.push(result);
+            }
+
+            Ok(results)
+        }
+
+        /// Get a parlay
+        #[ink(message)]
+        pub fn get_parlay(&self, parlay_id: u32) -> Option<Parlay> {
+            self.parlays.get(parlay_id)
+        }
+
+        /// Get the most a new parlay can pay, as a multiple of its stake
+        #[ink(message)]
+        pub fn get_parlay_max_multiple(&self) -> u32 {
+            self.parlay_max_multiple
+        }
+
+        /// Assemble the audit bundle for one race
+        #[ink(message)]
+        pub fn get_race_proof(&self, race_id: u8) -> Result<RaceProofBundle, Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            let step_count = self.race_step_count.get(race_id).unwrap_or(0);
+            let steps = (0..step_count)
+                .filter_map(|step| self.race_steps.get((race_id, step)))
+                .collect();
+
+            let horse_pools = (0..race.field_size)
+                .map(|i| (i, self.horse_pools.get((race_id, i)).unwrap_or(0)))
+                .collect();
+
+            let results = scale::Encode::encode(&(&race.rankings, &race.winners));
+            let results_digest = Hash::from(self.env().hash_bytes::<Blake2x256>(&results));
+
+            Ok(RaceProofBundle {
+                draw_seed: self.draw_seeds.get(race_id),
+                post_positions: self.get_post_positions(race_id),
+                entropy: self.race_entropy.get(race_id),
+                bet_entropy: self.bet_entropy.get(race_id),
+                steps,
+                published_probabilities: self.published_probabilities.get(race_id).unwrap_or_default(),
+                horse_pools,
+                total_pool: self.total_pool.get(race_id).unwrap_or(0),
+                pool_boost: self.pool_boosts.get(race_id).unwrap_or(0),
+                jackpot_seed: self.race_jackpot_seed.get(race_id).unwrap_or(0),
+                winner_odds: self.race_odds.get(race_id),
+                results_digest,
+                fee: self.race_fees.get(race_id).unwrap_or(0),
+                referral_commission: self.race_referral_commissions.get(race_id).unwrap_or(0),
+                race,
+            })
+        }
+
+        /// Get the live pool of a race as (staked total, weighted pool per horse)
+        #[ink(message)]
+        pub fn get_live_pool(&self, race_id: u8) -> (Balance, Vec<(u8, Balance)>) {
+            let pools = (0..self.field_size(race_id))
+                .map(|i| (i, self.live_pools.get((race_id, i)).unwrap_or(0)))
+                .collect();
+            (self.live_total.get(race_id).unwrap_or(0), pools)
+        }
+
+        /// Get the number of horses entered in a race
+        #[ink(message)]
+        pub fn get_field_size(&self, race_id: u8) -> u8 {
+            self.field_size(race_id)
+        }
+
+        /// Get the ids of races created by an organizer
+        #[ink(message)]
+        pub fn get_races_by_organizer(&self, organizer: AccountId) -> Vec<u8> {
+            (0..self.current_race_id)
+                .filter(|race_id| {
+                    self.races
+                        .get(race_id)
+                        .is_some_and(|race| race.organizer == Some(organizer))
+                })
+                .collect()
+        }
+
+        /// Get the owner registered for a horse in a race
+        #[ink(message)]
+        pub fn get_horse_owner(&self, race_id: u8, horse_id: u8) -> Option<AccountId> {
+            self.horse_owners.get((race_id, horse_id))
+        }
+
+        /// Get a race's purse while it is still to be paid out
+        #[ink(message)]
+        pub fn get_purse(&self, race_id: u8) -> Balance {
+            self.race_purses.get(race_id).unwrap_or(0)
+        }
+
+        /// Get the vesting schedule applied to new purses
+        #[ink(message)]
+        pub fn get_purse_vesting(&self) -> PurseVesting {
+            self.purse_vesting.clone()
+        }
+
+        /// Get an owner's purse escrow for a race and the amount claimable now
+        #[ink(message)]
+        pub fn get_purse_escrow(&self, owner: AccountId, race_id: u8) -> Option<(PurseEscrow, Balance)> {
+            let escrow = self.purse_escrows.get((owner, race_id))?;
+            let claimable = self.vested_purse(&escrow) - escrow.claimed;
+            Some((escrow, claimable))
+        }
+
+        /// Get an account's freeze while it is in force
+        #[ink(message)]
+        pub fn get_account_freeze(&self, account: AccountId) -> Option<AccountFreeze> {
+            self.frozen_accounts
+                .get(account)
+                .filter(|freeze| self.env().block_number() < freeze.expires_at)
+        }
+
+        /// Check whether an account is restricted from placing new bets
+        #[ink(message)]
+        pub fn is_restricted(&self, account: AccountId) -> bool {
+            self.restricted_accounts.contains(account)
+        }
+
+        /// Get the funds owed to races and bettors and the contract balance backing them
+        #[ink(message)]
+        pub fn get_solvency(&self) -> (Balance, Balance) {
+            (self.race_liabilities(), self.env().balance())
+        }
+
+        /// Get the steam detection threshold and window
+        #[ink(message)]
+        pub fn get_steam_detection(&self) -> (u16, u32) {
+            (self.steam_threshold_bps, self.steam_window_blocks)
+        }
+
+        /// Get the results of a finished race with each runner's finish, pool and odds
+        #[ink(message)]
+        pub fn get_race_results(&self, race_id: u8) -> Option<RaceResults> {
+            let race = self.races.get(race_id)?;
+            if race.status != RaceStatus::Finished {
+                return None;
+            }
+
+            let total_pool = self.total_pool.get(race_id).unwrap_or(0);
+            let net_pool = total_pool - self.race_fees.get(race_id).unwrap_or(0);
+            let odds = |pool: Balance| mul_div(net_pool, BPS_DENOMINATOR, pool).ok();
+
+            let horses = self.load_horses(race_id);
+            let rankings = race
+                .rankings
+                .iter()
+                .filter_map(|horse_id| horses.get(*horse_id as usize).cloned())
+                .map(|horse| {
+                    let pool = self.horse_pools.get((race_id, horse.id)).unwrap_or(0);
+                    HorseResult {
+                        horse_id: horse.id,
+                        finish_time: horse.finish_time,
+                        position: horse.position,
+                        pool,
+                        odds_bps: odds(pool),
+                    }
+                })
+                .collect();
+
+            Some(RaceResults {
+                race_id,
+                rankings,
+                total_pool,
+                net_pool,
+                winning_odds_bps: odds(self.winning_pool(&race)),
+                winners: race.winners,
+            })
+        }
+
+        /// Get the state of any market
+        #[ink(message)]
+        pub fn get_market(&self, market_id: MarketId) -> Option<MarketInfo> {
+            let race = self.races.get(market_id.race_id)?;
+            let (open, pool, total_pool) = match market_id.market {
+                Market::Win { horse_id } => (
+                    self.check_market(&race, horse_id).is_ok(),
+                    self.horse_pools.get((race.id, horse_id)).unwrap_or(0),
+                    self.total_pool.get(race.id).unwrap_or(0),
+                ),
+                Market::Live { horse_id } => (
+                    self.check_live_market(&race, horse_id).is_ok(),
+                    self.live_pools.get((race.id, horse_id)).unwrap_or(0),
+                    self.live_total.get(race.id).unwrap_or(0),
+                ),
+                // Summed over the box's permutations, so the odds hold while they are evenly backed
+                Market::TrifectaBox { ref horses } => (
+                    self.check_trifecta_box(&race, horses).is_ok(),
+                    Self::box_permutations(horses)
+                        .into_iter()
+                        .map(|(first, second, third)| self.trifecta_pools.get((race.id, first, second, third)).unwrap_or(0))
+                        .sum(),
+                    self.trifecta_totals.get(race.id).unwrap_or(0),
+                ),
+            };
+
+            let fee = mul_div(total_pool, self.house_fee_bps as u128, BPS_DENOMINATOR).ok()?;
+            Some(MarketInfo {
+                market_id,
+                open,
+                pool,
+                total_pool,
+                odds_bps: mul_div(total_pool - fee, BPS_DENOMINATOR, pool).ok(),
+            })
+        }
+
+        /// Estimate what a new `amount` on `horse_id` would pay if it wins, net of the
+        /// house fee, as if the stake were added to the pools now. Later bets move it.
+        #[ink(message)]
+        pub fn estimate_payout(&self, race_id: u8, horse_id: u8, amount: Balance) -> Balance {
+            let Some(race) = self.races.get(race_id) else {
+                return 0;
+            };
+            if horse_id >= race.field_size {
+                return 0;
+            }
+
+            // Stakes over the pool caps are refunded by `place_bet`, not pooled
+            let amount = amount.min(self.pool_cap_room(&race, horse_id, 0, 0));
+            if amount == 0 {
+                return 0;
+            }
+
+            // Locked odds and winnings are both held to the race's payout cap
+            let cap = self.payout_caps.get(race_id);
+            if race.bet_mode == BetMode::FixedOdds {
+                return self
+                    .fixed_odds
+                    .get((race_id, horse_id))
+                    .map(|odds| cap.map_or(odds, |cap| odds.min(cap)))
+                    .and_then(|odds| mul_div(amount, odds as u128, BPS_DENOMINATOR).ok())
+                    .unwrap_or(0);
+            }
+
+            let fee_bps = self.promotion_for(&race).map_or(self.house_fee_bps, |promotion| promotion.fee_bps);
+            let total_pool = self.total_pool.get(race_id).unwrap_or(0) + amount;
+            let horse_pool = self.horse_pools.get((race_id, horse_id)).unwrap_or(0) + amount;
+            mul_div(total_pool, fee_bps as u128, BPS_DENOMINATOR)
+                .and_then(|fee| mul_div(amount, total_pool - fee, horse_pool))
+                .and_then(|payout| self.cap_payout(race_id, amount, payout))
+                .unwrap_or(0)
+        }
+
+        /// Get an account's free pick on a race
+        #[ink(message)]
+        pub fn get_pick(&self, account: AccountId, race_id: u8) -> Option<Pick> {
+            self.picks.get((account, race_id))
+        }
+
+        /// Get an account's prediction points in a season
+        #[ink(message)]
+        pub fn get_season_points(&self, account: AccountId, season: u32) -> u32 {
+            self.season_points.get((account, season)).unwrap_or(0)
+        }
+
+        /// Get a season's top prediction scores, highest first
+        #[ink(message)]
+        pub fn get_leaderboard(&self, season: u32) -> Vec<(AccountId, u32)> {
+            self.leaderboards.get(season).unwrap_or_default()
+        }
+
+        /// Get the staked pool a race needs at its start
+        #[ink(message)]
+        pub fn get_min_pool(&self) -> Balance {
+            self.min_pool
+        }
+
+        /// Get the mirrored deployments reported for a race
+        #[ink(message)]
+        pub fn get_mirror_deployments(&self, race_id: u8) -> Vec<u32> {
+            self.mirror_deployments.get(race_id).unwrap_or_default()
+        }
+
+        /// Get the gross per-horse pools reported by a mirrored deployment
+        #[ink(message)]
+        pub fn get_mirror_pools(&self, race_id: u8, deployment: u32) -> Vec<Balance> {
+            self.mirror_pools.get((race_id, deployment)).unwrap_or_default()
+        }
+
+        /// Get the unreconciled balance with a mirror: positive if this deployment owes it
+        #[ink(message)]
+        pub fn get_mirror_settlement(&self, race_id: u8, deployment: u32) -> Option<i128> {
+            self.mirror_settlements.get((race_id, deployment))
+        }
+
+        /// Get the block from which a started emergency withdrawal may be finalized
+        #[ink(message)]
+        pub fn get_emergency_withdraw(&self) -> Option<u32> {
+            self.emergency_withdraw_at
+        }
+
+        /// Get a race's consolation share and, once settled, the runner-up payout as
+        /// (horse, tranche, runner-up pool)
+        #[ink(message)]
+        pub fn get_consolation(&self, race_id: u8) -> (u16, Option<(u8, Balance, Balance)>) {
+            (
+                self.race_consolation.get(race_id).unwrap_or(0),
+                self.runner_up_odds.get(race_id),
+            )
+        }
+
+        /// Get an account's loyalty points, betting streak and unused fee-free stake
+        #[ink(message)]
+        pub fn get_loyalty(&self, account: AccountId) -> Loyalty {
+            self.loyalty.get(account).unwrap_or_default()
+        }
+
+        /// Posted decimal odds in basis points for each horse of a race, 0 where none is posted
+        #[ink(message)]
+        pub fn get_fixed_odds(&self, race_id: u8) -> Vec<u32> {
+            let field_size = self.field_size(race_id);
+            (0..field_size)
+                .map(|horse_id| self.fixed_odds.get((race_id, horse_id)).unwrap_or(0))
+                .collect()
+        }
+
+        /// Funder and amount of the reserve backing a fixed-odds race
+        #[ink(message)]
+        pub fn get_odds_reserve(&self, race_id: u8) -> Option<(AccountId, Balance)> {
+            self.odds_reserves.get(race_id)
+        }
+
+        /// Payout the book owes if the horse wins
+        #[ink(message)]
+        pub fn get_fixed_liability(&self, race_id: u8, horse_id: u8) -> Balance {
+            self.fixed_liabilities.get((race_id, horse_id)).unwrap_or(0)
+        }
+
+        /// Odds locked by a bettor's `index`th bet on a fixed-odds race
+        #[ink(message)]
+        pub fn get_bet_odds(&self, bettor: AccountId, race_id: u8, index: u32) -> Option<u32> {
+            self.bet_odds.get((bettor, race_id, index))
+        }
+
+        /// Get a tournament
+        #[ink(message)]
+        pub fn get_tournament(&self, tournament_id: u32) -> Option<Tournament> {
+            self.tournaments.get(tournament_id)
+        }
+
+        /// Championship points of every horse in a tournament, indexed by horse id
+        #[ink(message)]
+        pub fn get_tournament_standings(&self, tournament_id: u32) -> Vec<u32> {
+            match self.tournaments.get(tournament_id) {
+                Some(tournament) => self.tournament_standings(&tournament),
+                None => Vec::new(),
+            }
+        }
+
+        /// Tournament a race is registered in, if any
+        #[ink(message)]
+        pub fn get_race_tournament(&self, race_id: u8) -> Option<u32> {
+            self.race_tournament.get(race_id)
+        }
+
+        /// Stake a bettor has on a horse in a tournament's champion pool
+        #[ink(message)]
+        pub fn get_tournament_bet(&self, tournament_id: u32, bettor: AccountId, horse_id: u8) -> Balance {
+            self.tournament_bets.get((tournament_id, bettor, horse_id)).unwrap_or(0)
+        }
+
+        /// Get the governance configuration, if governance is enabled
+        #[ink(message)]
+        pub fn get_governance(&self) -> Option<GovernanceConfig> {
+            self.governance
+        }
+
+        /// Get a governance proposal
+        #[ink(message)]
+        pub fn get_proposal(&self, proposal_id: u32) -> Option<Proposal> {
+            self.proposals.get(proposal_id)
+        }
+
+        /// Get the number of blocks newly started races run for
+        #[ink(message)]
+        pub fn get_race_duration(&self) -> u32 {
+            self.race_duration_blocks
+        }
+
+        /// Whether bets may be placed in a PSP22 asset
+        #[ink(message)]
+        pub fn is_asset_approved(&self, asset: AccountId) -> bool {
+            self.approved_assets.contains(asset)
+        }
+
+        /// Assets with a pool on a race, besides the native pool
+        #[ink(message)]
+        pub fn get_race_assets(&self, race_id: u8) -> Vec<AccountId> {
+            self.race_assets.get(race_id).unwrap_or_default()
+        }
+
+        /// Get the pool of a horse in a PSP22 asset
+        #[ink(message)]
+        pub fn get_asset_pool(&self, race_id: u8, horse_id: u8, asset: AccountId) -> Balance {
+            self.asset_pools.get((race_id, horse_id, asset)).unwrap_or(0)
+        }
+
+        /// Get a bettor's unclaimed asset bets on a race
+        #[ink(message)]
+        pub fn get_asset_bets(&self, bettor: AccountId, race_id: u8) -> Vec<AssetBet> {
+            self.asset_bets.get((bettor, race_id)).unwrap_or_default()
+        }
+
+        /// Get an account's stakes and returns in a PSP22 asset in races of a season
+        #[ink(message)]
+        pub fn get_asset_season_pnl(&self, account: AccountId, season: u32, asset: AccountId) -> SeasonPnl {
+            self.asset_season_pnl.get((account, season, asset)).unwrap_or_default()
+        }
+
+        /// Get the fees taken in a PSP22 asset and not yet withdrawn
+        #[ink(message)]
+        pub fn get_asset_fees(&self, asset: AccountId) -> Balance {
+            self.asset_fees.get(asset).unwrap_or(0)
+        }
+
+        /// Get the pool caps of a race
+        #[ink(message)]
+        pub fn get_pool_caps(&self, race_id: u8) -> PoolCaps {
+            self.pool_caps.get(race_id).unwrap_or_default()
+        }
+
+        /// Get the payout cap of a race, as a multiple of the stake in basis points
+        #[ink(message)]
+        pub fn get_payout_cap(&self, race_id: u8) -> Option<u32> {
+            self.payout_caps.get(race_id)
+        }
+
+        /// Get the basis points of every computed payout a race actually pays
+        #[ink(message)]
+        pub fn get_payout_factor(&self, race_id: u8) -> u32 {
+            self.payout_factors.get(race_id).unwrap_or(BPS_DENOMINATOR as u32)
+        }
+
+        /// Get a trifecta ticket
+        #[ink(message)]
+        pub fn get_trifecta_ticket(&self, ticket_id: u32) -> Option<TrifectaTicket> {
+            self.trifecta_tickets.get(ticket_id)
+        }
+
+        /// Stake on an ordered trifecta combination of a race
+        #[ink(message)]
+        pub fn get_trifecta_pool(&self, race_id: u8, first: u8, second: u8, third: u8) -> Balance {
+            self.trifecta_pools.get((race_id, first, second, third)).unwrap_or(0)
+        }
+
+        /// Get a registered horse's career record
+        #[ink(message)]
+        pub fn get_horse_career(&self, horse_id: u32) -> Option<HorseCareer> {
+            self.horse_careers.get(horse_id)
+        }
+
+        /// Handicap weight assigned to each horse of a race, by horse id. Empty unless
+        /// the field was drawn from the registry.
+        #[ink(message)]
+        pub fn get_handicaps(&self, race_id: u8) -> Vec<u8> {
+            self.race_handicaps.get(race_id).unwrap_or_default()
+        }
+
+        /// Registered horse running in each slot of a race, if any
+        #[ink(message)]
+        pub fn get_race_entrants(&self, race_id: u8) -> Vec<Option<u32>> {
+            (0..self.field_size(race_id))
+                .map(|i| self.race_entrants.get((race_id, i)).map(|(career_id, _)| career_id))
+                .collect()
+        }
+
+        /// Get the staking vault configuration
+        #[ink(message)]
+        pub fn get_staking(&self) -> Option<StakingConfig> {
+            self.staking
+        }
+
+        /// Get a staker's position, with rewards of closed epochs brought up to date
+        #[ink(message)]
+        pub fn get_stake(&self, staker: AccountId) -> StakeInfo {
+            self.synced_stake(staker)
+        }
+
+        /// Current staking epoch, its opening block and the fees collected in it so far
+        #[ink(message)]
+        pub fn get_staking_epoch(&self) -> (u32, u32, Balance) {
+            (self.staking_epoch, self.staking_epoch_start, self.epoch_rewards)
+        }
+
+        /// Get the per-account bet rate limits
+        #[ink(message)]
+        pub fn get_bet_rate_limits(&self) -> BetRateLimits {
+            self.bet_rate_limits
+        }
+
+        /// Claim window in blocks and where expired winnings go
+        #[ink(message)]
+        pub fn get_claim_window(&self) -> (u32, SweepDestination) {
+            (self.claim_window_blocks, self.sweep_destination)
+        }
+
+        /// Whether bets and claims advance the running race
+        #[ink(message)]
+        pub fn get_lazy_progression(&self) -> bool {
+            self.lazy_progression
+        }
+
+        /// Winnings still owed on a race, and the amount swept from it once expired
+        #[ink(message)]
+        pub fn get_race_unclaimed(&self, race_id: u8) -> (Balance, Balance) {
+            (
+                self.race_unclaimed.get(race_id).unwrap_or(0),
+                self.race_swept.get(race_id).unwrap_or(0),
+            )
+        }
+
+        /// Rounding dust moved out of a race's main pool once its last winning ticket was paid
+        #[ink(message)]
+        pub fn get_rounding_dust(&self, race_id: u8) -> Balance {
+            self.rounding_dust.get(race_id).unwrap_or(0)
+        }
+
+        /// Multisig signers and the number of confirmations actions need
+        #[ink(message)]
+        pub fn get_signers(&self) -> (Vec<AccountId>, u32) {
+            (self.signers.clone(), self.approval_threshold)
+        }
+
+        /// Get a queued owner action
+        #[ink(message)]
+        pub fn get_pending_action(&self, action_id: u32) -> Option<PendingAction> {
+            self.pending_actions.get(action_id)
+        }
+
+        /// Get the storage layout version
+        #[ink(message)]
+        pub fn get_storage_version(&self) -> u32 {
+            self.storage_version
+        }
+
+        /// Get the scheduled upgrade, if any
+        #[ink(message)]
+        pub fn get_pending_upgrade(&self) -> Option<(Hash, u32)> {
+            self.pending_upgrade
+        }
+
+        /// Remove a horse from the field and take its pool out of the race
+        fn scratch(&mut self, race_id: u8, horse_id: u8) -> Result<(), Error> {
+            let mut horses = self.load_horses(race_id);
+            let max = horses.len().saturating_sub(1) as u8;
+            let horse = horses.get_mut(horse_id as usize).ok_or(Error::InvalidHorse { max })?;
+            horse.scratched = true;
+            self.race_horses.insert(race_id, &horses);
+            self.horse_holds.remove((race_id, horse_id));
+
+            let refundable_pool = self.horse_pools.get((race_id, horse_id)).unwrap_or(0);
+            let total = self.total_pool.get(race_id).unwrap_or(0);
+            self.total_pool.insert(race_id, &(total - refundable_pool));
+            let unrefunded = self.scratch_unrefunded.get(race_id).unwrap_or(0);
+            self.scratch_unrefunded.insert(race_id, &(unrefunded + refundable_pool));
+
+            // Asset stakes on the horse are refunded with the race's asset winnings
+            for asset in self.race_assets.get(race_id).unwrap_or_default() {
+                let pool = self.asset_pools.get((race_id, horse_id, asset)).unwrap_or(0);
+                let total = self.asset_totals.get((race_id, asset)).unwrap_or(0);
+                self.asset_totals.insert((race_id, asset), &(total - pool));
+            }
+
+            self.env().emit_event(HorseScratched {
+                race_id,
+                horse_id,
+                refundable_pool,
+            });
+
+            Ok(())
+        }
+
+        fn is_scratched(&self, race_id: u8, horse_id: u8) -> bool {
+            Self::scratched_in(&self.load_horses(race_id), horse_id)
+        }
+
+        /// Whether a horse is scratched in a field read once with `load_horses`, for
+        /// callers checking several horses of the same race
+        fn scratched_in(horses: &[Horse], horse_id: u8) -> bool {
+            horses.get(horse_id as usize).is_some_and(|horse| horse.scratched)
+        }
+
+        /// Every horse of a race, indexed by horse id, falling back to the v1 layout
+        /// for races `migrate` has not reached yet
+        fn load_horses(&self, race_id: u8) -> Vec<Horse> {
+            if let Some(horses) = self.race_horses.get(race_id) {
+                return horses;
+            }
+            (0..self.field_size(race_id))
+                .filter_map(|i| self.horses.get((race_id, i)))
+                .collect()
+        }
+
+        fn load_horse(&self, race_id: u8, horse_id: u8) -> Option<Horse> {
+            self.load_horses(race_id).into_iter().nth(horse_id as usize)
+        }
+
+        /// Credit value paid back to a bettor against their season tally
+        fn record_returned(&mut self, bettor: AccountId, season: u32, amount: Balance) {
+            let mut pnl = self.season_pnl.get((bettor, season)).unwrap_or_default();
+            pnl.returned += amount;
+            self.season_pnl.insert((bettor, season), &pnl);
+        }
+
+        /// Ensure the caller may run `race`: a race manager, or its active organizer
+        fn ensure_race_operator(&self, race: &Race) -> Result<(), Error> {
+            if self.ensure_role(Role::RaceManager).is_ok() {
+                return Ok(());
+            }
+
+            let caller = self.env().caller();
+            match race.organizer {
+                Some(organizer) if organizer == caller => {
+                    let active = self.organizers.get(organizer).map(|a| a.active).unwrap_or(false);
+                    if active {
+                        Ok(())
+                    } else {
+                        Err(Error::Unauthorized)
+                    }
+                }
+                _ => Err(Error::Unauthorized),
+            }
+        }
+
+        /// Enforce the organizer pool cap on community-run races for `amount` more on a pool
+        /// of `pool`; a cap of 0 is no cap
+        fn ensure_within_pool_limit(&self, race: &Race, pool: Balance, amount: Balance) -> Result<(), Error> {
+            let max_pool = self.organizer_guardrails.max_pool;
+            if race.organizer.is_none() || max_pool == 0 {
+                return Ok(());
+            }
+
+            if pool + amount > max_pool {
+                return Err(Error::PoolLimitReached { max_pool });
+            }
+            Ok(())
+        }
+
+        /// Stake the horse can still take under the race's pool caps, with `prior_pool` and
+        /// `prior_horse_pool` already added to the race and horse by earlier legs of the same slip
+        fn pool_cap_room(&self, race: &Race, horse_id: u8, prior_pool: Balance, prior_horse_pool: Balance) -> Balance {
+            let caps = self.pool_caps.get(race.id).unwrap_or_default();
+            let mut room = Balance::MAX;
+
+            if let Some(cap) = caps.per_horse {
+                let pool = self.horse_pools.get((race.id, horse_id)).unwrap_or(0) + prior_horse_pool;
+                room = room.min(cap.saturating_sub(pool));
+            }
+
+            if let Some(cap) = caps.per_race {
+                let pool = self.total_pool.get(race.id).unwrap_or(0) + prior_pool;
+                room = room.min(cap.saturating_sub(pool));
+            }
+
+            room
+        }
+
+        /// Release an organizer's open race slot once the race settles or is cancelled
+        fn close_organizer_race(&mut self, race: &Race) {
+            if let Some(organizer) = race.organizer {
+                if let Some(mut account) = self.organizers.get(organizer) {
+                    account.open_races = account.open_races.saturating_sub(1);
+                    self.organizers.insert(organizer, &account);
+                }
+            }
+        }
+
+        /// Stake on the winners and payout owed on the tickets `bettor` holds on a finished race
+        fn winnings_of(&self, bettor: AccountId, race: &Race) -> Result<(Balance, Balance), Error> {
+            if race.status != RaceStatus::Finished {
+                return Err(Error::RaceNotFinished);
+            }
+
+            self.ensure_claim_open(race)?;
+
+            if race.winners.is_empty() {
+                return Err(Error::NoWinner);
+            }
+
+            // Calculate total bet on the winning horses and on a paid runner-up
+            let runner_up = self.runner_up_odds.get(race.id);
+            let mut winning_bet = 0u128;
+            let mut runner_up_bet = 0u128;
+            let mut fixed_payout = 0u128;
+
+            for (placed_by, i, bet) in self.held_tickets(bettor, race.id) {
+                if race.winners.contains(&bet.horse_id) {
+                    winning_bet += bet.amount;
+                    // Fixed-odds tickets are paid at their locked odds, split on a dead heat
+                    if let Some(odds) = self.bet_odds.get((placed_by, race.id, i)) {
+                        let full = mul_div(bet.amount, odds as u128, BPS_DENOMINATOR)?;
+                        fixed_payout += full / race.winners.len() as u128;
+                    }
+                } else if runner_up.is_some_and(|(horse_id, _, _)| horse_id == bet.horse_id) {
+                    runner_up_bet += bet.amount;
+                }
+            }
+
+            let mut total_bet = 0u128;
+            let mut payout = 0u128;
+
+            // Payout = (user_bet / winning_pool) * (total_pool - house fee - consolation),
+            // where a dead heat's winning pool spans every tied horse and both pools
+            // include any mirrored deployments
+            if race.bet_mode == BetMode::FixedOdds {
+                total_bet += winning_bet;
+                payout += fixed_payout;
+            } else if let (true, Some((net_pool, winning_pool))) = (winning_bet > 0, self.race_odds.get(race.id)) {
+                total_bet += winning_bet;
+                payout += mul_div(winning_bet, net_pool, winning_pool)?;
+            }
+
+            if let (true, Some((_, tranche, pool))) = (runner_up_bet > 0, runner_up) {
+                total_bet += runner_up_bet;
+                payout += mul_div(runner_up_bet, tranche, pool)?;
+            }
+
+            if total_bet == 0 {
+                if self.payouts_claimed.get((bettor, race.id)).unwrap_or(false) {
+                    return Err(Error::AlreadyClaimed);
+                }
+                return Err(Error::NoWinningBets);
+            }
+
+            if let Some(factor) = self.payout_factors.get(race.id) {
+                payout = mul_div(payout, factor as u128, BPS_DENOMINATOR)?;
+            }
+
+            Ok((total_bet, payout))
+        }
+
+        /// Settle `bettor`'s winnings on a race, paying them to `recipient`.
+        /// Every ticket the bettor holds on the race is burned.
+        fn pay_winnings(&mut self, bettor: AccountId, race_id: u8, recipient: AccountId) -> Result<Balance, Error> {
+            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
+
+            // Asset pools pay out first; a bettor may hold winners in an asset only
+            let asset_paid = self.pay_asset_winnings(bettor, &race, recipient)?;
+            let (total_bet, payout) = match self.winnings_of(bettor, &race) {
+                Err(Error::NoWinningBets) if asset_paid => return Ok(0),
+                result => result?,
+            };
+
+            for (_, _, bet) in self.held_tickets(bettor, race_id) {
+                self.burn_ticket(bettor, bet.id);
+            }
+            self.acquired_tickets.remove((bettor, race_id));
+
+            // Mark as claimed
+            self.payouts_claimed.insert((bettor, race_id), &true);
+            self.release_winnings(race.id, payout);
+            let unpaid = self.pool_winnings_unpaid.get(race_id).unwrap_or(0);
+            self.pool_winnings_unpaid.insert(race_id, &unpaid.saturating_sub(payout));
+            self.award_loyalty_points(bettor, LOYALTY_WIN_POINTS);
+            self.day_totals.payouts += payout;
+            self.record_returned(bettor, race.season, payout);
+            self.record_settled_stake(race_id, total_bet);
+            self.sweep_rounding_dust(&race);
+
+            // Transfer winnings
+            if self.env().transfer(recipient, payout).is_err() {
+                return Err(Error::TransferFailed);
+            }
+            self.ensure_solvent()?;
+
+            self.env().emit_event(WinningsClaimed {
+                bettor,
+                race_id,
+                payout,
+                recipient,
+                payout_factor: self.get_payout_factor(race_id),
+            });
+
+            Ok(payout)
+        }
+
+        /// Current holder of a bet's ticket, or None once spent by a claim or refund
+        fn ticket_holder(&self, bet: &Bet) -> Option<AccountId> {
+            if self.burned_tickets.contains(bet.id) {
+                return None;
+            }
+            if let Some(holder) = self.ticket_holders.get(bet.id) {
+                return Some(holder);
+            }
+
+            // Never transferred: spent by the bettor's own claim or refund on the race,
+            // which also covers bets placed before tickets were minted
+            let key = (bet.bettor, bet.race_id);
+            let scratch_refunded =
+                self.scratch_refunds_claimed.get(key).unwrap_or(false) && self.is_scratched(bet.race_id, bet.horse_id);
+            if self.payouts_claimed.get(key).unwrap_or(false) || scratch_refunded {
+                return None;
+            }
+            Some(bet.bettor)
+        }
+
+        /// Bets on a race whose tickets `holder` holds, as (bettor, bet index, bet): their own
+        /// untransferred bets followed by the tickets they received
+        fn held_tickets(&self, holder: AccountId, race_id: u8) -> Vec<(AccountId, u32, Bet)> {
+            let mut held = Vec::new();
+
+            for i in 0..self.bet_count.get((holder, race_id)).unwrap_or(0) {
+                if let Some(bet) = self.bets.get((holder, race_id, i)) {
+                    if !self.ticket_holders.contains(bet.id) && self.ticket_holder(&bet).is_some() {
+                        held.push((holder, i, bet));
+                    }
+                }
+            }
+
+            for (bettor, i) in self.acquired_tickets.get((holder, race_id)).unwrap_or_default() {
+                if let Some(bet) = self.bets.get((bettor, race_id, i)) {
+                    if self.ticket_holder(&bet) == Some(holder) {
+                        held.push((bettor, i, bet));
+                    }
+                }
+            }
+
+            held
+        }
+
+        fn mint_ticket(&mut self, to: AccountId, bet_id: u64) {
+            let balance = self.ticket_balances.get(to).unwrap_or(0);
+            self.ticket_balances.insert(to, &(balance + 1));
+            self.ticket_supply += 1;
+
+            self.env().emit_event(Transfer {
+                from: None,
+                to: Some(to),
+                id: Id::U64(bet_id),
+            });
+        }
+
+        fn burn_ticket(&mut self, holder: AccountId, bet_id: u64) {
+            self.burned_tickets.insert(bet_id, &());
+            self.ticket_holders.remove(bet_id);
+            self.ticket_approvals.remove(bet_id);
+
+            // Bets placed before tickets were minted were never counted
+            let balance = self.ticket_balances.get(holder).unwrap_or(0);
+            self.ticket_balances.insert(holder, &balance.saturating_sub(1));
+            self.ticket_supply = self.ticket_supply.saturating_sub(1);
+
+            self.env().emit_event(Transfer {
+                from: Some(holder),
+                to: None,
+                id: Id::U64(bet_id),
+            });
+        }
+
+        /// The bet behind a PSP34 ticket id and its current holder
+        fn ticket(&self, id: &Id) -> Result<(Bet, AccountId), PSP34Error> {
+            let bet = match id {
+                Id::U64(bet_id) => self.bets_by_id.get(bet_id).ok_or(PSP34Error::TokenNotExists)?,
+                _ => return Err(PSP34Error::TokenNotExists),
+            };
+            let holder = self.ticket_holder(&bet).ok_or(PSP34Error::TokenNotExists)?;
+            Ok((bet, holder))
+        }
+
+        /// The bettor's unexpired claim code on a race, if any
+        fn active_claim_code(&self, bettor: AccountId, race_id: u8) -> Option<Hash> {
+            let commitment = self.bettor_claim_codes.get((bettor, race_id))?;
+            let code = self.claim_codes.get(commitment)?;
+            if self.env().block_number() > code.expires_at {
+                return None;
+            }
+            Some(commitment)
+        }
+
+        /// Combined pool of every horse sharing first place
+        fn winning_pool(&self, race: &Race) -> Balance {
+            race.winners
+                .iter()
+                .map(|horse_id| self.horse_pools.get((race.id, *horse_id)).unwrap_or(0))
+                .sum()
+        }
+
+        fn record_settled_stake(&mut self, race_id: u8, amount: Balance) {
+            let settled = self.settled_stake.get(race_id).unwrap_or(0);
+            self.settled_stake.insert(race_id, &(settled + amount));
+        }
+
+        /// Stake that must be paid back out of a settled race before it can be archived
+        fn owed_stake(&self, race: &Race) -> Balance {
+            let horses = self.load_horses(race.id);
+            let mut owed = 0;
+            for i in 0..race.field_size {
+                let pool = self.horse_pools.get((race.id, i)).unwrap_or(0);
+                let is_winner = race.winners.contains(&i)
+                    || self.runner_up_odds.get(race.id).is_some_and(|(horse_id, _, _)| horse_id == i);
+                if race.status == RaceStatus::Cancelled || is_winner || Self::scratched_in(&horses, i) {
+                    owed += pool;
+                }
+            }
+            owed
+        }
+
+        /// Maximum bet for an account that has not yet graduated the new account ramp
+        fn bet_limit_for(&self, history: &AccountHistory) -> Option<Balance> {
+            let limits = &self.new_account_limits;
+            let age = self.env().block_number() - history.first_bet_block;
+
+            if history.races_entered >= limits.ramp_races && age >= limits.ramp_blocks {
+                return None;
+            }
+
+            let ramp = limits.increment_per_race.saturating_mul(history.races_entered as u128);
+            Some(limits.base_max_bet.saturating_add(ramp))
+        }
+
+        fn add_referred_handle(&mut self, referrer: AccountId, race_id: u8, delta: i128) {
+            let handle = self.referred_handle.get((referrer, race_id)).unwrap_or(0);
+            self.referred_handle.insert((referrer, race_id), &handle.saturating_add_signed(delta));
+
+            let race_handle = self.race_referred_handle.get(race_id).unwrap_or(0);
+            self.race_referred_handle.insert(race_id, &race_handle.saturating_add_signed(delta));
+        }
+
+        /// Commission owed on `handle` of referred bets in a settled race: the referral
+        /// share of the fee attributable to those bets
+        fn referral_commission(&self, race_id: u8, handle: Balance) -> Result<Balance, Error> {
+            let total_pool = self.total_pool.get(race_id).unwrap_or(0);
+            if total_pool == 0 {
+                return Ok(0);
+            }
+
+            let fee = self.race_fees.get(race_id).unwrap_or(0);
+            let rate = self.race_referral_rate.get(race_id).unwrap_or(0) as u128;
+            let attributable = mul_div(fee, handle.min(total_pool), total_pool)?;
+            mul_div(attributable, rate, BPS_DENOMINATOR)
+        }
+
+        fn betting_deadline(&self, race: &Race) -> u32 {
+            race.scheduled_start.saturating_sub(self.betting_cutoff_blocks)
+        }
+
+        /// Running horse with the largest pool, lowest id on ties
+        fn favorite(&self, race_id: u8) -> Option<u8> {
+            let horses = self.load_horses(race_id);
+            let mut favorite: Option<(u8, Balance)> = None;
+            for i in 0..self.field_size(race_id) {
+                if Self::scratched_in(&horses, i) || self.horse_holds.contains((race_id, i)) {
+                    continue;
+                }
+                let pool = self.horse_pools.get((race_id, i)).unwrap_or(0);
+                if favorite.is_none_or(|(_, best)| pool > best) {
+                    favorite = Some((i, pool));
+                }
+            }
+            favorite.map(|(horse_id, _)| horse_id)
+        }
+
+        /// Fold races settled since the last evaluation into the account's streak
+        fn evaluate_streak(&self, account: AccountId) -> (StreakState, Vec<u8>) {
+            let mut streak = self.streaks.get(account).unwrap_or_default();
+            let mut counted = Vec::new();
+
+            for race_id in 0..self.current_race_id {
+                if self.bet_count.get((account, race_id)).unwrap_or(0) == 0
+                    || self.streak_counted.get((account, race_id)).unwrap_or(false)
+                {
+                    continue;
+                }
+
+                let race = match self.races.get(race_id) {
+                    Some(race) if race.status == RaceStatus::Finished => race,
+                    _ => continue,
+                };
+
+                if self.backed_winner(account, &race) {
+                    streak.consecutive_losses = 0;
+                } else {
+                    streak.consecutive_losses += 1;
+                }
+                counted.push(race_id);
+            }
+
+            (streak, counted)
+        }
+
+        fn refresh_streak(&mut self, account: AccountId) -> StreakState {
+            let (streak, counted) = self.evaluate_streak(account);
+            for race_id in counted {
+                self.streak_counted.insert((account, race_id), &true);
+            }
+            self.streaks.insert(account, &streak);
+            streak
+        }
+
+        fn backed_winner(&self, bettor: AccountId, race: &Race) -> bool {
+            let count = self.bet_count.get((bettor, race.id)).unwrap_or(0);
+            (0..count).any(|i| {
+                self.bets
+                    .get((bettor, race.id, i))
+                    .is_some_and(|bet| race.winners.contains(&bet.horse_id))
+            })
+        }
+
+        /// Lowest-fee promotion covering the race's grade and start block
+        fn promotion_for(&self, race: &Race) -> Option<TakeoutPromotion> {
+            self.promotions
+                .iter()
+                .filter(|promotion| {
+                    promotion.grades.contains(&race.grade)
+                        && (promotion.start_block..=promotion.end_block).contains(&race.start_block)
+                        && promotion.fee_bps < self.house_fee_bps
+                })
+                .min_by_key(|promotion| promotion.fee_bps)
+                .cloned()
+        }
+
+        /// Take the house fee from a finished race's live pool; with no live winners
+        /// the remainder rolls into the jackpot
+        fn settle_live_pool(&mut self, race: &Race, fee_bps: u16) -> Result<(), Error> {
+            let total = self.live_total.get(race.id).unwrap_or(0);
+            if total == 0 {
+                return Ok(());
+            }
+
+            let fee = mul_div(total, fee_bps as u128, BPS_DENOMINATOR)?;
+            self.live_fees.insert(race.id, &fee);
+            self.accrued_fees += fee;
+            self.day_totals.fees += fee;
+
+            if self.live_winning_weight(race) > 0 {
+                self.owe_winnings(race.id, total - fee);
+            } else if total > fee {
+                let amount = total - fee;
+                self.jackpot += amount;
+
+                self.env().emit_event(JackpotRolledOver {
+                    from_race: race.id,
+                    amount,
+                    jackpot: self.jackpot,
+                });
+            }
+
+            Ok(())
+        }
+
+        /// Take the house fee from each of a finished race's asset pools and price its winners.
+        /// An asset pool with no winning stake goes to the asset's fees.
+        fn settle_asset_pools(&mut self, race: &Race, fee_bps: u16) -> Result<(), Error> {
+            for asset in self.race_assets.get(race.id).unwrap_or_default() {
+                let total = self.asset_totals.get((race.id, asset)).unwrap_or(0);
+                let winning_pool: Balance = race.winners
+                    .iter()
+                    .map(|horse_id| self.asset_pools.get((race.id, *horse_id, asset)).unwrap_or(0))
+                    .sum();
+
+                let fee = mul_div(total, fee_bps as u128, BPS_DENOMINATOR)?;
+                let taken = if winning_pool > 0 {
+                    self.asset_odds.insert((race.id, asset), &(total - fee, winning_pool));
+                    fee
+                } else {
+                    total
+                };
+                let fees = self.asset_fees.get(asset).unwrap_or(0);
+                self.asset_fees.insert(asset, &(fees + taken));
+            }
+
+            Ok(())
+        }
+
+        /// Pay `bettor`'s asset bets on a finished or archived race to `recipient`: winners at their asset
+        /// pool's price, stakes on scratched horses back. Returns whether anything was paid.
+        fn pay_asset_winnings(&mut self, bettor: AccountId, race: &Race, recipient: AccountId) -> Result<bool, Error> {
+            let payouts = self.take_asset_payouts(bettor, race)?;
+
+            for &(asset, payout) in &payouts {
+                self.token_transfer(asset, recipient, payout)?;
+
+                self.env().emit_event(AssetWinningsClaimed {
+                    bettor,
+                    race_id: race.id,
+                    asset,
+                    payout,
+                    recipient,
+                });
+            }
+
+            Ok(!payouts.is_empty())
+        }
+
+        /// Record an asset bet already pulled from `bettor` in its race's pool for the asset
+        fn record_asset_bet(&mut self, bettor: AccountId, race: &Race, horse_id: u8, asset: AccountId, amount: Balance) {
+            let race_id = race.id;
+            let mut assets = self.race_assets.get(race_id).unwrap_or_default();
+            if !assets.contains(&asset) {
+                assets.push(asset);
+                self.race_assets.insert(race_id, &assets);
+            }
+
+            let mut bets = self.asset_bets.get((bettor, race_id)).unwrap_or_default();
+            bets.push(AssetBet {
+                asset,
+                horse_id,
+                amount,
+            });
+            self.asset_bets.insert((bettor, race_id), &bets);
+
+            let pool = self.asset_pools.get((race_id, horse_id, asset)).unwrap_or(0);
+            self.asset_pools.insert((race_id, horse_id, asset), &(pool + amount));
+            let total = self.asset_totals.get((race_id, asset)).unwrap_or(0);
+            self.asset_totals.insert((race_id, asset), &(total + amount));
+
+            let mut pnl = self.asset_season_pnl.get((bettor, race.season, asset)).unwrap_or_default();
+            pnl.staked += amount;
+            self.asset_season_pnl.insert((bettor, race.season, asset), &pnl);
+            self.count_in_window(bettor);
+        }
+
+        /// Credit an asset paid back to a bettor against their season tally in that asset
+        fn record_asset_returned(&mut self, bettor: AccountId, season: u32, asset: AccountId, amount: Balance) {
+            let mut pnl = self.asset_season_pnl.get((bettor, season, asset)).unwrap_or_default();
+            pnl.returned += amount;
+            self.asset_season_pnl.insert((bettor, season, asset), &pnl);
+        }
+
+        /// Take `bettor`'s asset bets off a finished or archived race while its claim window is
+        /// open, returning the payout owed in each asset and recording the stake it settles
+        fn take_asset_payouts(&mut self, bettor: AccountId, race: &Race) -> Result<Vec<(AccountId, Balance)>, Error> {
+            let claimable = matches!(race.status, RaceStatus::Finished | RaceStatus::Archived);
+            if !claimable || self.ensure_claim_open(race).is_err() {
+                return Ok(Vec::new());
+            }
+
+            let bets = match self.asset_bets.take((bettor, race.id)) {
+                Some(bets) => bets,
+                None => return Ok(Vec::new()),
+            };
+
+            let horses = self.load_horses(race.id);
+            let mut payouts = Vec::new();
+            for asset in self.race_assets.get(race.id).unwrap_or_default() {
+                let mut winning_stake = 0u128;
+                let mut payout = 0u128;
+                for bet in bets.iter().filter(|bet| bet.asset == asset) {
+                    if Self::scratched_in(&horses, bet.horse_id) {
+                        payout += bet.amount;
+                    } else if race.winners.contains(&bet.horse_id) {
+                        winning_stake += bet.amount;
+                    }
+                }
+                let refunded = payout;
+
+                if let (true, Some((net_pool, winning_pool))) = (winning_stake > 0, self.asset_odds.get((race.id, asset))) {
+                    payout += mul_div(winning_stake, net_pool, winning_pool)?;
+                }
+
+                if payout == 0 {
+                    continue;
+                }
+
+                self.record_asset_settled_stake(race.id, asset, refunded + winning_stake);
+                self.record_asset_returned(bettor, race.season, asset, payout);
+                payouts.push((asset, payout));
+            }
+
+            Ok(payouts)
+        }
+
+        fn record_asset_settled_stake(&mut self, race_id: u8, asset: AccountId, amount: Balance) {
+            let settled = self.asset_settled_stake.get((race_id, asset)).unwrap_or(0);
+            self.asset_settled_stake.insert((race_id, asset), &(settled + amount));
+        }
+
+        /// Whether stake owed back out of a settled race in any of its assets is still unclaimed
+        fn asset_stake_unsettled(&self, race: &Race) -> bool {
+            let horses = self.load_horses(race.id);
+            self.race_assets.get(race.id).unwrap_or_default().into_iter().any(|asset| {
+                let owed: Balance = (0..race.field_size)
+                    .filter(|i| {
+                        race.status == RaceStatus::Cancelled || race.winners.contains(i) || Self::scratched_in(&horses, *i)
+                    })
+                    .map(|i| self.asset_pools.get((race.id, i, asset)).unwrap_or(0))
+                    .sum();
+                self.asset_settled_stake.get((race.id, asset)).unwrap_or(0) < owed
+            })
+        }
+
+        /// Refund every asset bet `bettor` placed on a cancelled race, returning whether
+        /// anything was refunded
+        fn refund_asset_bets(&mut self, bettor: AccountId, race_id: u8) -> Result<bool, Error> {
+            let bets = match self.asset_bets.take((bettor, race_id)) {
+                Some(bets) => bets,
+                None => return Ok(false),
+            };
+            let season = self.races.get(race_id).map(|race| race.season).unwrap_or_default();
+
+            for asset in self.race_assets.get(race_id).unwrap_or_default() {
+                let amount: Balance = bets.iter().filter(|bet| bet.asset == asset).map(|bet| bet.amount).sum();
+                if amount == 0 {
+                    continue;
+                }
+
+                self.record_asset_settled_stake(race_id, asset, amount);
+                self.record_asset_returned(bettor, season, asset, amount);
+                self.token_transfer(asset, bettor, amount)?;
+
+                self.env().emit_event(AssetBetRefunded {
+                    bettor,
+                    race_id,
+                    asset,
+                    amount,
+                });
+            }
+
+            Ok(true)
+        }
+
+        /// Take the house fee from a race's trifecta pool, leaving the rest to the winning
+        /// permutation. With fewer than three horses home every ticket is refunded instead.
+        fn settle_trifecta(&mut self, race: &Race, fee_bps: u16) -> Result<(), Error> {
+            let total = self.trifecta_totals.get(race.id).unwrap_or(0);
+            if total == 0 {
+                return Ok(());
+            }
+
+            if race.rankings.len() < 3 {
+                self.owe_winnings(race.id, total);
+                return Ok(());
+            }
+
+            let fee = mul_div(total, fee_bps as u128, BPS_DENOMINATOR)?;
+            self.trifecta_fees.insert(race.id, &fee);
+            self.accrued_fees += fee;
+            self.day_totals.fees += fee;
+
+            if self.trifecta_winning_stake(race) > 0 {
+                self.owe_winnings(race.id, total - fee);
+            } else if total > fee {
+                let amount = total - fee;
+                self.jackpot += amount;
+
+                self.env().emit_event(JackpotRolledOver {
+                    from_race: race.id,
+                    amount,
+                    jackpot: self.jackpot,
+                });
+            }
+
+            Ok(())
+        }
+
+        /// Ensure the race's trifecta pool is open for a box of `horses`
+        fn check_trifecta_box(&self, race: &Race, horses: &[u8]) -> Result<(), Error> {
+            if race.bet_mode != BetMode::Parimutuel {
+                return Err(Error::WrongBetMode);
+            }
+
+            if horses.len() < 3 || horses.len() > MAX_BOX_HORSES {
+                return Err(Error::InvalidCombination);
+            }
+
+            for (index, horse_id) in horses.iter().enumerate() {
+                if horses[..index].contains(horse_id) {
+                    return Err(Error::InvalidCombination);
+                }
+                self.check_market(race, *horse_id)?;
+            }
+
+            Ok(())
+        }
+
+        /// Every ordered (first, second, third) permutation of three of the boxed horses
+        fn box_permutations(horses: &[u8]) -> Vec<(u8, u8, u8)> {
+            let mut permutations = Vec::new();
+            for first in horses.iter() {
+                for second in horses.iter().filter(|h| *h != first) {
+                    for third in horses.iter().filter(|h| *h != first && *h != second) {
+                        permutations.push((*first, *second, *third));
+                    }
+                }
+            }
+            permutations
+        }
+
+        /// Claim every trifecta ticket the caller holds on a race that pays out
+        fn claim_trifecta_tickets(&mut self, race_id: u8) -> Result<Balance, Error> {
+            let bettor = self.env().caller();
+            let mut amount = 0;
+            for ticket_id in self.trifecta_ticket_ids.get((bettor, race_id)).unwrap_or_default() {
+                match self.claim_trifecta(ticket_id) {
+                    Ok(payout) => amount += payout,
+                    Err(Error::NoWinningBets) | Err(Error::AlreadyClaimed) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+
+            if amount == 0 {
+                return Err(Error::NoWinningBets);
+            }
+            Ok(amount)
+        }
+
+        /// Stake on the permutation matching the first three home
+        fn trifecta_winning_stake(&self, race: &Race) -> Balance {
+            match race.rankings[..] {
+                [first, second, third, ..] => self.trifecta_pools.get((race.id, first, second, third)).unwrap_or(0),
+                _ => 0,
+            }
+        }
+
+        fn live_winning_weight(&self, race: &Race) -> Balance {
+            race.winners
+                .iter()
+                .map(|horse_id| self.live_pools.get((race.id, *horse_id)).unwrap_or(0))
+                .sum()
+        }
+
+        fn field_size(&self, race_id: u8) -> u8 {
+            self.races.get(race_id).map(|race| race.field_size).unwrap_or(0)
+        }
+
+        /// Split a finished race's purse between the owners of its winners. Shares of
+        /// winners without a registered owner go to the house.
+        fn escrow_purse(&mut self, race: &Race) {
+            let purse = match self.race_purses.take(race.id) {
+                Some(purse) if !race.winners.is_empty() => purse,
+                Some(purse) => {
+                    self.accrued_fees += purse;
+                    return;
+                }
+                None => return,
+            };
+
+            let share = purse / race.winners.len() as u128;
+            let mut unassigned = purse - share * race.winners.len() as u128;
+
+            for horse_id in race.winners.iter() {
+                let owner = match self.horse_owners.get((race.id, *horse_id)) {
+                    Some(owner) => owner,
+                    None => {
+                        unassigned += share;
+                        continue;
+                    }
+                };
+
+                let mut escrow = self.purse_escrows.get((owner, race.id)).unwrap_or(PurseEscrow {
+                    total: 0,
+                    claimed: 0,
+                    start_block: self.env().block_number(),
+                    vesting: self.purse_vesting.clone(),
+                });
+                escrow.total += share;
+                self.purse_escrows.insert((owner, race.id), &escrow);
+
+                self.env().emit_event(PurseEscrowed {
+                    race_id: race.id,
+                    owner,
+                    amount: share,
+                });
+            }
+
+            self.accrued_fees += unassigned;
+        }
+
+        fn vested_purse(&self, escrow: &PurseEscrow) -> Balance {
+            let elapsed = self.env().block_number().saturating_sub(escrow.start_block);
+            if elapsed < escrow.vesting.cliff_blocks {
+                return 0;
+            }
+            if elapsed >= escrow.vesting.duration_blocks {
+                return escrow.total;
+            }
+            // Below the total, so the division cannot overflow
+            mul_div(escrow.total, elapsed as u128, escrow.vesting.duration_blocks as u128).unwrap_or(0)
+        }
+
+        fn ensure_not_frozen(&self, account: AccountId) -> Result<(), Error> {
+            if self.get_account_freeze(account).is_some() {
+                return Err(Error::AccountFrozen);
+            }
+            Ok(())
+        }
+
+        fn ensure_not_restricted(&self, account: AccountId) -> Result<(), Error> {
+            if self.restricted_accounts.contains(account) {
+                return Err(Error::AccountRestricted);
+            }
+            Ok(())
+        }
+
+        /// Each running horse's share of the staked pool, in basis points
+        fn pool_shares(&self, race: &Race) -> Vec<(u8, u16)> {
+            let horses = self.load_horses(race.id);
+            let pools: Vec<(u8, Balance)> = (0..race.field_size)
+                .filter(|i| !Self::scratched_in(&horses, *i))
+                .map(|i| (i, self.horse_pools.get((race.id, i)).unwrap_or(0)))
+                .collect();
+            let staked: Balance = pools.iter().map(|(_, pool)| pool).sum();
+
+            pools
+                .into_iter()
+                .map(|(i, pool)| (i, (pool * BPS_DENOMINATOR).checked_div(staked).unwrap_or(0) as u16))
+                .collect()
+        }
+
+        /// Compare pool shares with each horse's reference share from the start of its
+        /// window, emitting `SteamMove` and starting a new window on large moves
+        fn detect_steam(&mut self, race: &Race, shares_before: Vec<(u8, u16)>) {
+            if self.steam_threshold_bps == 0 {
+                return;
+            }
+
+            // The first bets into an empty market are not a move
+            let priced = shares_before.iter().any(|(_, share)| *share > 0);
+
+            let now = self.env().block_number();
+            for ((horse_id, before), (_, after)) in shares_before.into_iter().zip(self.pool_shares(race)) {
+                let (since_block, reference) = match self.steam_references.get((race.id, horse_id)) {
+                    Some((start, share)) if now - start <= self.steam_window_blocks => (start, share),
+                    _ => (now, before),
+                };
+
+                if priced && after.abs_diff(reference) >= self.steam_threshold_bps {
+                    self.env().emit_event(SteamMove {
+                        race_id: race.id,
+                        horse_id,
+                        before_bps: reference,
+                        after_bps: after,
+                        since_block,
+                    });
+                    self.steam_references.insert((race.id, horse_id), &(now, after));
+                } else {
+                    self.steam_references.insert((race.id, horse_id), &(since_block, reference));
+                }
+            }
+        }
+
+        /// Place an account's new season total on the bounded leaderboard
+        fn update_leaderboard(&mut self, season: u32, account: AccountId, points: u32) {
+            let mut board = self.leaderboards.get(season).unwrap_or_default();
+            board.retain(|(entry, _)| *entry != account);
+
+            // Earlier entries keep their place on ties
+            let index = board.iter().position(|(_, score)| *score < points).unwrap_or(board.len());
+            if index < LEADERBOARD_SIZE {
+                board.insert(index, (account, points));
+                board.truncate(LEADERBOARD_SIZE);
+                self.leaderboards.insert(season, &board);
+            }
+        }
+
+        /// Cancel a race, returning its jackpot seed and purse
+        fn cancel(&mut self, mut race: Race) {
+            let race_id = race.id;
+            race.status = RaceStatus::Cancelled;
+            race.settled_at = self.env().block_number();
+            self.races.insert(race_id, &race);
+            self.close_organizer_race(&race);
+
+            let side_pools = self.live_total.get(race_id).unwrap_or(0) + self.trifecta_totals.get(race_id).unwrap_or(0);
+            self.cancel_unrefunded.insert(race_id, &side_pools);
+
+            // The purse of a cancelled race goes back to the house
+            if let Some(purse) = self.race_purses.take(race_id) {
+                self.accrued_fees += purse;
+            }
+
+            // The reserve of a cancelled fixed-odds race goes back to its funder
+            if let Some((funder, reserve)) = self.odds_reserves.take(race_id) {
+                self.credit_deposit(funder, reserve);
+            }
+
+            // A cancelled race hands its jackpot seed back
+            if let Some(seed) = self.race_jackpot_seed.take(race_id) {
+                let total = self.total_pool.get(race_id).unwrap_or(0);
+                self.total_pool.insert(race_id, &(total - seed));
+                self.jackpot += seed;
+            }
+
+            self.env().emit_event(RaceCancelled {
+                race_id,
+                block_number: self.env().block_number(),
+            });
+        }
+
+        /// Take the house fee and price the winners out of a parimutuel race's pool,
+        /// returning the fee
+        fn settle_pool(&mut self, race: &Race) -> Result<Balance, Error> {
+            let race_id = race.id;
+
+            // Take the house fee; the rest is owed to backers of the winner
+            let total_pool = self.total_pool.get(race_id).unwrap_or(0);
+            let fee_bps = match self.promotion_for(race) {
+                Some(promotion) => {
+                    self.env().emit_event(PromotionApplied {
+                        race_id,
+                        promotion_id: promotion.id,
+                        fee_bps: promotion.fee_bps,
+                    });
+                    promotion.fee_bps
+                }
+                None => self.house_fee_bps,
+            };
+            let fee = mul_div(total_pool, fee_bps as u128, BPS_DENOMINATOR)?;
+            self.race_fees.insert(race_id, &fee);
+            self.settle_live_pool(race, fee_bps)?;
+            self.settle_trifecta(race, fee_bps)?;
+            self.settle_asset_pools(race, fee_bps)?;
+
+            // Referrers are owed their commission on referred bets out of the fee
+            self.race_referral_rate.insert(race_id, &self.referral_bps);
+            let referred = self.race_referred_handle.get(race_id).unwrap_or(0);
+            let commissions = self.referral_commission(race_id, referred)?;
+            if commissions > 0 {
+                self.race_referral_commissions.insert(race_id, &commissions);
+                self.race_referral_unclaimed.insert(race_id, &(commissions, referred));
+                self.referral_owed += commissions;
+            }
+            let mut house_fee = fee - commissions;
+
+            // Organizers earn their share of the house fee on their own races
+            if let Some(organizer) = race.organizer {
+                if let Some(mut account) = self.organizers.get(organizer) {
+                    let share = mul_div(house_fee, account.fee_share_bps as u128, BPS_DENOMINATOR)?;
+                    account.fees_earned += share;
+                    self.organizers.insert(organizer, &account);
+                    self.organizer_fees_owed += share;
+                    house_fee -= share;
+                }
+            }
+            let staking_share = self.fund_staking(house_fee)?;
+            self.accrued_fees += house_fee - staking_share;
+
+            let winning_pool = self.winning_pool(race);
+            let (net_total, winning_total) = self.net_mirror_pools(race, total_pool - fee, winning_pool, fee_bps)?;
+            let net_total = self.pay_consolation(race, net_total)?;
+            if winning_total > 0 {
+                // Snapshot the winner odds as (net pool, winning pool); winners are paid at this price,
+                // and what the payout cap holds back from local winners rolls into the jackpot
+                let capped_total = self.cap_payout(race_id, winning_total, net_total)?;
+                self.race_odds.insert(race_id, &(capped_total, winning_total));
+                let owed = mul_div(winning_pool, capped_total, winning_total)?;
+                self.owe_pool_winnings(race.id, owed);
+
+                let held_back = mul_div(winning_pool, net_total, winning_total)? - owed;
+                if held_back > 0 {
+                    self.jackpot += held_back;
+
+                    self.env().emit_event(JackpotRolledOver {
+                        from_race: race_id,
+                        amount: held_back,
+                        jackpot: self.jackpot,
+                    });
+                }
+            } else if net_total > 0 {
+                // Nobody backed the winner: roll the pot into the jackpot
+                let amount = net_total;
+                self.jackpot += amount;
+
+                self.env().emit_event(JackpotRolledOver {
+                    from_race: race_id,
+                    amount,
+                    jackpot: self.jackpot,
+                });
+            }
+
+            Ok(fee)
+        }
+
+        /// Pay a fixed-odds race's winning tickets out of its stakes and reserve,
+        /// handing what is left back to the reserve's funder
+        fn settle_book(&mut self, race: &Race) -> Result<(), Error> {
+            let total_pool = self.total_pool.get(race.id).unwrap_or(0);
+            let reserve = self.odds_reserves.take(race.id);
+
+            // A dead heat splits each winning ticket's payout between the tied horses
+            let mut owed = 0;
+            for horse_id in race.winners.iter() {
+                let liability = self.fixed_liabilities.get((race.id, *horse_id)).unwrap_or(0);
+                owed += liability / race.winners.len() as u128;
+            }
+
+            let (funder, funded) = match reserve {
+                Some((funder, funded)) => (Some(funder), funded),
+                None => (None, 0),
+            };
+
+            // Stakes and reserve short of the winners (e.g. after scratches) scale every payout
+            // down pro-rata, so the last claimer is not the one left unpaid
+            let available = total_pool + funded;
+            if owed > available {
+                self.payout_factors.insert(race.id, &(mul_div(available, BPS_DENOMINATOR, owed)? as u32));
+                owed = available;
+            }
+            self.owe_pool_winnings(race.id, owed);
+
+            let amount = available - owed;
+            match funder {
+                Some(funder) => self.credit_deposit(funder, amount),
+                None => self.accrued_fees += amount,
+            }
+
+            self.env().emit_event(BookSettled {
+                race_id: race.id,
+                funder,
+                owed,
+                amount,
+            });
+
+            Ok(())
+        }
+
+        /// Limit a payout on `stake` to the race's payout cap, if it has one
+        fn cap_payout(&self, race_id: u8, stake: Balance, payout: Balance) -> Result<Balance, Error> {
+            match self.payout_caps.get(race_id) {
+                Some(cap) => Ok(payout.min(mul_div(stake, cap as u128, BPS_DENOMINATOR)?)),
+                None => Ok(payout),
+            }
+        }
+
+        /// Add to an account's withdrawable balance
+        fn credit_deposit(&mut self, account: AccountId, amount: Balance) {
+            let balance = self.deposits.get(account).unwrap_or(0);
+            self.deposits.insert(account, &(balance + amount));
+            self.total_deposits += amount;
+        }
+
+        /// Score a finished tournament race. Horses sharing first place all score the
+        /// winner's points; the rest score by their position in the rankings.
+        fn award_championship_points(&mut self, race: &Race) {
+            let tournament_id = match self.race_tournament.get(race.id) {
+                Some(tournament_id) => tournament_id,
+                None => return,
+            };
+            let tournament = match self.tournaments.get(tournament_id) {
+                Some(tournament) => tournament,
+                None => return,
+            };
+
+            for (position, horse_id) in race.rankings.iter().enumerate() {
+                let position = if race.winners.contains(horse_id) { 0 } else { position };
+                let points = match tournament.points.get(position) {
+                    Some(points) => *points,
+                    None => break,
+                };
+                let total = self.championship_points.get((tournament_id, *horse_id)).unwrap_or(0);
+                self.championship_points.insert((tournament_id, *horse_id), &(total + points));
+            }
+
+            self.env().emit_event(ChampionshipPointsAwarded {
+                tournament_id,
+                race_id: race.id,
+                standings: self.tournament_standings(&tournament),
+            });
+        }
+
+        fn tournament_standings(&self, tournament: &Tournament) -> Vec<u32> {
+            (0..tournament.field_size)
+                .map(|horse_id| self.championship_points.get((tournament.id, horse_id)).unwrap_or(0))
+                .collect()
+        }
+
+        /// Record winnings owed on a race
+        fn owe_winnings(&mut self, race_id: u8, amount: Balance) {
+            self.unclaimed_winnings += amount;
+            let owed = self.race_unclaimed.get(race_id).unwrap_or(0);
+            self.race_unclaimed.insert(race_id, &(owed + amount));
+        }
+
+        /// Record winnings owed on a race's main pool, tracked apart from its live and
+        /// trifecta pools so the pool's rounding dust can be told from unclaimed winnings
+        fn owe_pool_winnings(&mut self, race_id: u8, amount: Balance) {
+            self.owe_winnings(race_id, amount);
+            let unpaid = self.pool_winnings_unpaid.get(race_id).unwrap_or(0);
+            self.pool_winnings_unpaid.insert(race_id, &(unpaid + amount));
+        }
+
+        /// Once every winning ticket on a race's main pool is paid, move what per-claim
+        /// truncation left of the pool's winnings to the sweep destination
+        fn sweep_rounding_dust(&mut self, race: &Race) {
+            let settled = self.settled_stake.get(race.id).unwrap_or(0);
+            if settled < self.owed_stake(race) {
+                return;
+            }
+
+            let amount = self.pool_winnings_unpaid.take(race.id).unwrap_or(0);
+            if amount == 0 {
+                return;
+            }
+
+            self.release_winnings(race.id, amount);
+            self.rounding_dust.insert(race.id, &amount);
+            match self.sweep_destination {
+                SweepDestination::Treasury => self.accrued_fees += amount,
+                SweepDestination::Jackpot => self.jackpot += amount,
+            }
+
+            self.env().emit_event(RoundingDustSwept {
+                race_id: race.id,
+                amount,
+                destination: self.sweep_destination,
+            });
+        }
+
+        /// Record winnings paid out of a race
+        fn release_cancel_refund(&mut self, race_id: u8, amount: Balance) {
+            let unrefunded = self.cancel_unrefunded.get(race_id).unwrap_or(0);
+            self.cancel_unrefunded.insert(race_id, &unrefunded.saturating_sub(amount));
+        }
+
+        fn release_winnings(&mut self, race_id: u8, amount: Balance) {
+            self.unclaimed_winnings = self.unclaimed_winnings.saturating_sub(amount);
+            let owed = self.race_unclaimed.get(race_id).unwrap_or(0);
+            self.race_unclaimed.insert(race_id, &owed.saturating_sub(amount));
+        }
+
+        /// Ensure the race's claim window has not expired
+        fn ensure_claim_open(&self, race: &Race) -> Result<(), Error> {
+            let window = self.claim_window_blocks;
+            let expired_at = race.settled_at.saturating_add(window);
+            if window > 0 && self.env().block_number() >= expired_at {
+                return Err(Error::ClaimExpired { expired_at });
+            }
+            Ok(())
+        }
+
+        /// Combine the local and mirrored pools into one (net pool, winning pool) price and
+        /// record each mirror's balance: its winners' payouts at that price less its net pool
+        fn net_mirror_pools(
+            &mut self,
+            race: &Race,
+            net_pool: Balance,
+            winning_pool: Balance,
+            fee_bps: u16,
+        ) -> Result<(Balance, Balance), Error> {
+            let deployments = self.mirror_deployments.get(race.id).unwrap_or_default();
+
+            let mut mirrors = Vec::new();
+            let (mut net_total, mut winning_total) = (net_pool, winning_pool);
+            for deployment in deployments {
+                let pools = self.mirror_pools.get((race.id, deployment)).unwrap_or_default();
+                let gross: Balance = pools.iter().sum();
+                let net = gross - mul_div(gross, fee_bps as u128, BPS_DENOMINATOR)?;
+                let winning: Balance = race
+                    .winners
+                    .iter()
+                    .map(|horse_id| pools.get(*horse_id as usize).copied().unwrap_or(0))
+                    .sum();
+
+                net_total += net;
+                winning_total += winning;
+                mirrors.push((deployment, net, winning));
+            }
+
+            // Without winners anywhere every deployment keeps its own pot
+            if winning_total == 0 {
+                return Ok((net_pool, 0));
+            }
+
+            for (deployment, net, winning) in mirrors {
+                let payouts = mul_div(winning, net_total, winning_total)?;
+                let amount = payouts as i128 - net as i128;
+                self.mirror_settlements.insert((race.id, deployment), &amount);
+
+                self.env().emit_event(MirrorSettled {
+                    race_id: race.id,
+                    deployment,
+                    amount,
+                });
+            }
+
+            Ok((net_total, winning_total))
+        }
+
+        fn ensure_bridge(&self) -> Result<AccountId, Error> {
+            match self.bridge {
+                Some(bridge) if bridge == self.env().caller() => Ok(bridge),
+                _ => Err(Error::Unauthorized),
+            }
+        }
+
+        /// Fail if the contract balance no longer covers `race_liabilities`. Every message that
+        /// pays native funds out checks this last, so an overpayment reverts instead of
+        /// spending funds owed to other races.
+        fn ensure_solvent(&self) -> Result<(), Error> {
+            if self.env().balance() < self.race_liabilities() {
+                return Err(Error::Insolvent);
+            }
+            Ok(())
+        }
+
+        /// Funds attributable to races: pools, live and trifecta pools, purses and odds reserves of open races,
+        /// refunds owed on cancelled races and scratched horses, unclaimed winnings, the jackpot, deposits,
+        /// tournament pools, staking rewards, open parlay stakes and reserves, organizer bonds and fees, and referral
+        /// commissions
+        fn race_liabilities(&self) -> Balance {
+            let mut owed = self.unclaimed_winnings + self.jackpot + self.total_deposits + self.open_tournament_pools;
+            owed += self.epoch_rewards + self.staking_rewards_owed + self.open_parlay_stakes + self.parlay_reserves;
+            owed += self.organizer_bonds + self.organizer_fees_owed + self.referral_owed;
+            for race_id in 0..self.current_race_id {
+                let race = match self.races.get(race_id) {
+                    Some(race) => race,
+                    None => continue,
+                };
+                match race.status {
+                    RaceStatus::Pending | RaceStatus::Active => {
+                        owed += self.total_pool.get(race_id).unwrap_or(0);
+                        owed += self.live_total.get(race_id).unwrap_or(0);
+                        owed += self.trifecta_totals.get(race_id).unwrap_or(0);
+                        owed += self.race_purses.get(race_id).unwrap_or(0);
+                        owed += self.odds_reserves.get(race_id).map(|(_, reserve)| reserve).unwrap_or(0);
+                        owed += self.scratch_unrefunded.get(race_id).unwrap_or(0);
+                    }
+                    RaceStatus::Finished => owed += self.scratch_unrefunded.get(race_id).unwrap_or(0),
+                    RaceStatus::Cancelled => {
+                        let settled = self.settled_stake.get(race_id).unwrap_or(0);
+                        owed += self.owed_stake(&race).saturating_sub(settled);
+                        owed += self.cancel_unrefunded.get(race_id).unwrap_or(0);
+                    }
+                    _ => {}
+                }
+            }
+            owed
+        }
+
+        /// Set aside the consolation tranche for backers of the first horse home behind the
+        /// winners, returning what is left of the net pool for the winners
+        fn pay_consolation(&mut self, race: &Race, net_pool: Balance) -> Result<Balance, Error> {
+            let share_bps = match self.race_consolation.get(race.id) {
+                Some(share_bps) => share_bps,
+                None => return Ok(net_pool),
+            };
+
+            let runner_up = match race.rankings.iter().find(|horse_id| !race.winners.contains(horse_id)) {
+                Some(horse_id) => *horse_id,
+                None => return Ok(net_pool),
+            };
+
+            let pool = self.horse_pools.get((race.id, runner_up)).unwrap_or(0);
+            if pool == 0 {
+                return Ok(net_pool);
+            }
+
+            // Any of the tranche above the payout cap stays with the winners
+            let tranche = mul_div(net_pool, share_bps as u128, BPS_DENOMINATOR)?;
+            let tranche = self.cap_payout(race.id, pool, tranche)?;
+            self.runner_up_odds.insert(race.id, &(runner_up, tranche, pool));
+            self.owe_pool_winnings(race.id, tranche);
+
+            Ok(net_pool - tranche)
+        }
+
+        /// Extend the account's streak on its first bet in a race and award a point plus
+        /// a bonus that grows with the streak
+        fn accrue_loyalty(&mut self, account: AccountId, race_id: u8) {
+            let mut loyalty = self.loyalty.get(account).unwrap_or_default();
+            loyalty.streak = match loyalty.last_race {
+                Some(last) if last + 1 == race_id => loyalty.streak + 1,
+                _ => 1,
+            };
+            loyalty.last_race = Some(race_id);
+            self.loyalty.insert(account, &loyalty);
+
+            let bonus = (loyalty.streak - 1).min(LOYALTY_MAX_STREAK_BONUS);
+            self.award_loyalty_points(account, 1 + bonus);
+        }
+
+        fn award_loyalty_points(&mut self, account: AccountId, points: u32) {
+            let mut loyalty = self.loyalty.get(account).unwrap_or_default();
+            loyalty.points = loyalty.points.saturating_add(points);
+            self.loyalty.insert(account, &loyalty);
+
+            self.env().emit_event(LoyaltyPointsEarned {
+                account,
+                points,
+                streak: loyalty.streak,
+            });
+        }
+
+        /// House-paid top-up on a bet covered by the account's fee-free stake
+        fn loyalty_discount(&mut self, account: AccountId, race_id: u8, amount: Balance) -> Balance {
+            let mut loyalty = match self.loyalty.get(account) {
+                Some(loyalty) if loyalty.fee_free_stake > 0 => loyalty,
+                _ => return 0,
+            };
+
+            let covered = amount.min(loyalty.fee_free_stake);
+            let discount = mul_div(covered, self.house_fee_bps as u128, BPS_DENOMINATOR).unwrap_or(0);
+            if discount == 0 || discount > self.accrued_fees {
+                return 0;
+            }
+
+            loyalty.fee_free_stake -= covered;
+            self.loyalty.insert(account, &loyalty);
+            self.accrued_fees -= discount;
+
+            self.env().emit_event(LoyaltyDiscountApplied {
+                account,
+                race_id,
+                discount,
+            });
+
+            discount
+        }
+
+        /// Owner setters are disabled for parameters under governance
+        fn ensure_ungoverned(&self) -> Result<(), Error> {
+            if self.governance.is_some() {
+                return Err(Error::Governed);
+            }
+            Ok(())
+        }
+
+        fn check_parameter(change: &ParameterChange) -> Result<(), Error> {
+            match change {
+                ParameterChange::HouseFee(fee_bps) if *fee_bps > MAX_HOUSE_FEE_BPS => Err(Error::FeeTooHigh {
+                    max_bps: MAX_HOUSE_FEE_BPS,
+                }),
+                ParameterChange::RaceDuration(blocks) if *blocks == 0 || *blocks > MAX_RACE_DURATION_BLOCKS => {
+                    Err(Error::InvalidSchedule)
+                }
+                _ => Ok(()),
+            }
+        }
+
+        fn apply_parameter(&mut self, change: ParameterChange) -> Result<(), Error> {
+            Self::check_parameter(&change)?;
+
+            match change {
+                ParameterChange::HouseFee(fee_bps) => {
+                    self.house_fee_bps = fee_bps;
+                    self.env().emit_event(HouseFeeUpdated { fee_bps });
+                }
+                ParameterChange::RaceDuration(duration_blocks) => {
+                    self.race_duration_blocks = duration_blocks;
+                    self.env().emit_event(RaceDurationUpdated { duration_blocks });
+                }
+                ParameterChange::NewAccountLimits(limits) => self.new_account_limits = limits,
+            }
+
+            Ok(())
+        }
+
+        /// Pull `value` of a PSP22 token from `from` into the contract
+        fn token_transfer_from(&self, token: AccountId, from: AccountId, value: Balance) -> Result<(), Error> {
+            build_call::<ink::env::DefaultEnvironment>()
+                .call(token)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("PSP22::transfer_from")))
+                        .push_arg(from)
+                        .push_arg(self.env().account_id())
+                        .push_arg(value)
+                        .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<Result<(), PSP22Error>>()
+                .try_invoke()
+                .map_err(|_| Error::TokenCallFailed)?
+                .map_err(|_| Error::TokenCallFailed)?
+                .map_err(|_| Error::TokenCallFailed)
+        }
+
+        /// Send `value` of a PSP22 token held by the contract to `to`
+        fn token_transfer(&self, token: AccountId, to: AccountId, value: Balance) -> Result<(), Error> {
+            build_call::<ink::env::DefaultEnvironment>()
+                .call(token)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("PSP22::transfer")))
+                        .push_arg(to)
+                        .push_arg(value)
+                        .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<Result<(), PSP22Error>>()
+                .try_invoke()
+                .map_err(|_| Error::TokenCallFailed)?
+                .map_err(|_| Error::TokenCallFailed)?
+                .map_err(|_| Error::TokenCallFailed)
+        }
+
+        /// Current `PSP22::balance_of` of `account` on the governance token
+        fn governance_weight(&self, token: AccountId, account: AccountId) -> Result<Balance, Error> {
+            build_call::<ink::env::DefaultEnvironment>()
+                .call(token)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("PSP22::balance_of"))).push_arg(account),
+                )
+                .returns::<Balance>()
+                .try_invoke()
+                .map_err(|_| Error::TokenCallFailed)?
+                .map_err(|_| Error::TokenCallFailed)
+        }
+
+        /// Set aside the stakers' share of a race's house fee for the open epoch
+        fn fund_staking(&mut self, house_fee: Balance) -> Result<Balance, Error> {
+            let config = match self.staking {
+                Some(config) => config,
+                None => return Ok(0),
+            };
+
+            let share = mul_div(house_fee, config.fee_share_bps as u128, BPS_DENOMINATOR)?;
+            self.epoch_rewards += share;
+            Ok(share)
+        }
+
+        /// Close the open epoch if it has run its length, sharing its fees over the active
+        /// stake and activating stake pending for the next epoch. Fees of an epoch with no
+        /// active stake carry over.
+        fn advance_staking_epoch(&mut self) {
+            let config = match self.staking {
+                Some(config) => config,
+                None => return,
             };
 
-            self.races.insert(race_id, &race);
-            self.current_race_id += 1;
+            let now = self.env().block_number();
+            if now < self.staking_epoch_start + config.epoch_blocks {
+                return;
+            }
+
+            let epoch = self.staking_epoch;
+            let rewards = self.epoch_rewards;
+            if self.total_staked > 0 && rewards > 0 {
+                if let Ok(per_token) = mul_div(rewards, REWARD_PRECISION, self.total_staked) {
+                    self.reward_per_token += per_token;
+                    self.staking_rewards_owed += rewards;
+                    self.epoch_rewards = 0;
+                }
+            }
+
+            self.env().emit_event(StakingEpochClosed {
+                epoch,
+                rewards,
+                total_staked: self.total_staked,
+            });
+
+            self.staking_epoch += 1;
+            self.staking_epoch_start = now;
+            self.epoch_reward_per_token.insert(self.staking_epoch, &self.reward_per_token);
+            self.total_staked += self.pending_stake.take(self.staking_epoch).unwrap_or(0);
+        }
+
+        /// A staker's position with rewards of closed epochs credited and stake that has
+        /// since become active moved out of pending
+        fn synced_stake(&self, staker: AccountId) -> StakeInfo {
+            let mut stake = self.stakes.get(staker).unwrap_or_default();
+
+            let earned = stake.active.saturating_mul(self.reward_per_token - stake.reward_per_token_paid);
+            stake.rewards += earned / REWARD_PRECISION;
 
-            Ok(race_id)
+            if stake.pending > 0 && stake.pending_epoch <= self.staking_epoch {
+                let start = self.epoch_reward_per_token.get(stake.pending_epoch).unwrap_or(0);
+                let earned = stake.pending.saturating_mul(self.reward_per_token - start);
+                stake.rewards += earned / REWARD_PRECISION;
+                stake.active += stake.pending;
+                stake.pending = 0;
+            }
+
+            stake.reward_per_token_paid = self.reward_per_token;
+            stake
+        }
+
+        fn sync_stake(&mut self, staker: AccountId) -> StakeInfo {
+            let stake = self.synced_stake(staker);
+            self.stakes.insert(staker, &stake);
+            stake
+        }
+
+        /// Add stake pending for the next epoch, returning the epoch it becomes active in
+        fn add_stake(&mut self, staker: AccountId, amount: Balance) -> u32 {
+            let mut stake = self.sync_stake(staker);
+            let active_from_epoch = self.staking_epoch + 1;
+            stake.pending += amount;
+            stake.pending_epoch = active_from_epoch;
+            self.stakes.insert(staker, &stake);
+
+            let pending = self.pending_stake.get(active_from_epoch).unwrap_or(0);
+            self.pending_stake.insert(active_from_epoch, &(pending + amount));
+
+            active_from_epoch
+        }
+
+        /// Take stake out of the vault, pending stake first
+        fn remove_stake(&mut self, staker: AccountId, amount: Balance) -> Result<(), Error> {
+            let mut stake = self.sync_stake(staker);
+
+            if amount == 0 || amount > stake.active + stake.pending {
+                return Err(Error::InsufficientStake);
+            }
+
+            let from_pending = amount.min(stake.pending);
+            if from_pending > 0 {
+                stake.pending -= from_pending;
+                let pending = self.pending_stake.get(stake.pending_epoch).unwrap_or(0);
+                self.pending_stake.insert(stake.pending_epoch, &(pending - from_pending));
+            }
+
+            let from_active = amount - from_pending;
+            stake.active -= from_active;
+            self.total_staked -= from_active;
+            self.stakes.insert(staker, &stake);
+
+            Ok(())
+        }
+
+        fn start_upgrade_timelock(&mut self, code_hash: Hash) -> Result<(), Error> {
+            let eta_block = self.env().block_number() + UPGRADE_DELAY_BLOCKS;
+            self.pending_upgrade = Some((code_hash, eta_block));
+
+            self.env().emit_event(UpgradeScheduled {
+                code_hash,
+                eta_block,
+            });
+
+            Ok(())
+        }
+
+        fn stop_upgrade(&mut self) -> Result<(), Error> {
+            let (code_hash, _) = self.pending_upgrade.take().ok_or(Error::NoUpgradeScheduled)?;
+
+            self.env().emit_event(UpgradeCancelled { code_hash });
+
+            Ok(())
+        }
+
+        fn apply_upgrade(&mut self, code_hash: Hash) -> Result<(), Error> {
+            let (scheduled, eta_block) = self.pending_upgrade.ok_or(Error::NoUpgradeScheduled)?;
+
+            if scheduled != code_hash {
+                return Err(Error::UpgradeMismatch);
+            }
+
+            if self.env().block_number() < eta_block {
+                return Err(Error::UpgradeTimelocked {
+                    executable_at: eta_block,
+                });
+            }
+
+            self.pending_upgrade = None;
+
+            if self.env().set_code_hash(&code_hash).is_err() {
+                return Err(Error::UpgradeFailed);
+            }
+
+            self.env().emit_event(Upgraded { code_hash });
+
+            Ok(())
+        }
+
+        fn start_emergency_withdraw(&mut self) -> Result<u32, Error> {
+            if self.emergency_withdraw_at.is_some() {
+                return Err(Error::EmergencyWithdrawPending);
+            }
+
+            let executable_at = self.env().block_number() + EMERGENCY_DELAY_BLOCKS;
+            self.emergency_withdraw_at = Some(executable_at);
+
+            self.env().emit_event(EmergencyWithdrawInitiated { executable_at });
+
+            Ok(executable_at)
+        }
+
+        fn stop_emergency_withdraw(&mut self) -> Result<(), Error> {
+            if self.emergency_withdraw_at.take().is_none() {
+                return Err(Error::NoEmergencyWithdraw);
+            }
+
+            self.env().emit_event(EmergencyWithdrawCancelled {});
+
+            Ok(())
+        }
+
+        fn complete_emergency_withdraw(&mut self, to: AccountId) -> Result<Balance, Error> {
+            let executable_at = self.emergency_withdraw_at.ok_or(Error::NoEmergencyWithdraw)?;
+            if self.env().block_number() < executable_at {
+                return Err(Error::UpgradeTimelocked { executable_at });
+            }
+
+            let amount = self.env().balance().saturating_sub(self.race_liabilities());
+            self.emergency_withdraw_at = None;
+
+            if self.env().transfer(to, amount).is_err() {
+                return Err(Error::TransferFailed);
+            }
+
+            self.env().emit_event(EmergencyWithdrawFinalized { to, amount });
+
+            Ok(amount)
+        }
+
+        /// Sensitive owner messages are disabled while a multisig is enabled
+        fn ensure_no_multisig(&self) -> Result<(), Error> {
+            if self.approval_threshold > 0 {
+                return Err(Error::ApprovalRequired);
+            }
+            Ok(())
+        }
+
+        fn ensure_signer(&self) -> Result<AccountId, Error> {
+            let caller = self.env().caller();
+            if self.approval_threshold == 0 || !self.signers.contains(&caller) {
+                return Err(Error::NotSigner);
+            }
+            Ok(caller)
+        }
+
+        fn set_signers(&mut self, mut signers: Vec<AccountId>, threshold: u32) -> Result<(), Error> {
+            signers.sort();
+            signers.dedup();
+
+            if signers.len() > MAX_SIGNERS || threshold == 0 || threshold as usize > signers.len() {
+                return Err(Error::InvalidThreshold);
+            }
+
+            self.signers = signers.clone();
+            self.approval_threshold = threshold;
+
+            self.env().emit_event(SignersUpdated { signers, threshold });
+
+            Ok(())
+        }
+
+        fn execute_action(&mut self, action: OwnerAction) -> Result<(), Error> {
+            match action {
+                OwnerAction::SetHouseFee(fee_bps) => {
+                    self.ensure_ungoverned()?;
+                    self.apply_parameter(ParameterChange::HouseFee(fee_bps))
+                }
+                OwnerAction::ScheduleUpgrade(code_hash) => self.start_upgrade_timelock(code_hash),
+                OwnerAction::CancelUpgrade => self.stop_upgrade(),
+                OwnerAction::Upgrade(code_hash) => self.apply_upgrade(code_hash),
+                OwnerAction::InitiateEmergencyWithdraw => self.start_emergency_withdraw().map(|_| ()),
+                OwnerAction::CancelEmergencyWithdraw => self.stop_emergency_withdraw(),
+                OwnerAction::FinalizeEmergencyWithdraw(to) => self.complete_emergency_withdraw(to).map(|_| ()),
+                OwnerAction::SetSigners(signers, threshold) => self.set_signers(signers, threshold),
+            }
+        }
+
+        /// With lazy progression on, run one `update_race` catch-up, bounded by
+        /// `MAX_CATCHUP_BLOCKS`, on the first running race that is behind the chain
+        fn progress_active_race(&mut self) -> Result<(), Error> {
+            if !self.lazy_progression {
+                return Ok(());
+            }
+
+            let now = self.env().block_number();
+            let behind = (0..self.current_race_id)
+                .filter_map(|race_id| self.races.get(race_id))
+                .find(|race| race.status == RaceStatus::Active && race.oracle.is_none() && now > race.current_block);
+            match behind {
+                Some(race) => self.update_race(race.id),
+                None => Ok(()),
+            }
+        }
+
+        /// Ensure the caller holds the Owner role
+        fn ensure_owner(&self) -> Result<(), Error> {
+            self.ensure_role(Role::Owner)
+        }
+
+        /// Ensure the caller holds `role`, or the Owner role
+        fn ensure_role(&self, role: Role) -> Result<(), Error> {
+            match self.roles.get(self.env().caller()) {
+                Some(held) if held == role || held == Role::Owner => Ok(()),
+                _ => Err(Error::Unauthorized),
+            }
+        }
+
+        /// Fold the race's revealed bettor entropy and bet entropy into a 32-bit seed
+        fn entropy_seed(&self, race_id: u8) -> u32 {
+            let mut entropy = self.race_entropy.get(race_id).unwrap_or([0u8; 32]);
+
+            // entropy = blake2_256(entropy ++ bet entropy), once anyone has bet
+            if let Some(bet_entropy) = self.bet_entropy.get(race_id) {
+                let mut input = Vec::with_capacity(64);
+                input.extend_from_slice(&entropy);
+                input.extend_from_slice(&bet_entropy);
+                entropy = self.env().hash_bytes::<Blake2x256>(&input);
+            }
+
+            u32::from_le_bytes([entropy[0], entropy[1], entropy[2], entropy[3]])
+        }
+
+        /// Fold a bet into the race's bet entropy:
+        /// blake2_256(bet entropy ++ bettor ++ amount ++ block number). Bets close before
+        /// the start, so the accumulator is fixed for the whole simulation.
+        fn mix_bet_entropy(&mut self, race_id: u8, bettor: AccountId, amount: Balance) {
+            let mut input = Vec::with_capacity(84);
+            input.extend_from_slice(&self.bet_entropy.get(race_id).unwrap_or([0u8; 32]));
+            input.extend_from_slice(bettor.as_ref());
+            input.extend_from_slice(&amount.to_le_bytes());
+            input.extend_from_slice(&self.env().block_number().to_le_bytes());
+            let entropy = self.env().hash_bytes::<Blake2x256>(&input);
+            self.bet_entropy.insert(race_id, &entropy);
+        }
+
+        /// Fisher-Yates shuffle of the horse ids, swap `i` picked by blake2_256(seed ++ i)
+        fn draw_post_positions(&self, seed: &[u8; 32], field_size: u8) -> Vec<u8> {
+            let mut positions: Vec<u8> = (0..field_size).collect();
+            for i in (1..positions.len()).rev() {
+                let mut input = seed.to_vec();
+                input.push(i as u8);
+                let hash = self.env().hash_bytes::<Blake2x256>(&input);
+                let pick = u32::from_le_bytes([hash[0], hash[1], hash[2], hash[3]]) as usize % (i + 1);
+                positions.swap(i, pick);
+            }
+            positions
+        }
+
+        /// Soft going affinity of a horse, 0-100, taken from the race's draw seed
+        fn affinity(seed: &[u8; 32], horse_id: u8) -> u8 {
+            seed[horse_id as usize % 32] % 101
+        }
+
+        /// Pick distinct registered horses for every slot of a field from the draw seed.
+        /// Empty while the registry is too small to fill the field.
+        fn draw_entrants(&self, seed: &[u8; 32], field_size: u8) -> Vec<u32> {
+            let registered = self.horse_career_count;
+            if registered < field_size as u32 {
+                return Vec::new();
+            }
+
+            let mut entrants: Vec<u32> = Vec::new();
+            for i in 0..field_size as usize {
+                let roll = u32::from_le_bytes([seed[i % 32], seed[(i + 1) % 32], seed[(i + 2) % 32], seed[(i + 3) % 32]]);
+                let mut pick = roll % registered;
+                while entrants.contains(&pick) {
+                    pick = (pick + 1) % registered;
+                }
+                entrants.push(pick);
+            }
+            entrants
+        }
+
+        /// Add a finished race to the careers of its registered runners
+        fn record_careers(&mut self, race: &Race) {
+            for (rank, horse_id) in race.rankings.iter().enumerate() {
+                let entrant = self.race_entrants.get((race.id, *horse_id));
+                let mut career = match entrant.and_then(|(career_id, _)| self.horse_careers.get(career_id)) {
+                    Some(career) => career,
+                    None => continue,
+                };
+
+                // Horses sharing first place are all credited with the win
+                let position = if race.winners.contains(horse_id) { 1 } else { rank as u8 + 1 };
+                career.starts += 1;
+                career.experience += 1;
+                if position == 1 {
+                    career.wins += 1;
+                    career.experience += 2;
+                } else if position <= 3 {
+                    career.places += 1;
+                    career.experience += 1;
+                }
+
+                career.form.push(position);
+                if career.form.len() > FORM_LENGTH {
+                    career.form.remove(0);
+                }
+                self.horse_careers.insert(career.id, &career);
+            }
+        }
+
+        /// Weight a registered horse carries for the wins in its form line
+        fn handicap(career: &HorseCareer) -> u8 {
+            let wins = career.form.iter().filter(|position| **position == 1).count() as u8;
+            wins.saturating_mul(HANDICAP_PER_WIN).min(MAX_HANDICAP)
+        }
+
+        fn fresh_horse(id: u8) -> Horse {
+            Horse {
+                id,
+                position: 0,
+                finished: false,
+                finish_time: None,
+                scratched: false,
+                last_velocity: 0,
+                last_update_block: 0,
+                affinity: 0,
+                experience: 0,
+                handicap: 0,
+                metadata: None,
+            }
+        }
+
+        /// Acceleration of one horse in one step under a given algorithm version.
+        /// Earlier versions must stay here unchanged so old races remain verifiable.
+        fn acceleration(
+            version: u16,
+            horse: &Horse,
+            block: u32,
+            timestamp: u64,
+            entropy: u32,
+            condition: TrackCondition,
+        ) -> Option<u32> {
+            let (horse_id, affinity) = (horse.id, horse.affinity);
+            match version {
+                // v1: uniform pseudo-random acceleration of 15-35 units
+                1 => Some(Self::pseudo_random(horse_id, block, timestamp, entropy) % 21 + 15),
+                // v2: as v1, seeded by block and entropy only so caught-up blocks are deterministic
+                2 => Some(Self::pseudo_random(horse_id, block, 0, entropy) % 21 + 15),
+                // v3: as v2, with softer going slowing the field and favouring horses with affinity
+                3 => {
+                    let roll = Self::pseudo_random(horse_id, block, 0, entropy);
+                    let (base, spread, bonus) = match condition {
+                        TrackCondition::Fast => (15, 21, 0),
+                        TrackCondition::Good => (14, 21, 2),
+                        TrackCondition::Muddy => (12, 19, 6),
+                        TrackCondition::Sloppy => (10, 19, 8),
+                    };
+                    Some(roll % spread + base + bonus * affinity as u32 / 100)
+                }
+                // v4: as v3, plus one unit per experience level of a registered horse
+                4 => Some(Self::acceleration(3, horse, block, timestamp, entropy, condition)? + horse.experience as u32),
+                // v5: as v4, less one unit per handicap weight carried for recent wins
+                5 => {
+                    let acceleration = Self::acceleration(4, horse, block, timestamp, entropy, condition)?;
+                    Some(acceleration.saturating_sub(horse.handicap as u32))
+                }
+                // v6: as v5; runners level on distance or finish block are ranked by photo finish
+                6 => Self::acceleration(5, horse, block, timestamp, entropy, condition),
+                _ => None,
+            }
+        }
+
+        /// Sort runners into finishing order. Before simulation v6 runners level on
+        /// distance or finish block keep their horse id order; from v6 they are split by
+        /// `photo_finish_key` at the race's last simulated block.
+        fn rank_horses(horses: &mut [Horse], race: &Race, block: u32) {
+            let photo_finish = race.sim_version >= 6;
+            horses.sort_by(|a, b| {
+                let order = match (a.finished, b.finished) {
+                    (true, true) => a.finish_time.cmp(&b.finish_time),
+                    (true, false) => core::cmp::Ordering::Less,
+                    (false, true) => core::cmp::Ordering::Greater,
+                    (false, false) => b.position.cmp(&a.position),
+                };
+                if photo_finish {
+                    order.then_with(|| {
+                        Self::photo_finish_key(race.id, a.id, block).cmp(&Self::photo_finish_key(race.id, b.id, block))
+                    })
+                } else {
+                    order
+                }
+            });
+        }
+
+        /// Tiebreak for level runners: the Blake2x256 hash of (race id, horse id, block),
+        /// lowest first. Anyone can recompute it from the race's public data.
+        fn photo_finish_key(race_id: u8, horse_id: u8, block: u32) -> [u8; 32] {
+            let mut key = [0u8; 32];
+            ink::env::hash_encoded::<Blake2x256, _>(&(race_id, horse_id, block), &mut key);
+            key
+        }
+
+        /// Dead heat: every horse finishing in the same block as the leader shares first place
+        fn dead_heat_winners(ranked: &[Horse]) -> Vec<u8> {
+            let leader = &ranked[0];
+            ranked
+                .iter()
+                .take_while(|horse| {
+                    horse.id == leader.id
+                        || (leader.finished && horse.finished && horse.finish_time == leader.finish_time)
+                })
+                .map(|horse| horse.id)
+                .collect()
+        }
+
+        /// Pseudo-random number generator
+        fn pseudo_random(seed: u8, block: u32, timestamp: u64, entropy: u32) -> u32 {
+            #[cfg(test)]
+            if let Some(roll) = tests::scripted_roll(seed, block) {
+                return roll % 100;
+            }
+
+            let hash = timestamp as u32 
+                ^ block 
+                ^ (seed as u32)
+                ^ entropy;
+            
+            // Simple LCG algorithm
+            let a: u32 = 1664525;
+            let c: u32 = 1013904223;
+            (a.wrapping_mul(hash).wrapping_add(c)) % 100
+        }
+    }
+
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Error {
+        RaceNotFound,
+        RaceNotActive,
+        RaceAlreadyStarted,
+        RaceNotFinished,
+        MaxRacesReached,
+        // Horse ids run from 0 to `max`
+        InvalidHorse { max: u8 },
+        // Betting on the market closed, or closes, at block `closes_at`
+        BettingClosed { closes_at: u32 },
+        InvalidBetAmount,
+        NoWinner,
+        NoWinningBets,
+        AlreadyClaimed,
+        TransferFailed,
+        Unauthorized,
+        RaceNotCancellable,
+        RaceNotCancelled,
+        NothingToRefund,
+        NoUpgradeScheduled,
+        UpgradeMismatch,
+        UpgradeTimelocked { executable_at: u32 },
+        UpgradeFailed,
+        AlreadyMigrated,
+        HorseOnHold,
+        HorseNotOnHold,
+        NotEnoughRunners,
+        FeeTooHigh { max_bps: u16 },
+        InsufficientFees,
+        CannotChangeOwnerRole,
+        RoleNotFound,
+        LossCapReached { cap: Balance },
+        InvalidProbabilities,
+        OrganizerNotFound,
+        InsufficientBond,
+        OrganizerHasOpenRaces,
+        PoolLimitReached { max_pool: Balance },
+        RaceNotSettled,
+        ArchiveGracePeriod { archivable_at: u32 },
+        UnclaimedPayouts,
+        AlreadyCommitted,
+        NoCommitment,
+        InvalidReveal,
+        ClaimCodeActive,
+        InvalidClaimCode,
+        ClaimCodeExpired { expired_at: u32 },
+        UnsupportedSimVersion,
+        MissingReplayData,
+        BetAboveLimit { max_bet: Balance },
+        InvalidReferrer,
+        NothingToClaim,
+        InvalidSchedule,
+        HorseNotRunning,
+        CardNotFound,
+        InvalidCard,
+        InvalidCardStrategy,
+        CoolingOff { until: u32 },
+        StreakAcknowledgementRequired,
+        NoStreakToAcknowledge,
+        MathOverflow,
+        TooManyPromotions,
+        PromotionNotFound,
+        InvalidSignature,
+        InvalidNonce,
+        OrderExpired,
+        InsufficientDeposit,
+        InvalidSlip,
+        InvalidParlay,
+        ParlayNotFound,
+        ParlayUndecided,
+        AccountFrozen,
+        AccountNotFrozen,
+        MetadataTooLong,
+        AlreadyPicked,
+        TooManyMirrors,
+        EmergencyWithdrawPending,
+        NoEmergencyWithdraw,
+        ConsolationUnavailable,
+        RedemptionDisabled,
+        InsufficientPoints,
+        InvalidFieldSize { min: u8, max: u8 },
+        WrongBetMode,
+        InvalidOdds,
+        OddsNotPosted,
+        ReserveExceeded,
+        InvalidTournament,
+        TournamentNotFound,
+        TournamentUndecided,
+        Governed,
+        GovernanceNotEnabled,
+        ProposalNotFound,
+        VotingClosed,
+        VotingOpen,
+        AlreadyVoted,
+        BelowProposalThreshold,
+        TokenCallFailed,
+        NoVotingPower,
+        PoolCapReached,
+        InvalidCombination,
+        TicketNotFound,
+        StakingNotEnabled,
+        InsufficientStake,
+        TooManyBets { max_bets: u32 },
+        // The next bet window opens at block `retry_at`
+        BetRateLimited { retry_at: u32 },
+        ClaimExpired { expired_at: u32 },
+        ClaimWindowOpen,
+        ApprovalRequired,
+        NotSigner,
+        ActionNotFound,
+        AlreadyConfirmed,
+        InvalidThreshold,
+        ActionAlreadyExecuted,
+        OracleSettled,
+        InvalidRankings,
+        SettleTimeoutPending,
+        AccountRestricted,
+        AccountNotRestricted,
+        Insolvent,
+        InvalidPayoutCap,
+        AssetNotApproved,
+        TooManyAssets,
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use core::cell::RefCell;
+        use ink::env::{test, DefaultEnvironment};
+
+        /// Rolls the simulation draws in tests in place of `pseudo_random`, given the roll's
+        /// seed (a horse id, or the race id for the track condition) and block
+        pub(super) trait RollSource {
+            fn roll(&self, seed: u8, block: u32) -> u32;
+        }
+
+        impl<F: Fn(u8, u32) -> u32> RollSource for F {
+            fn roll(&self, seed: u8, block: u32) -> u32 {
+                self(seed, block)
+            }
+        }
+
+        std::thread_local! {
+            static ROLLS: RefCell<Option<Box<dyn RollSource>>> = RefCell::new(None);
+        }
+
+        pub(super) fn scripted_roll(seed: u8, block: u32) -> Option<u32> {
+            ROLLS.with(|rolls| rolls.borrow().as_ref().map(|source| source.roll(seed, block)))
+        }
+
+        fn script_rolls(source: impl RollSource + 'static) {
+            ROLLS.with(|rolls| *rolls.borrow_mut() = Some(Box::new(source)));
+        }
+
+        /// Script the rolls so `winners` run 35 units a block on a fast track and the rest 15,
+        /// with the winners dead-heating when there is more than one
+        fn favour(winners: &'static [u8]) {
+            script_rolls(move |seed: u8, _| if winners.contains(&seed) { 20 } else { 0 });
+        }
+
+        fn accounts() -> test::DefaultAccounts<DefaultEnvironment> {
+            test::default_accounts::<DefaultEnvironment>()
+        }
+
+        fn balance(account: AccountId) -> Balance {
+            test::get_account_balance::<DefaultEnvironment>(account).unwrap_or(0)
+        }
+
+        fn set_caller(account: AccountId) {
+            test::set_caller::<DefaultEnvironment>(account);
+            test::set_value_transferred::<DefaultEnvironment>(0);
+        }
+
+        fn advance_blocks(blocks: u32) {
+            for _ in 0..blocks {
+                test::advance_block::<DefaultEnvironment>();
+            }
+        }
+
+        /// Deploy the platform with alice as owner
+        fn deploy() -> KareraPlatform {
+            set_caller(accounts().alice);
+            KareraPlatform::new()
+        }
+
+        /// Create a race of `field_size` horses on a fast track, as alice
+        fn create_race(platform: &mut KareraPlatform, field_size: u8) -> u8 {
+            set_caller(accounts().alice);
+            let race_id = platform.create_race(field_size).expect("race created");
+            platform.set_track_condition(race_id, TrackCondition::Fast).expect("track set");
+            race_id
+        }
+
+        /// Place a bet as `bettor`, moving `amount` into the contract's balance
+        fn bet(platform: &mut KareraPlatform, bettor: AccountId, race_id: u8, horse_id: u8, amount: Balance) -> Result<(), Error> {
+            let contract = test::callee::<DefaultEnvironment>();
+            test::set_account_balance::<DefaultEnvironment>(contract, balance(contract) + amount);
+            test::set_caller::<DefaultEnvironment>(bettor);
+            test::set_value_transferred::<DefaultEnvironment>(amount);
+            let result = platform.place_bet(race_id, horse_id);
+            test::set_value_transferred::<DefaultEnvironment>(0);
+            result
+        }
+
+        /// Place a parlay as `bettor`, moving `amount` into the contract's balance
+        fn parlay(platform: &mut KareraPlatform, bettor: AccountId, legs: Vec<(u8, u8)>, amount: Balance) -> u32 {
+            let contract = test::callee::<DefaultEnvironment>();
+            test::set_account_balance::<DefaultEnvironment>(contract, balance(contract) + amount);
+            test::set_caller::<DefaultEnvironment>(bettor);
+            test::set_value_transferred::<DefaultEnvironment>(amount);
+            let parlay_id = platform.place_parlay(legs).expect("parlay placed");
+            test::set_value_transferred::<DefaultEnvironment>(0);
+            parlay_id
+        }
+
+        /// Start a race as alice and update it a block at a time until it finishes
+        fn run_race(platform: &mut KareraPlatform, race_id: u8) -> Race {
+            set_caller(accounts().alice);
+            platform.start_race(race_id).expect("race started");
+
+            for _ in 0..=RACE_DURATION_BLOCKS {
+                advance_blocks(1);
+                platform.update_race(race_id).expect("race updated");
+                let race = platform.get_race(race_id).expect("race exists");
+                if race.status == RaceStatus::Finished {
+                    return race;
+                }
+            }
+            panic!("race {race_id} did not finish");
+        }
+
+        /// Claim as `bettor`, returning the payout and the balance it moved
+        fn claim(platform: &mut KareraPlatform, bettor: AccountId, race_id: u8) -> Result<(Balance, Balance), Error> {
+            set_caller(bettor);
+            let before = balance(bettor);
+            let payout = platform.claim_winnings(race_id)?;
+            Ok((payout, balance(bettor) - before))
+        }
+
+        #[test]
+        fn mul_div_matches_plain_math_for_small_values() {
+            assert_eq!(mul_div(300, 700, 1_000), Ok(210));
+            assert_eq!(mul_div(7, 3, 2), Ok(10));
+            assert_eq!(mul_div(0, u128::MAX, 1), Ok(0));
+        }
+
+        #[test]
+        fn mul_div_handles_products_wider_than_u128() {
+            assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX), Ok(u128::MAX));
+            assert_eq!(mul_div(u128::MAX, 2, 4), Ok(u128::MAX / 2));
+            assert_eq!(mul_div(1 << 100, 1 << 100, 1 << 90), Ok(1 << 110));
+            assert_eq!(mul_div(u128::MAX, 9_000, BPS_DENOMINATOR), Ok(306_254_130_228_844_617_117_037_146_688_591_390_309));
+        }
+
+        #[test]
+        fn mul_div_rejects_overflowing_quotient() {
+            assert_eq!(mul_div(u128::MAX, 2, 1), Err(Error::MathOverflow));
+            assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX - 1), Err(Error::MathOverflow));
+        }
+
+        #[test]
+        fn mul_div_rejects_zero_divisor() {
+            assert_eq!(mul_div(1, 1, 0), Err(Error::MathOverflow));
+        }
+
+        #[test]
+        fn widening_mul_splits_high_and_low_halves() {
+            assert_eq!(widening_mul(u128::MAX, u128::MAX), (u128::MAX - 1, 1));
+            assert_eq!(widening_mul(1 << 127, 2), (1, 0));
+            assert_eq!(widening_mul(12, 12), (0, 144));
+        }
+
+        #[ink::test]
+        fn full_cycle_pays_the_winning_backer_the_whole_pool() {
+            let (bob, charlie) = (accounts().bob, accounts().charlie);
+            let mut platform = deploy();
+            favour(&[0]);
+
+            let race_id = create_race(&mut platform, 4);
+            bet(&mut platform, bob, race_id, 0, 100).unwrap();
+            bet(&mut platform, charlie, race_id, 1, 300).unwrap();
+            assert_eq!(platform.get_total_pool(race_id), 400);
+
+            let race = run_race(&mut platform, race_id);
+            assert_eq!(race.winners, vec![0]);
+            assert_eq!(race.rankings[0], 0);
+
+            assert_eq!(claim(&mut platform, bob, race_id), Ok((400, 400)));
+            assert_eq!(claim(&mut platform, bob, race_id), Err(Error::AlreadyClaimed));
+            assert_eq!(claim(&mut platform, charlie, race_id), Err(Error::NoWinningBets));
+        }
+
+        #[ink::test]
+        fn winners_split_the_net_pool_pro_rata_after_the_house_fee() {
+            let (bob, charlie, django) = (accounts().bob, accounts().charlie, accounts().django);
+            let mut platform = deploy();
+            platform.set_house_fee(500).unwrap();
+            favour(&[0]);
+
+            let race_id = create_race(&mut platform, 4);
+            bet(&mut platform, bob, race_id, 0, 100).unwrap();
+            bet(&mut platform, charlie, race_id, 0, 300).unwrap();
+            bet(&mut platform, django, race_id, 1, 400).unwrap();
+            run_race(&mut platform, race_id);
+
+            // 800 pool less a 5% fee leaves 760 for 400 of winning stake
+            assert_eq!(claim(&mut platform, bob, race_id), Ok((190, 190)));
+            assert_eq!(claim(&mut platform, charlie, race_id), Ok((570, 570)));
+        }
+
+        #[ink::test]
+        fn dead_heat_shares_the_pool_between_tied_horses() {
+            let (bob, charlie, django) = (accounts().bob, accounts().charlie, accounts().django);
+            let mut platform = deploy();
+            favour(&[0, 1]);
+
+            let race_id = create_race(&mut platform, 4);
+            bet(&mut platform, bob, race_id, 0, 100).unwrap();
+            bet(&mut platform, charlie, race_id, 1, 300).unwrap();
+            bet(&mut platform, django, race_id, 2, 400).unwrap();
+
+            let race = run_race(&mut platform, race_id);
+            assert_eq!(race.winners.len(), 2);
+            assert!(race.winners.contains(&0) && race.winners.contains(&1));
+
+            assert_eq!(claim(&mut platform, bob, race_id), Ok((200, 200)));
+            assert_eq!(claim(&mut platform, charlie, race_id), Ok((600, 600)));
+        }
+
+        #[ink::test]
+        fn unbacked_winner_rolls_the_pool_into_the_jackpot() {
+            let bob = accounts().bob;
+            let mut platform = deploy();
+            favour(&[0]);
+
+            let race_id = create_race(&mut platform, 4);
+            bet(&mut platform, bob, race_id, 1, 250).unwrap();
+            run_race(&mut platform, race_id);
+
+            assert_eq!(claim(&mut platform, bob, race_id), Err(Error::NoWinningBets));
+            assert_eq!(platform.get_jackpot(), 250);
+
+            // The next platform race opens with the jackpot in its pool
+            let next = create_race(&mut platform, 4);
+            assert_eq!(platform.get_total_pool(next), 250);
+            assert_eq!(platform.get_jackpot(), 0);
+        }
+
+        #[ink::test]
+        fn race_without_bets_settles_with_nothing_owed() {
+            let bob = accounts().bob;
+            let mut platform = deploy();
+            favour(&[2]);
+
+            let race_id = create_race(&mut platform, 3);
+            let race = run_race(&mut platform, race_id);
+
+            assert_eq!(race.winners, vec![2]);
+            assert_eq!(platform.get_total_pool(race_id), 0);
+            assert_eq!(platform.get_jackpot(), 0);
+            assert_eq!(claim(&mut platform, bob, race_id), Err(Error::NoWinningBets));
+        }
+
+        #[ink::test]
+        fn cancelled_race_refunds_every_stake_once() {
+            let (bob, charlie) = (accounts().bob, accounts().charlie);
+            let mut platform = deploy();
+
+            let race_id = create_race(&mut platform, 4);
+            bet(&mut platform, bob, race_id, 0, 100).unwrap();
+            bet(&mut platform, bob, race_id, 2, 50).unwrap();
+            bet(&mut platform, charlie, race_id, 1, 300).unwrap();
+
+            set_caller(accounts().alice);
+            platform.cancel_race(race_id).unwrap();
+
+            set_caller(bob);
+            let before = balance(bob);
+            assert_eq!(platform.claim_refund(race_id), Ok(150));
+            assert_eq!(balance(bob) - before, 150);
+            assert_eq!(platform.claim_refund(race_id), Err(Error::AlreadyClaimed));
+            assert_eq!(platform.claim_winnings(race_id), Err(Error::RaceNotFinished));
+
+            set_caller(charlie);
+            assert_eq!(platform.claim_refund(race_id), Ok(300));
+        }
+
+        #[ink::test]
+        fn betting_closes_at_the_cutoff_before_the_scheduled_start() {
+            let bob = accounts().bob;
+            let mut platform = deploy();
+
+            let race_id = create_race(&mut platform, 4);
+            let race = platform.get_race(race_id).unwrap();
+            let closes_at = race.scheduled_start - DEFAULT_BETTING_CUTOFF_BLOCKS;
+
+            advance_blocks(closes_at - ink::env::block_number::<DefaultEnvironment>() - 1);
+            assert_eq!(bet(&mut platform, bob, race_id, 0, 100), Ok(()));
+
+            advance_blocks(1);
+            assert_eq!(bet(&mut platform, bob, race_id, 0, 100), Err(Error::BettingClosed { closes_at }));
+            assert_eq!(
+                bet(&mut platform, bob, race_id, 4, 100),
+                Err(Error::BettingClosed { closes_at })
+            );
+        }
+
+        #[ink::test]
+        fn invalid_horse_reports_the_highest_horse_id() {
+            let bob = accounts().bob;
+            let mut platform = deploy();
+
+            let race_id = create_race(&mut platform, 4);
+            assert_eq!(bet(&mut platform, bob, race_id, 4, 100), Err(Error::InvalidHorse { max: 3 }));
         }
 
-        /// Start a race
-        #[ink(message)]
-        pub fn start_race(&mut self, race_id: u8) -> Result<(), Error> {
-            let caller = self.env().caller();
-            if caller != self.owner {
-                return Err(Error::Unauthorized);
+        #[ink::test]
+        fn truncated_payouts_leave_rounding_dust_swept_after_the_last_claim() {
+            let accounts = accounts();
+            let mut platform = deploy();
+            favour(&[0]);
+
+            let race_id = create_race(&mut platform, 4);
+            for bettor in [accounts.bob, accounts.charlie, accounts.django] {
+                bet(&mut platform, bettor, race_id, 0, 1).unwrap();
             }
+            bet(&mut platform, accounts.eve, race_id, 1, 1).unwrap();
+            run_race(&mut platform, race_id);
 
-            let mut race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
-            
-            if race.status != RaceStatus::Pending {
-                return Err(Error::RaceAlreadyStarted);
+            // 4 over 3 winning units truncates to 1 each, leaving 1 behind
+            assert_eq!(claim(&mut platform, accounts.bob, race_id), Ok((1, 1)));
+            assert_eq!(claim(&mut platform, accounts.charlie, race_id), Ok((1, 1)));
+            assert_eq!(platform.get_rounding_dust(race_id), 0);
+            assert_eq!(claim(&mut platform, accounts.django, race_id), Ok((1, 1)));
+            assert_eq!(platform.get_rounding_dust(race_id), 1);
+            assert_eq!(platform.get_race_unclaimed(race_id), (0, 0));
+        }
+
+        #[ink::test]
+        fn winning_parlay_pays_the_stake_at_every_legs_odds_out_of_house_fees() {
+            let (bob, charlie, django, eve) = (accounts().bob, accounts().charlie, accounts().django, accounts().eve);
+            let mut platform = deploy();
+            platform.accrued_fees = 10_000;
+            favour(&[0]);
+
+            let (first, second) = (create_race(&mut platform, 4), create_race(&mut platform, 4));
+            for race_id in [first, second] {
+                bet(&mut platform, bob, race_id, 0, 100).unwrap();
+                bet(&mut platform, charlie, race_id, 1, 300).unwrap();
             }
+            let winning = parlay(&mut platform, django, vec![(first, 0), (second, 0)], 10);
+            let losing = parlay(&mut platform, eve, vec![(first, 1), (second, 0)], 100);
+            run_race(&mut platform, first);
+            run_race(&mut platform, second);
 
-            let current_block = self.env().block_number();
-            race.status = RaceStatus::Active;
-            race.start_block = current_block;
-            race.current_block = current_block;
+            // 49 times each stake is held back from the fees until the parlays settle
+            assert_eq!(platform.get_accrued_fees(), 10_000 - 490 - 4_900);
 
-            self.races.insert(race_id, &race);
+            // The losing stake is kept by the house
+            assert_eq!(platform.settle_parlay(losing), Ok(0));
+            assert_eq!(platform.get_accrued_fees(), 10_100 - 490);
 
-            self.env().emit_event(RaceStarted {
-                race_id,
-                start_block: current_block,
-            });
+            // 10 at 4x and 4x; the 150 won comes out of the house's fees
+            let before = balance(django);
+            assert_eq!(platform.settle_parlay(winning), Ok(160));
+            assert_eq!(balance(django) - before, 160);
+            assert_eq!(platform.get_accrued_fees(), 9_950);
+            assert_eq!(platform.settle_parlay(winning), Err(Error::AlreadyClaimed));
+        }
 
-            Ok(())
+        #[ink::test]
+        fn parlay_legs_on_cancelled_races_and_scratched_horses_are_void() {
+            let (bob, charlie, django, eve) = (accounts().bob, accounts().charlie, accounts().django, accounts().eve);
+            let mut platform = deploy();
+            platform.accrued_fees = 100_000;
+            favour(&[0]);
+
+            let (cancelled, scratched, decided) = (
+                create_race(&mut platform, 4),
+                create_race(&mut platform, 4),
+                create_race(&mut platform, 4),
+            );
+            bet(&mut platform, bob, decided, 0, 100).unwrap();
+            bet(&mut platform, charlie, decided, 1, 100).unwrap();
+            let parlay_id = parlay(&mut platform, django, vec![(cancelled, 1), (scratched, 2), (decided, 0)], 10);
+            let losing = parlay(&mut platform, eve, vec![(scratched, 1), (decided, 1)], 1_000);
+
+            set_caller(accounts().alice);
+            platform.cancel_race(cancelled).unwrap();
+            platform.scratch_horse(scratched, 2).unwrap();
+            run_race(&mut platform, scratched);
+            run_race(&mut platform, decided);
+
+            // Only the decided leg prices the parlay: 10 at 2x
+            assert_eq!(platform.settle_parlay(losing), Ok(0));
+            assert_eq!(platform.settle_parlay(parlay_id), Ok(20));
         }
 
-        /// Update race progress
-        #[ink(message)]
-        pub fn update_race(&mut self, race_id: u8) -> Result<(), Error> {
-            let mut race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
-            
-            if race.status != RaceStatus::Active {
-                return Err(Error::RaceNotActive);
-            }
+        #[ink::test]
+        fn parlay_is_undecided_until_every_leg_settles_unless_a_leg_lost() {
+            let django = accounts().django;
+            let mut platform = deploy();
+            platform.accrued_fees = 1_000;
+            favour(&[0]);
 
-            let current_block = self.env().block_number();
-            let blocks_elapsed = current_block - race.start_block;
+            let (first, second) = (create_race(&mut platform, 4), create_race(&mut platform, 4));
+            let pending = parlay(&mut platform, django, vec![(first, 0), (second, 0)], 10);
+            let lost = parlay(&mut platform, django, vec![(first, 1), (second, 0)], 10);
+            run_race(&mut platform, first);
 
-            // Check if race should end
-            if blocks_elapsed >= RACE_DURATION_BLOCKS {
-                return self.finish_race(race_id);
+            assert_eq!(platform.settle_parlay(pending), Err(Error::ParlayUndecided));
+            assert_eq!(platform.settle_parlay(lost), Ok(0));
+            // The lost stake and its released reserve go to the house, the pending reserve stays held
+            assert_eq!(platform.get_accrued_fees(), 1_000 - 490 + 10);
+        }
+
+        #[ink::test]
+        fn parlay_counts_against_the_loss_cap_and_season_pnl() {
+            let (bob, charlie, django) = (accounts().bob, accounts().charlie, accounts().django);
+            let mut platform = deploy();
+            platform.accrued_fees = 10_000;
+            favour(&[0]);
+
+            let (first, second) = (create_race(&mut platform, 4), create_race(&mut platform, 4));
+            for race_id in [first, second] {
+                bet(&mut platform, bob, race_id, 0, 100).unwrap();
+                bet(&mut platform, charlie, race_id, 1, 300).unwrap();
             }
+            set_caller(django);
+            platform.set_loss_cap(Some(30)).unwrap();
+            let parlay_id = parlay(&mut platform, django, vec![(first, 0), (second, 0)], 20);
 
-            // Update horse positions
-            let mut positions = Vec::new();
-            let mut all_finished = true;
+            set_caller(django);
+            test::set_value_transferred::<DefaultEnvironment>(20);
+            assert_eq!(platform.place_parlay(vec![(first, 0), (second, 0)]), Err(Error::LossCapReached { cap: 30 }));
 
-            for i in 0..HORSES_PER_RACE {
-                if let Some(mut horse) = self.horses.get((race_id, i)) {
-                    if !horse.finished {
-                        // Generate pseudo-random acceleration (15-35 units)
-                        let acceleration = self.pseudo_random(horse.id, current_block) % 21 + 15;
-                        horse.position += acceleration;
-
-                        // Check if horse finished
-                        if horse.position >= FINISH_LINE {
-                            horse.position = FINISH_LINE;
-                            horse.finished = true;
-                            horse.finish_time = Some(blocks_elapsed);
-                            race.rankings.push(horse.id);
-                        } else {
-                            all_finished = false;
-                        }
+            run_race(&mut platform, first);
+            run_race(&mut platform, second);
+            assert_eq!(platform.settle_parlay(parlay_id), Ok(320));
 
-                        positions.push((horse.id, horse.position));
-                        self.horses.insert((race_id, i), &horse);
-                    }
-                }
+            let season = platform.get_race(first).unwrap().season;
+            assert_eq!(platform.get_season_pnl(django, season), SeasonPnl { staked: 20, returned: 320 });
+        }
+
+        #[ink::test]
+        fn parlay_reserves_its_capped_payout_out_of_house_fees() {
+            let (bob, charlie, django) = (accounts().bob, accounts().charlie, accounts().django);
+            let mut platform = deploy();
+            platform.set_parlay_max_multiple(10).unwrap();
+            favour(&[0]);
+
+            let (first, second) = (create_race(&mut platform, 4), create_race(&mut platform, 4));
+            for race_id in [first, second] {
+                bet(&mut platform, bob, race_id, 0, 100).unwrap();
+                bet(&mut platform, charlie, race_id, 1, 300).unwrap();
             }
 
-            race.current_block = current_block;
-            self.races.insert(race_id, &race);
+            // Nothing in the fees to reserve 90 of winnings against
+            set_caller(django);
+            test::set_value_transferred::<DefaultEnvironment>(10);
+            assert_eq!(platform.place_parlay(vec![(first, 0), (second, 0)]), Err(Error::InsufficientFees));
 
-            self.env().emit_event(RaceUpdate {
-                race_id,
-                block_number: current_block,
-                positions,
-            });
+            platform.accrued_fees = 90;
+            let parlay_id = parlay(&mut platform, django, vec![(first, 0), (second, 0)], 10);
+            set_caller(accounts().alice);
+            assert_eq!(platform.withdraw_fees(accounts().alice, 1), Err(Error::InsufficientFees));
+            run_race(&mut platform, first);
+            run_race(&mut platform, second);
 
-            // If all horses finished, end race
-            if all_finished {
-                self.finish_race(race_id)?;
+            // 10 at 4x and 4x, capped at ten times the stake
+            assert_eq!(platform.settle_parlay(parlay_id), Ok(100));
+            assert_eq!(platform.get_accrued_fees(), 0);
+        }
+
+        #[ink::test]
+        fn trifecta_box_is_bet_priced_and_claimed_as_a_market() {
+            let (bob, charlie) = (accounts().bob, accounts().charlie);
+            let mut platform = deploy();
+            script_rolls(|seed: u8, _| [20, 15, 10, 0].get(seed as usize).copied().unwrap_or(0));
+
+            let race_id = create_race(&mut platform, 4);
+            let market = |horses: Vec<u8>| MarketId {
+                race_id,
+                market: Market::TrifectaBox { horses },
+            };
+            let contract = test::callee::<DefaultEnvironment>();
+            for (bettor, horses) in [(bob, vec![0, 1, 2]), (charlie, vec![1, 2, 3])] {
+                test::set_account_balance::<DefaultEnvironment>(contract, balance(contract) + 600);
+                set_caller(bettor);
+                test::set_value_transferred::<DefaultEnvironment>(600);
+                platform.bet(market(horses)).unwrap();
             }
 
-            Ok(())
+            // Six permutations of 100 each, in a 1200 trifecta pool
+            let info = platform.get_market(market(vec![0, 1, 2])).unwrap();
+            assert!(info.open);
+            assert_eq!((info.pool, info.total_pool, info.odds_bps), (600, 1_200, Some(20_000)));
+
+            let race = run_race(&mut platform, race_id);
+            assert_eq!(race.rankings[..3], [0, 1, 2]);
+
+            set_caller(bob);
+            assert_eq!(platform.claim(market(vec![0, 1, 2])), Ok(1_200));
+            assert_eq!(platform.claim(market(vec![0, 1, 2])), Err(Error::NoWinningBets));
+            set_caller(charlie);
+            assert_eq!(platform.claim(market(vec![1, 2, 3])), Err(Error::NoWinningBets));
         }
 
-        /// Finish a race
-        fn finish_race(&mut self, race_id: u8) -> Result<(), Error> {
-            let mut race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
-            
-            if race.status != RaceStatus::Active {
-                return Err(Error::RaceNotActive);
-            }
+        /// Enable staking with every house fee going to stakers and epochs that close at
+        /// each race settlement
+        fn enable_staking(platform: &mut KareraPlatform) {
+            set_caller(accounts().alice);
+            platform.set_house_fee(1_000).unwrap();
+            platform
+                .set_staking(StakingConfig {
+                    token: accounts().frank,
+                    fee_share_bps: 10_000,
+                    epoch_blocks: 1,
+                })
+                .unwrap();
+        }
 
-            // Collect and sort horses
-            let mut horses_vec = Vec::new();
-            for i in 0..HORSES_PER_RACE {
-                if let Some(horse) = self.horses.get((race_id, i)) {
-                    horses_vec.push(horse);
-                }
-            }
+        /// Settle a race taking a 100 fee, closing the staking epoch
+        fn settle_fee_race(platform: &mut KareraPlatform) {
+            favour(&[0]);
+            let race_id = create_race(platform, 4);
+            bet(platform, accounts().django, race_id, 0, 1_000).unwrap();
+            run_race(platform, race_id);
+        }
 
-            horses_vec.sort_by(|a, b| {
-                match (a.finished, b.finished) {
-                    (true, true) => a.finish_time.cmp(&b.finish_time),
-                    (true, false) => core::cmp::Ordering::Less,
-                    (false, true) => core::cmp::Ordering::Greater,
-                    (false, false) => b.position.cmp(&a.position),
-                }
-            });
+        #[ink::test]
+        fn stake_activates_the_epoch_after_staking_and_shares_fees_pro_rata() {
+            let (bob, charlie) = (accounts().bob, accounts().charlie);
+            let mut platform = deploy();
+            enable_staking(&mut platform);
 
-            race.rankings.clear();
-            for horse in horses_vec.iter() {
-                race.rankings.push(horse.id);
-            }
+            assert_eq!(platform.add_stake(bob, 100), 1);
+            assert_eq!(platform.add_stake(charlie, 300), 1);
+            let stake = platform.get_stake(bob);
+            assert_eq!((stake.pending, stake.active), (100, 0));
 
-            race.winner = Some(race.rankings[0]);
-            race.status = RaceStatus::Finished;
+            // Epoch 0 has no active stake, so its fees carry over into epoch 1
+            settle_fee_race(&mut platform);
+            assert_eq!(platform.get_staking_epoch().2, 100);
+            let stake = platform.get_stake(bob);
+            assert_eq!((stake.pending, stake.active, stake.rewards), (0, 100, 0));
 
-            self.races.insert(race_id, &race);
+            settle_fee_race(&mut platform);
+            assert_eq!(platform.get_stake(bob).rewards, 50);
+            assert_eq!(platform.get_stake(charlie).rewards, 150);
 
-            self.env().emit_event(RaceFinished {
-                race_id,
-                winner: race.rankings[0],
-                rankings: race.rankings.clone(),
-            });
+            set_caller(bob);
+            assert_eq!(platform.claim_rewards(), Ok(50));
+            assert_eq!(platform.claim_rewards(), Err(Error::NothingToClaim));
+        }
 
-            Ok(())
+        #[ink::test]
+        fn stake_withdrawn_before_the_epoch_closes_earns_nothing_from_it() {
+            let (bob, charlie) = (accounts().bob, accounts().charlie);
+            let mut platform = deploy();
+            enable_staking(&mut platform);
+
+            platform.add_stake(bob, 100);
+            platform.add_stake(charlie, 100);
+            settle_fee_race(&mut platform);
+
+            assert_eq!(platform.remove_stake(charlie, 100), Ok(()));
+            settle_fee_race(&mut platform);
+            assert_eq!(platform.get_stake(bob).rewards, 200);
+            assert_eq!(platform.get_stake(charlie).rewards, 0);
         }
 
-        /// Place a bet on a horse
-        #[ink(message, payable)]
-        pub fn place_bet(&mut self, race_id: u8, horse_id: u8) -> Result<(), Error> {
-            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
-            
-            if race.status != RaceStatus::Pending {
-                return Err(Error::BettingClosed);
-            }
+        #[ink::test]
+        fn transferred_ticket_pays_its_new_holder_instead_of_the_bettor() {
+            let (bob, charlie, django) = (accounts().bob, accounts().charlie, accounts().django);
+            let mut platform = deploy();
+            favour(&[0]);
 
-            if horse_id >= HORSES_PER_RACE {
-                return Err(Error::InvalidHorse);
-            }
+            let race_id = create_race(&mut platform, 4);
+            bet(&mut platform, bob, race_id, 0, 100).unwrap();
+            bet(&mut platform, charlie, race_id, 1, 300).unwrap();
 
-            let bettor = self.env().caller();
-            let amount = self.env().transferred_value();
+            set_caller(bob);
+            assert_eq!(platform.transfer(django, Id::U64(0), Vec::new()), Ok(()));
+            assert_eq!(platform.owner_of(Id::U64(0)), Some(django));
+            assert_eq!((platform.balance_of(bob), platform.balance_of(django)), (0, 1));
 
-            if amount == 0 {
-                return Err(Error::InvalidBetAmount);
-            }
+            run_race(&mut platform, race_id);
+            assert_eq!(claim(&mut platform, bob, race_id), Err(Error::NoWinningBets));
+            assert_eq!(claim(&mut platform, django, race_id), Ok((400, 400)));
+            assert_eq!(platform.owner_of(Id::U64(0)), None);
+            assert_eq!(platform.total_supply(), 1);
+        }
 
-            let bet = Bet {
-                bettor,
+        #[ink::test]
+        fn ticket_sent_back_to_its_bettor_is_paid_once() {
+            let (bob, charlie, django) = (accounts().bob, accounts().charlie, accounts().django);
+            let mut platform = deploy();
+            favour(&[0]);
+
+            let race_id = create_race(&mut platform, 4);
+            bet(&mut platform, bob, race_id, 0, 100).unwrap();
+            bet(&mut platform, charlie, race_id, 1, 300).unwrap();
+
+            set_caller(bob);
+            platform.transfer(django, Id::U64(0), Vec::new()).unwrap();
+            set_caller(django);
+            platform.transfer(bob, Id::U64(0), Vec::new()).unwrap();
+            assert_eq!(platform.owner_of(Id::U64(0)), Some(bob));
+            assert_eq!((platform.balance_of(bob), platform.balance_of(django)), (1, 0));
+
+            run_race(&mut platform, race_id);
+            assert_eq!(claim(&mut platform, django, race_id), Err(Error::NoWinningBets));
+            assert_eq!(claim(&mut platform, bob, race_id), Ok((400, 400)));
+            assert_eq!(claim(&mut platform, bob, race_id), Err(Error::AlreadyClaimed));
+        }
+
+        #[ink::test]
+        fn refunds_burn_the_refunded_tickets() {
+            let bob = accounts().bob;
+            let mut platform = deploy();
+
+            let race_id = create_race(&mut platform, 4);
+            bet(&mut platform, bob, race_id, 0, 100).unwrap();
+            bet(&mut platform, bob, race_id, 2, 50).unwrap();
+            assert_eq!((platform.balance_of(bob), platform.total_supply()), (2, 2));
+
+            set_caller(accounts().alice);
+            platform.scratch_horse(race_id, 2).unwrap();
+            set_caller(bob);
+            assert_eq!(platform.claim_scratch_refund(race_id), Ok(50));
+            assert_eq!(platform.owner_of(Id::U64(1)), None);
+            assert_eq!((platform.balance_of(bob), platform.total_supply()), (1, 1));
+
+            set_caller(accounts().alice);
+            platform.cancel_race(race_id).unwrap();
+            set_caller(bob);
+            assert_eq!(platform.claim_refund(race_id), Ok(100));
+            assert_eq!(platform.owner_of(Id::U64(0)), None);
+            assert_eq!((platform.balance_of(bob), platform.total_supply()), (0, 0));
+        }
+
+        #[ink::test]
+        fn overpaying_claim_reverts_as_insolvent() {
+            let (bob, charlie) = (accounts().bob, accounts().charlie);
+            let mut platform = deploy();
+            favour(&[0]);
+
+            let (settled, open) = (create_race(&mut platform, 4), create_race(&mut platform, 4));
+            bet(&mut platform, bob, settled, 0, 100).unwrap();
+            bet(&mut platform, charlie, settled, 1, 300).unwrap();
+            bet(&mut platform, charlie, open, 1, 500).unwrap();
+            run_race(&mut platform, settled);
+            let (liabilities, funds) = platform.get_solvency();
+            assert_eq!(liabilities, 900);
+
+            // Mispriced winner odds paying out every spare unit and 600 more, 200 of it
+            // out of the open race's pool
+            platform.race_odds.insert(settled, &(funds - liabilities + 600, 100));
+            assert_eq!(claim(&mut platform, bob, settled), Err(Error::Insolvent));
+        }
+
+        #[ink::test]
+        fn emergency_withdraw_leaves_open_parlay_and_scratched_stakes() {
+            let (bob, django, frank) = (accounts().bob, accounts().django, accounts().frank);
+            let mut platform = deploy();
+            platform.accrued_fees = 2_450;
+
+            let (first, second) = (create_race(&mut platform, 4), create_race(&mut platform, 4));
+            bet(&mut platform, bob, first, 2, 100).unwrap();
+            parlay(&mut platform, django, vec![(first, 0), (second, 0)], 50);
+            set_caller(accounts().alice);
+            platform.scratch_horse(first, 2).unwrap();
+            // The scratched stake, the parlay stake and the fees reserved for its winnings
+            assert_eq!(platform.get_solvency().0, 2_600);
+
+            let contract = test::callee::<DefaultEnvironment>();
+            let executable_at = platform.initiate_emergency_withdraw().unwrap();
+            test::set_block_number::<DefaultEnvironment>(executable_at);
+            let unattributed = balance(contract) - 2_600;
+            assert_eq!(platform.finalize_emergency_withdraw(frank), Ok(unattributed));
+            assert_eq!(balance(contract), 2_600);
+        }
+
+        #[ink::test]
+        fn validate_slip_counts_earlier_legs_against_bet_and_horse_limits() {
+            let bob = accounts().bob;
+            let mut platform = deploy();
+            platform
+                .set_bet_rate_limits(BetRateLimits {
+                    max_bets_per_race: 2,
+                    max_bets_per_window: 0,
+                    window_blocks: 0,
+                })
+                .unwrap();
+
+            let race_id = create_race(&mut platform, 4);
+            platform
+                .set_pool_caps(race_id, PoolCaps {
+                    per_horse: Some(150),
+                    per_race: None,
+                })
+                .unwrap();
+            let leg = |horse_id, amount| SlipLeg {
                 race_id,
                 horse_id,
                 amount,
             };
 
-            // Store bet
-            let count = self.bet_count.get((bettor, race_id)).unwrap_or(0);
-            self.bets.insert((bettor, race_id, count), &bet);
-            self.bet_count.insert((bettor, race_id), &(count + 1));
+            let results = platform.validate_slip(bob, vec![leg(0, 100), leg(0, 100), leg(1, 100)], 300);
+            assert_eq!(results, Ok(vec![Ok(()), Err(Error::PoolCapReached), Ok(())]));
 
-            // Update pools
-            let total = self.total_pool.get(race_id).unwrap_or(0);
-            self.total_pool.insert(race_id, &(total + amount));
+            let results = platform.validate_slip(bob, vec![leg(0, 50), leg(1, 50), leg(2, 50)], 150);
+            assert_eq!(results, Ok(vec![Ok(()), Ok(()), Err(Error::TooManyBets { max_bets: 2 })]));
 
-            let horse_total = self.horse_pools.get((race_id, horse_id)).unwrap_or(0);
-            self.horse_pools.insert((race_id, horse_id), &(horse_total + amount));
+            // Leg amounts wrapping around to the value are rejected, not recorded
+            let results = platform.validate_slip(bob, vec![leg(0, u128::MAX), leg(1, 101)], 100);
+            assert_eq!(results, Err(Error::InvalidBetAmount));
+        }
 
-            self.env().emit_event(BetPlaced {
-                bettor,
-                race_id,
-                horse_id,
-                amount,
-            });
+        #[ink::test]
+        fn referral_commission_set_aside_at_settlement_is_paid_after_archiving() {
+            let (bob, charlie, django) = (accounts().bob, accounts().charlie, accounts().django);
+            let mut platform = deploy();
+            platform.set_house_fee(500).unwrap();
+            platform.set_referral_share(2_000).unwrap();
+            favour(&[0]);
 
-            Ok(())
+            let race_id = create_race(&mut platform, 4);
+            let contract = test::callee::<DefaultEnvironment>();
+            test::set_account_balance::<DefaultEnvironment>(contract, balance(contract) + 1_000);
+            set_caller(bob);
+            test::set_value_transferred::<DefaultEnvironment>(1_000);
+            platform.place_bet_with_referrer(race_id, 0, charlie).unwrap();
+            bet(&mut platform, django, race_id, 1, 1_000).unwrap();
+            let race = run_race(&mut platform, race_id);
+
+            assert_eq!(claim(&mut platform, bob, race_id), Ok((1_900, 1_900)));
+            test::set_block_number::<DefaultEnvironment>(race.settled_at + ARCHIVE_GRACE_BLOCKS);
+            set_caller(accounts().alice);
+            assert_eq!(platform.archive_race(race_id), Ok(true));
+
+            // A 100 fee on 2000, half of it on referred bets, at a 20% referral share
+            set_caller(charlie);
+            assert_eq!(platform.claim_referral_earnings(), Ok(10));
+            assert_eq!(platform.claim_referral_earnings(), Err(Error::NothingToClaim));
         }
 
-        /// Claim winnings
-        #[ink(message)]
-        pub fn claim_winnings(&mut self, race_id: u8) -> Result<Balance, Error> {
-            let race = self.races.get(race_id).ok_or(Error::RaceNotFound)?;
-            
-            if race.status != RaceStatus::Finished {
-                return Err(Error::RaceNotFinished);
-            }
+        #[ink::test]
+        fn asset_pools_settle_and_pay_each_asset_before_the_race_archives() {
+            let (bob, charlie, django) = (accounts().bob, accounts().charlie, accounts().django);
+            let (gold, silver) = (AccountId::from([0xA1; 32]), AccountId::from([0xA2; 32]));
+            let mut platform = deploy();
+            platform.set_house_fee(500).unwrap();
+            favour(&[0]);
 
-            let caller = self.env().caller();
-            let claimed = self.payouts_claimed.get((caller, race_id)).unwrap_or(false);
-            
-            if claimed {
-                return Err(Error::AlreadyClaimed);
-            }
+            // PSP22 pulls cannot run off-chain, so the bets are recorded as if pulled
+            let race_id = create_race(&mut platform, 4);
+            let race = platform.get_race(race_id).unwrap();
+            platform.record_asset_bet(bob, &race, 0, gold, 300);
+            platform.record_asset_bet(charlie, &race, 1, gold, 700);
+            platform.record_asset_bet(charlie, &race, 0, silver, 400);
+            platform.record_asset_bet(django, &race, 2, silver, 600);
+            let race = run_race(&mut platform, race_id);
 
-            let winner_horse = race.winner.ok_or(Error::NoWinner)?;
-            
-            // Calculate total bet on winning horse
-            let bet_count = self.bet_count.get((caller, race_id)).unwrap_or(0);
-            let mut total_bet = 0u128;
-            
-            for i in 0..bet_count {
-                if let Some(bet) = self.bets.get((caller, race_id, i)) {
-                    if bet.horse_id == winner_horse {
-                        total_bet += bet.amount;
-                    }
-                }
-            }
+            // Each pool of 1000 keeps a 50 fee in its own asset
+            assert_eq!(platform.get_asset_fees(gold), 50);
+            assert_eq!(platform.get_asset_fees(silver), 50);
+            assert_eq!(platform.take_asset_payouts(bob, &race), Ok(vec![(gold, 950)]));
+            assert_eq!(platform.take_asset_payouts(bob, &race), Ok(vec![]));
 
-            if total_bet == 0 {
-                return Err(Error::NoWinningBets);
-            }
+            test::set_block_number::<DefaultEnvironment>(race.settled_at + ARCHIVE_GRACE_BLOCKS);
+            set_caller(accounts().alice);
+            assert_eq!(platform.archive_race(race_id), Err(Error::UnclaimedPayouts));
 
-            let total_pool = self.total_pool.get(race_id).unwrap_or(0);
-            let winning_pool = self.horse_pools.get((race_id, winner_horse)).unwrap_or(0);
+            assert_eq!(platform.take_asset_payouts(charlie, &race), Ok(vec![(silver, 950)]));
+            assert_eq!(platform.get_asset_season_pnl(charlie, race.season, gold), SeasonPnl { staked: 700, returned: 0 });
+            assert_eq!(platform.get_asset_season_pnl(charlie, race.season, silver), SeasonPnl { staked: 400, returned: 950 });
+            assert_eq!(platform.take_asset_payouts(django, &race), Ok(vec![]));
+            assert_eq!(platform.archive_race(race_id), Ok(true));
+        }
 
-            if winning_pool == 0 {
-                return Err(Error::NoWinningBets);
-            }
+        #[ink::test]
+        fn cancelled_live_and_trifecta_stakes_are_refunded_and_released() {
+            let (bob, charlie) = (accounts().bob, accounts().charlie);
+            let mut platform = deploy();
+            favour(&[0]);
 
-            // Payout = (user_bet / winning_pool) * total_pool
-            let payout = (total_bet * total_pool) / winning_pool;
+            let race_id = create_race(&mut platform, 4);
+            let contract = test::callee::<DefaultEnvironment>();
+            test::set_account_balance::<DefaultEnvironment>(contract, balance(contract) + 600);
+            set_caller(bob);
+            test::set_value_transferred::<DefaultEnvironment>(600);
+            let ticket_id = platform.place_trifecta_box(race_id, vec![0, 1, 2]).unwrap();
 
-            // Mark as claimed
-            self.payouts_claimed.insert((caller, race_id), &true);
+            set_caller(accounts().alice);
+            platform.start_race(race_id).unwrap();
+            advance_blocks(1);
+            platform.update_race(race_id).unwrap();
+            test::set_account_balance::<DefaultEnvironment>(contract, balance(contract) + 400);
+            set_caller(charlie);
+            test::set_value_transferred::<DefaultEnvironment>(400);
+            platform.place_live_bet(race_id, 1).unwrap();
 
-            // Transfer winnings
-            if self.env().transfer(caller, payout).is_err() {
-                return Err(Error::TransferFailed);
-            }
+            set_caller(accounts().alice);
+            platform.cancel_race(race_id).unwrap();
+            assert_eq!(platform.get_solvency().0, 1_000);
 
-            Ok(payout)
+            set_caller(bob);
+            assert_eq!(platform.claim_trifecta(ticket_id), Ok(600));
+            set_caller(charlie);
+            assert_eq!(platform.claim_live_winnings(race_id), Ok(400));
+            assert_eq!(platform.get_solvency().0, 0);
         }
 
-        /// Get race details
-        #[ink(message)]
-        pub fn get_race(&self, race_id: u8) -> Option<Race> {
-            self.races.get(race_id)
-        }
+        #[ink::test]
+        fn migrate_packs_v1_horse_entries_into_race_horses() {
+            let mut platform = deploy();
+            let races: Vec<u8> = (0..3).map(|_| create_race(&mut platform, 4)).collect();
+            set_caller(accounts().alice);
+            platform.scratch_horse(races[1], 2).unwrap();
+            let field = |platform: &KareraPlatform, race_id: u8| -> Vec<(u8, bool)> {
+                platform.get_all_horses(race_id).iter().map(|horse| (horse.id, horse.scratched)).collect()
+            };
+            let before: Vec<_> = races.iter().map(|race_id| field(&platform, *race_id)).collect();
 
-        /// Get horse details
-        #[ink(message)]
-        pub fn get_horse(&self, race_id: u8, horse_id: u8) -> Option<Horse> {
-            self.horses.get((race_id, horse_id))
+            // Roll the races back to the v1 layout of one entry per horse
+            for race_id in races.iter().copied() {
+                let horses = platform.race_horses.take(race_id).unwrap();
+                for (i, horse) in horses.iter().enumerate() {
+                    platform.horses.insert((race_id, i as u8), horse);
+                }
+            }
+            platform.storage_version = 1;
+            assert!(platform.is_scratched(races[1], 2));
+
+            assert_eq!(platform.migrate(), Ok(true));
+            assert_eq!(platform.get_storage_version(), STORAGE_VERSION);
+            for (race_id, horses) in races.iter().zip(before) {
+                assert_eq!(platform.race_horses.get(race_id).unwrap().len(), 4);
+                assert!(!platform.horses.contains((*race_id, 0)));
+                assert_eq!(field(&platform, *race_id), horses);
+            }
+            assert_eq!(platform.migrate(), Err(Error::AlreadyMigrated));
         }
 
-        /// Get all horses for a race
-        #[ink(message)]
-        pub fn get_all_horses(&self, race_id: u8) -> Vec<Horse> {
-            let mut horses = Vec::new();
-            for i in 0..HORSES_PER_RACE {
-                if let Some(horse) = self.horses.get((race_id, i)) {
-                    horses.push(horse);
-                }
+        #[ink::test]
+        fn tournament_with_no_finished_race_refunds_every_stake_without_a_fee() {
+            let (bob, charlie) = (accounts().bob, accounts().charlie);
+            let mut platform = deploy();
+            platform.set_house_fee(500).unwrap();
+
+            let races = vec![create_race(&mut platform, 4), create_race(&mut platform, 4)];
+            let tournament_id = platform.create_tournament(races.clone(), vec![3, 1]).unwrap();
+            let contract = test::callee::<DefaultEnvironment>();
+            for (bettor, horse_id, amount) in [(bob, 0, 300), (bob, 1, 100), (charlie, 2, 500)] {
+                test::set_account_balance::<DefaultEnvironment>(contract, balance(contract) + amount);
+                set_caller(bettor);
+                test::set_value_transferred::<DefaultEnvironment>(amount);
+                platform.bet_on_champion(tournament_id, horse_id).unwrap();
             }
-            horses
+            set_caller(accounts().django);
+            platform.set_loss_cap(Some(100)).unwrap();
+            test::set_value_transferred::<DefaultEnvironment>(200);
+            assert_eq!(platform.bet_on_champion(tournament_id, 0), Err(Error::LossCapReached { cap: 100 }));
+
+            set_caller(accounts().alice);
+            for race_id in races.iter().copied() {
+                platform.cancel_race(race_id).unwrap();
+            }
+            platform.settle_tournament(tournament_id).unwrap();
+            assert_eq!(platform.get_accrued_fees(), 0);
+            assert_eq!(platform.get_solvency().0, 900);
+
+            let season = platform.get_race(races[0]).unwrap().season;
+            assert_eq!(platform.get_season_pnl(bob, season), SeasonPnl { staked: 400, returned: 0 });
+            set_caller(bob);
+            assert_eq!(platform.claim_tournament_winnings(tournament_id), Ok(400));
+            assert_eq!(platform.get_season_pnl(bob, season), SeasonPnl { staked: 400, returned: 400 });
+            assert_eq!(platform.claim_tournament_winnings(tournament_id), Err(Error::AlreadyClaimed));
+            set_caller(charlie);
+            assert_eq!(platform.claim_tournament_winnings(tournament_id), Ok(500));
+            assert_eq!(platform.get_solvency().0, 0);
         }
 
-        /// Get current race count
-        #[ink(message)]
-        pub fn get_race_count(&self) -> u8 {
-            self.current_race_id
+        #[ink::test]
+        fn asset_bets_face_the_loss_cap_and_rate_limits() {
+            let bob = accounts().bob;
+            let gold = AccountId::from([0xA1; 32]);
+            let mut platform = deploy();
+            platform.set_asset_approved(gold, true).unwrap();
+            platform
+                .set_bet_rate_limits(BetRateLimits {
+                    max_bets_per_race: 0,
+                    max_bets_per_window: 1,
+                    window_blocks: 100,
+                })
+                .unwrap();
+            let race_id = create_race(&mut platform, 4);
+
+            // The cap holds the asset's own stakes, apart from native value
+            set_caller(bob);
+            platform.set_loss_cap(Some(500)).unwrap();
+            assert_eq!(platform.place_asset_bet(race_id, 0, gold, 600), Err(Error::LossCapReached { cap: 500 }));
+
+            bet(&mut platform, bob, race_id, 1, 100).unwrap();
+            set_caller(bob);
+            assert!(matches!(
+                platform.place_asset_bet(race_id, 0, gold, 100),
+                Err(Error::BetRateLimited { .. })
+            ));
         }
 
-        /// Get total pool for a race
-        #[ink(message)]
-        pub fn get_total_pool(&self, race_id: u8) -> Balance {
-            self.total_pool.get(race_id).unwrap_or(0)
+        #[ink::test]
+        fn estimate_payout_holds_winnings_to_the_payout_cap() {
+            let mut platform = deploy();
+
+            let pool_race = create_race(&mut platform, 4);
+            bet(&mut platform, accounts().charlie, pool_race, 1, 900).unwrap();
+            set_caller(accounts().alice);
+            platform.set_payout_cap(pool_race, Some(20_000)).unwrap();
+            // 100 would take the whole 1000 pool, capped at twice the stake
+            assert_eq!(platform.estimate_payout(pool_race, 0, 100), 200);
+
+            let odds_race = create_race(&mut platform, 4);
+            platform.set_bet_mode(odds_race, BetMode::FixedOdds).unwrap();
+            platform.set_fixed_odds(odds_race, vec![50_000, 30_000, 20_000, 15_000]).unwrap();
+            platform.set_payout_cap(odds_race, Some(25_000)).unwrap();
+            assert_eq!(platform.estimate_payout(odds_race, 0, 100), 250);
+            assert_eq!(platform.estimate_payout(odds_race, 2, 100), 200);
         }
 
-        /// Get horse pool
-        #[ink(message)]
-        pub fn get_horse_pool(&self, race_id: u8, horse_id: u8) -> Balance {
-            self.horse_pools.get((race_id, horse_id)).unwrap_or(0)
+        #[ink::test]
+        fn referred_bet_over_the_pool_cap_refunds_the_excess() {
+            let (bob, charlie) = (accounts().bob, accounts().charlie);
+            let mut platform = deploy();
+            let race_id = create_race(&mut platform, 4);
+            platform
+                .set_pool_caps(race_id, PoolCaps {
+                    per_horse: Some(200),
+                    per_race: None,
+                })
+                .unwrap();
+
+            let contract = test::callee::<DefaultEnvironment>();
+            test::set_account_balance::<DefaultEnvironment>(contract, balance(contract) + 300);
+            set_caller(bob);
+            test::set_value_transferred::<DefaultEnvironment>(300);
+            let before = balance(bob);
+            assert_eq!(platform.place_bet_with_referrer(race_id, 0, charlie), Ok(()));
+
+            assert_eq!(balance(bob) - before, 100);
+            assert_eq!(platform.horse_pools.get((race_id, 0)), Some(200));
+            assert_eq!(platform.referred_handle.get((charlie, race_id)), Some(200));
         }
 
-        /// Pseudo-random number generator
-        fn pseudo_random(&self, seed: u8, block: u32) -> u32 {
-            let hash = self.env().block_timestamp() as u32 
-                ^ block 
-                ^ (seed as u32);
-            
-            // Simple LCG algorithm
-            let a: u32 = 1664525;
-            let c: u32 = 1013904223;
-            (a.wrapping_mul(hash).wrapping_add(c)) % 100
+        #[ink::test]
+        fn scripted_rolls_replay_the_same_result() {
+            script_rolls(|seed: u8, block: u32| (seed as u32 * 7 + block * 3) % 21);
+
+            // The same race id run over the same blocks on two deployments
+            let mut results = Vec::new();
+            for _ in 0..2 {
+                let mut platform = deploy();
+                test::set_block_number::<DefaultEnvironment>(100);
+                let race_id = create_race(&mut platform, 6);
+                let race = run_race(&mut platform, race_id);
+                assert_eq!(platform.verify_race(race_id), Ok(true));
+                results.push(race);
+            }
+
+            assert_eq!(results[0].rankings.len(), 6);
+            assert_eq!(results[0].rankings, results[1].rankings);
+            assert_eq!(results[0].winners, results[1].winners);
         }
     }
 
-    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
-    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
-    pub enum Error {
-        RaceNotFound,
-        RaceNotActive,
-        RaceAlreadyStarted,
-        RaceNotFinished,
-        MaxRacesReached,
-        InvalidHorse,
-        BettingClosed,
-        InvalidBetAmount,
-        NoWinner,
-        NoWinningBets,
-        AlreadyClaimed,
-        TransferFailed,
-        Unauthorized,
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use ink_e2e::ContractsBackend;
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        #[ink_e2e::test]
+        async fn race_lifecycle_on_chain<Client: E2EBackend>(mut client: Client) -> E2EResult<()> {
+            let mut constructor = KareraPlatformRef::new();
+            let contract = client
+                .instantiate("karera_platform", &ink_e2e::alice(), &mut constructor)
+                .submit()
+                .await
+                .expect("instantiate failed");
+            let mut call_builder = contract.call_builder::<KareraPlatform>();
+
+            let create = call_builder.create_race(4);
+            let race_id = client
+                .call(&ink_e2e::alice(), &create)
+                .submit()
+                .await
+                .expect("create_race failed")
+                .return_value()
+                .expect("race not created");
+
+            let place_bet = call_builder.place_bet(race_id, 0);
+            client
+                .call(&ink_e2e::bob(), &place_bet)
+                .value(1_000_000_000_000)
+                .submit()
+                .await
+                .expect("place_bet failed");
+
+            let start = call_builder.start_race(race_id);
+            client.call(&ink_e2e::alice(), &start).submit().await.expect("start_race failed");
+
+            let get_race = call_builder.get_race(race_id);
+            let race = client.call(&ink_e2e::alice(), &get_race).dry_run().await?.return_value();
+            assert_eq!(race.map(|race| race.status), Some(RaceStatus::Active));
+
+            let get_pool = call_builder.get_total_pool(race_id);
+            let pool = client.call(&ink_e2e::alice(), &get_pool).dry_run().await?.return_value();
+            assert_eq!(pool, 1_000_000_000_000);
+
+            Ok(())
+        }
     }
-}
\ No newline at end of file
+}